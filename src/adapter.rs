@@ -0,0 +1,275 @@
+use crate::{policy::Insertion, validator::Validator, ChainMap};
+use std::collections::{hash_map::RandomState, HashMap};
+use std::hash::{BuildHasher, Hash};
+use std::sync::Arc;
+
+/// Maps keys to the form they are compared in, e.g. lowercasing them for
+/// case insensitive lookups, see [`ChainMap::with_key_adapter`].
+///
+/// Implemented for closures taking a key and returning its normalized form.
+pub trait KeyAdapter<Q: ?Sized> {
+    /// The normalized form of a key
+    type Key: Hash + Eq;
+
+    fn adapt(&self, key: &Q) -> Self::Key;
+}
+
+impl<F, Q, N> KeyAdapter<Q> for F
+where
+    F: Fn(&Q) -> N,
+    Q: ?Sized,
+    N: Hash + Eq,
+{
+    type Key = N;
+
+    fn adapt(&self, key: &Q) -> N {
+        self(key)
+    }
+}
+
+/// Compares string keys ignoring case, as with Windows environment
+/// variables.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CaseInsensitive;
+
+impl<Q: AsRef<str> + ?Sized> KeyAdapter<Q> for CaseInsensitive {
+    type Key = String;
+
+    fn adapt(&self, key: &Q) -> String {
+        key.as_ref().to_lowercase()
+    }
+}
+
+/// A chain whose keys are compared through a [`KeyAdapter`], created with
+/// [`ChainMap::with_key_adapter`].
+///
+/// Keys are stored as they were inserted, and every lookup, insert and
+/// removal goes through the adapter, in every layer.
+pub struct AdaptedChainMap<K, V, A: KeyAdapter<K>, S = RandomState> {
+    chain: ChainMap<A::Key, (K, V), S>,
+    adapter: A,
+}
+
+impl<K, V, S> ChainMap<K, V, S>
+where
+    K: Hash + Eq + 'static,
+    V: 'static,
+    S: BuildHasher + Clone,
+{
+    /// Rebuilds the chain so that keys are compared by their adapted form.
+    /// If several keys of one layer adapt to the same form, the one
+    /// visited last is kept.
+    ///
+    /// The layers keep the chain's hasher, and its shadow and write
+    /// policies and validator carry over to the adapted chain.
+    pub fn with_key_adapter<A>(self, adapter: A) -> AdaptedChainMap<K, V, A, S>
+    where
+        A: KeyAdapter<K>,
+    {
+        let mut this = self;
+        this.force_lazy();
+        let maps = this
+            .maps
+            .into_iter()
+            .map(|map| {
+                let mut adapted =
+                    HashMap::with_capacity_and_hasher(map.len(), map.hasher().clone());
+                adapted.extend(map.into_iter().map(|(k, v)| (adapter.adapt(&k), (k, v))));
+                adapted
+            })
+            .collect();
+        let mut chain = ChainMap::from_maps(maps);
+        chain.shadow_policy = this.shadow_policy;
+        chain.write_policy = this.write_policy;
+        chain.validator = this.validator.map(|check| -> Validator<_, _> {
+            Arc::new(move |_, pair: &(K, V)| check(&pair.0, &pair.1))
+        });
+        AdaptedChainMap { chain, adapter }
+    }
+}
+
+impl<K, V, A: KeyAdapter<K>> AdaptedChainMap<K, V, A> {
+    pub fn new(adapter: A) -> Self {
+        Self {
+            chain: ChainMap::from_maps(vec![HashMap::new()]),
+            adapter,
+        }
+    }
+}
+
+impl<K, V, A: KeyAdapter<K>, S: BuildHasher> AdaptedChainMap<K, V, A, S> {
+    /// Inserts a key-value pair into the current scope, honoring the
+    /// shadow policy and validator carried over from the original chain,
+    /// see [`ChainMap::insert_checked`].
+    pub fn insert_checked(&mut self, key: K, value: V) -> Result<Insertion<V>, crate::Error> {
+        let adapted = self.adapter.adapt(&key);
+        Ok(match self.chain.insert_checked(adapted, (key, value))? {
+            Insertion::Vacant => Insertion::Vacant,
+            Insertion::Replaced((_, v)) => Insertion::Replaced(v),
+            Insertion::Shadowed => Insertion::Shadowed,
+        })
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key,
+    /// following the write policy carried over from the original chain.
+    pub fn get_mut_for_write<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        A: KeyAdapter<Q, Key = <A as KeyAdapter<K>>::Key>,
+        <A as KeyAdapter<K>>::Key: Clone,
+        K: Clone,
+        V: Clone,
+        Q: ?Sized,
+    {
+        let adapted = self.adapter.adapt(key);
+        self.chain.get_mut_for_write(&adapted).map(|(_, v)| v)
+    }
+
+    /// Inserts a key-value pair into the current scope, replacing any pair
+    /// in that scope whose key adapts to the same form.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let adapted = self.adapter.adapt(&key);
+        self.chain.insert(adapted, (key, value)).map(|(_, v)| v)
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        A: KeyAdapter<Q, Key = <A as KeyAdapter<K>>::Key>,
+        Q: ?Sized,
+    {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+
+    /// Returns the key as it was inserted along with its value.
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        A: KeyAdapter<Q, Key = <A as KeyAdapter<K>>::Key>,
+        Q: ?Sized,
+    {
+        let (k, v) = self.chain.get(&self.adapter.adapt(key))?;
+        Some((k, v))
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        A: KeyAdapter<Q, Key = <A as KeyAdapter<K>>::Key>,
+        Q: ?Sized,
+    {
+        let adapted = self.adapter.adapt(key);
+        self.chain.get_mut(&adapted).map(|(_, v)| v)
+    }
+
+    /// Removes the pair whose key adapts to the same form from the current
+    /// scope, returning the key as it was inserted along with its value. A
+    /// binding in an outer scope becomes visible again.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        A: KeyAdapter<Q, Key = <A as KeyAdapter<K>>::Key>,
+        Q: ?Sized,
+    {
+        let adapted = self.adapter.adapt(key);
+        self.chain.remove(&adapted)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        A: KeyAdapter<Q, Key = <A as KeyAdapter<K>>::Key>,
+        Q: ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn last_has<Q>(&self, key: &Q) -> bool
+    where
+        A: KeyAdapter<Q, Key = <A as KeyAdapter<K>>::Key>,
+        Q: ?Sized,
+    {
+        self.chain.last_has(&self.adapter.adapt(key))
+    }
+
+    pub fn new_child(&mut self)
+    where
+        S: Default,
+    {
+        self.chain.new_child();
+    }
+
+    /// Removes the current scope, returning its pairs keyed as they were
+    /// inserted.
+    pub fn remove_child(&mut self) -> Option<HashMap<K, V, S>>
+    where
+        K: Hash + Eq,
+        S: Clone + Default,
+    {
+        let map = self.chain.remove_child()?;
+        let mut ret = HashMap::with_capacity_and_hasher(map.len(), map.hasher().clone());
+        ret.extend(map.into_values());
+        Some(ret)
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.chain.child_len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn case_insensitive() {
+        let mut env = AdaptedChainMap::new(CaseInsensitive);
+        env.insert("Path".to_string(), 1);
+        env.new_child();
+        assert!(env.contains_key("PATH"));
+        env.insert("PATH".to_string(), 2);
+        assert_eq!(env.get("path"), Some(&2));
+        assert_eq!(
+            env.get_key_value("pAtH").map(|(k, _)| k.as_str()),
+            Some("PATH")
+        );
+        *env.get_mut("path").unwrap() += 1;
+        let removed = env.remove_child().unwrap();
+        assert_eq!(removed.get("PATH"), Some(&3));
+        assert_eq!(
+            env.get_key_value("path").map(|(k, _)| k.as_str()),
+            Some("Path")
+        );
+        env.new_child();
+        assert_eq!(env.remove("PATH"), None);
+        env.remove_child();
+        assert_eq!(env.remove("PATH"), Some(("Path".to_string(), 1)));
+        assert!(!env.contains_key("path"));
+    }
+
+    #[test]
+    fn adapt_existing_chain() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("a-b", 1);
+        let adapted = chain_map.with_key_adapter(|k: &&str| k.replace('-', "_"));
+        assert_eq!(adapted.get(&"a_b"), Some(&1));
+        assert!(adapted.last_has(&"a-b"));
+    }
+
+    #[test]
+    fn adapt_keeps_policies() {
+        use crate::{ShadowPolicy, WritePolicy};
+        let mut chain_map: ChainMap<String, u8> = ChainMap::default();
+        chain_map.insert("Key".to_string(), 1);
+        chain_map.set_shadow_policy(ShadowPolicy::Deny);
+        chain_map.set_write_policy(WritePolicy::CopyUp);
+        chain_map.set_validator(|_, v: &u8| if *v < 10 { Ok(()) } else { Err("too big") });
+        let mut adapted = chain_map.with_key_adapter(CaseInsensitive);
+        adapted.new_child();
+        assert!(matches!(
+            adapted.insert_checked("NEW".to_string(), 10),
+            Err(crate::Error::Invalid(_))
+        ));
+        assert!(matches!(
+            adapted.insert_checked("KEY".to_string(), 2),
+            Err(crate::Error::Shadowing)
+        ));
+        *adapted.get_mut_for_write("key").unwrap() += 1;
+        assert_eq!(adapted.remove_child().unwrap().get("Key"), Some(&2));
+        assert_eq!(adapted.get("key"), Some(&1));
+    }
+}
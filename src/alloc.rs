@@ -0,0 +1,145 @@
+use allocator_api2::{
+    alloc::{Allocator, Global},
+    vec::Vec,
+};
+use hashbrown::{DefaultHashBuilder, HashMap};
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+};
+
+/// A chain whose spine and layers are all allocated with `A`, so a whole
+/// chain can live in an arena and be released at once.
+///
+/// Layers are [`hashbrown`] maps since the std `HashMap` can't be given an
+/// allocator.
+pub struct ChainMapIn<K, V, A: Allocator + Clone = Global, S = DefaultHashBuilder> {
+    maps: Vec<HashMap<K, V, S, A>, A>,
+    alloc: A,
+}
+
+/// A [`ChainMapIn`] whose spine and layers are allocated from a bump
+/// arena. Dropping the chain doesn't return any memory to the arena; it is
+/// all released at once when the arena is.
+#[cfg(feature = "bumpalo")]
+pub type BumpChainMap<'bump, K, V, S = DefaultHashBuilder> =
+    ChainMapIn<K, V, &'bump bumpalo::Bump, S>;
+
+impl<K, V, A> ChainMapIn<K, V, A>
+where
+    K: Hash + Eq,
+    A: Allocator + Clone,
+{
+    pub fn new_in(alloc: A) -> Self {
+        Self::with_hasher_in(DefaultHashBuilder::default(), alloc)
+    }
+}
+
+impl<K, V, A, S> ChainMapIn<K, V, A, S>
+where
+    K: Hash + Eq,
+    A: Allocator + Clone,
+    S: BuildHasher + Clone,
+{
+    pub fn with_hasher_in(hash_builder: S, alloc: A) -> Self {
+        let mut maps = Vec::new_in(alloc.clone());
+        maps.push(HashMap::with_hasher_in(hash_builder, alloc.clone()));
+        Self { maps, alloc }
+    }
+
+    pub fn allocator(&self) -> &A {
+        &self.alloc
+    }
+
+    /// Inserts a key-value pair into the innermost layer.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.maps.last_mut()?.insert(key, value)
+    }
+
+    pub fn insert_at(&mut self, idx: usize, key: K, value: V) -> Result<Option<V>, crate::Error> {
+        if let Some(map) = self.maps.get_mut(idx) {
+            Ok(map.insert(key, value))
+        } else {
+            Err(crate::Error::IndexOutOfRange)
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.iter().rev().find_map(|map| map.get(key))
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.iter_mut().rev().find_map(|map| map.get_mut(key))
+    }
+
+    /// Pushes an empty layer allocated with the chain's allocator and
+    /// hasher.
+    pub fn new_child(&mut self) {
+        let hasher = self.maps[0].hasher().clone();
+        self.maps.push(HashMap::with_hasher_in(hasher, self.alloc.clone()));
+    }
+
+    pub fn new_child_with(&mut self, map: HashMap<K, V, S, A>) {
+        self.maps.push(map);
+    }
+
+    /// Removes the innermost layer. The last remaining layer is cleared
+    /// and returned in place of being removed.
+    pub fn remove_child(&mut self) -> Option<HashMap<K, V, S, A>> {
+        if self.maps.len() == 1 {
+            let hasher = self.maps[0].hasher().clone();
+            let empty = HashMap::with_hasher_in(hasher, self.alloc.clone());
+            Some(std::mem::replace(&mut self.maps[0], empty))
+        } else {
+            self.maps.pop()
+        }
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.maps.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scopes() {
+        let mut chain_map = ChainMapIn::new_in(Global);
+        chain_map.insert("x", 0);
+        chain_map.insert("y", 2);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        assert_eq!(chain_map.get("x"), Some(&1));
+        assert_eq!(chain_map.get("y"), Some(&2));
+        *chain_map.get_mut("y").unwrap() += 1;
+        let ret = chain_map.remove_child().unwrap();
+        assert_eq!(ret.get("x"), Some(&1));
+        assert_eq!(chain_map.get("x"), Some(&0));
+        assert_eq!(chain_map.get("y"), Some(&3));
+    }
+
+    #[cfg(feature = "bumpalo")]
+    #[test]
+    fn bump_layers() {
+        let bump = bumpalo::Bump::new();
+        let mut chain_map: BumpChainMap<u32, u32> = ChainMapIn::new_in(&bump);
+        chain_map.insert(0, 0);
+        chain_map.new_child();
+        for i in 0..64 {
+            chain_map.insert(i, i + 1);
+        }
+        assert_eq!(chain_map.get(&0), Some(&1));
+        assert!(bump.allocated_bytes() > 0);
+        drop(chain_map);
+    }
+}
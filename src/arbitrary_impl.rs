@@ -0,0 +1,75 @@
+use crate::{ChainMap, ChainSet};
+use arbitrary::{Arbitrary, Result, Unstructured};
+use std::{
+    collections::{HashMap, HashSet},
+    hash::{BuildHasher, Hash},
+};
+
+impl<'a, K, V, S> Arbitrary<'a> for ChainMap<K, V, S>
+where
+    K: Arbitrary<'a> + Hash + Eq,
+    V: Arbitrary<'a>,
+    S: BuildHasher + Default,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut maps: Vec<HashMap<K, V, S>> = u.arbitrary()?;
+        if maps.is_empty() {
+            maps.push(HashMap::default());
+        }
+        Ok(Self::from_maps(maps))
+    }
+}
+
+impl<'a, T> Arbitrary<'a> for ChainSet<T>
+where
+    T: Arbitrary<'a> + Hash + Eq,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let mut sets: Vec<HashSet<T>> = u.arbitrary()?;
+        if sets.is_empty() {
+            sets.push(HashSet::new());
+        }
+        Ok(Self { sets })
+    }
+}
+
+impl<'a, K, V, S> Arbitrary<'a> for crate::LockedChainMap<K, V, S>
+where
+    K: Arbitrary<'a> + Hash + Eq + Clone,
+    V: Arbitrary<'a> + Clone,
+    S: BuildHasher + Default + Clone,
+{
+    fn arbitrary(u: &mut Unstructured<'a>) -> Result<Self> {
+        let layers: Vec<Vec<(K, V)>> = u.arbitrary()?;
+        let mut maps: Vec<_> = layers
+            .into_iter()
+            .map(|l| l.into_iter().collect())
+            .collect();
+        if maps.is_empty() {
+            maps.push(Default::default());
+        }
+        Ok(Self { maps })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn never_empty() {
+        let mut u = Unstructured::new(&[]);
+        let chain_map: ChainMap<u8, u8> = ChainMap::arbitrary(&mut u).unwrap();
+        assert_eq!(chain_map.child_len(), 1);
+        let chain_set: ChainSet<u8> = ChainSet::arbitrary(&mut u).unwrap();
+        assert_eq!(chain_set.sets.len(), 1);
+    }
+
+    #[test]
+    fn random_layers() {
+        let data: Vec<u8> = (0..=255).cycle().take(1024).collect();
+        let mut u = Unstructured::new(&data);
+        let chain_map: ChainMap<u8, u8> = ChainMap::arbitrary(&mut u).unwrap();
+        assert!(chain_map.child_len() >= 1);
+    }
+}
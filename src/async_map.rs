@@ -0,0 +1,198 @@
+use crate::{ChainMap, ReadView};
+use async_lock::{RwLock, RwLockReadGuard, RwLockReadGuardArc};
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    ops::Deref,
+    sync::Arc,
+};
+
+/// A shared chain whose scopes are guarded by an async read-write lock, so
+/// tasks waiting on it yield to the executor instead of blocking a thread.
+///
+/// Cloning an `AsyncChainMap` produces another handle to the same chain.
+pub struct AsyncChainMap<K, V, S = RandomState> {
+    maps: Arc<RwLock<ReadView<K, V, S>>>,
+}
+
+/// A read lock on an [`AsyncChainMap`], see [`AsyncChainMap::read`].
+pub struct ReadGuard<'a, K, V, S = RandomState> {
+    view: RwLockReadGuard<'a, ReadView<K, V, S>>,
+}
+
+/// A read lock on an [`AsyncChainMap`] that owns a handle to the chain,
+/// so it can be held across `.await` points and moved between tasks.
+pub struct AsyncReadGuard<K, V, S = RandomState> {
+    view: RwLockReadGuardArc<ReadView<K, V, S>>,
+}
+
+impl<K, V, S> AsyncChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn new(map: HashMap<K, V, S>) -> Self {
+        Self {
+            maps: Arc::new(RwLock::new(ReadView { maps: vec![map] })),
+        }
+    }
+
+    /// Returns a copy of the value corresponding to the key.
+    pub async fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.read().await.get(key).cloned()
+    }
+
+    /// Inserts a key-value pair into the current scope.
+    pub async fn insert(&self, key: K, value: V) -> Option<V> {
+        self.maps.write().await.maps.last_mut()?.insert(key, value)
+    }
+
+    pub async fn new_child_with(&self, map: HashMap<K, V, S>) {
+        self.maps.write().await.maps.push(map);
+    }
+
+    pub async fn child_len(&self) -> usize {
+        self.maps.read().await.child_len()
+    }
+
+    /// Acquires a read lock, so that several lookups observe the same
+    /// state of the chain even while other tasks are waiting to write.
+    pub async fn read(&self) -> ReadGuard<'_, K, V, S> {
+        ReadGuard {
+            view: self.maps.read().await,
+        }
+    }
+
+    /// Acquires a read lock that can be held across `.await` points.
+    pub async fn read_owned(&self) -> AsyncReadGuard<K, V, S> {
+        AsyncReadGuard {
+            view: self.maps.read_arc().await,
+        }
+    }
+
+    /// Copies the current state of the chain into a regular [`ChainMap`].
+    pub async fn to_chain_map(&self) -> ChainMap<K, V, S>
+    where
+        K: Clone,
+        V: Clone,
+        S: Clone,
+    {
+        ChainMap::from_maps(self.maps.read().await.maps.clone())
+    }
+}
+
+impl<K, V, S> AsyncChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    pub async fn new_child(&self) {
+        self.new_child_with(HashMap::default()).await
+    }
+
+    /// Removes the current scope. As with [`ChainMap::remove_child`], the
+    /// last scope is cleared rather than removed.
+    pub async fn remove_child(&self) -> Option<HashMap<K, V, S>> {
+        let maps = &mut self.maps.write().await.maps;
+        if maps.len() == 1 {
+            Some(std::mem::take(&mut maps[0]))
+        } else {
+            maps.pop()
+        }
+    }
+}
+
+impl<K, V, S> Deref for ReadGuard<'_, K, V, S> {
+    type Target = ReadView<K, V, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.view
+    }
+}
+
+impl<K, V, S> Deref for AsyncReadGuard<K, V, S> {
+    type Target = ReadView<K, V, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.view
+    }
+}
+
+impl<K, V> Default for AsyncChainMap<K, V>
+where
+    K: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new(HashMap::new())
+    }
+}
+
+impl<K, V, S> Clone for AsyncChainMap<K, V, S> {
+    fn clone(&self) -> Self {
+        Self {
+            maps: Arc::clone(&self.maps),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use futures_lite::future::block_on;
+
+    #[test]
+    fn async_scopes() {
+        block_on(async {
+            let chain_map = AsyncChainMap::default();
+            chain_map.insert("x", 0).await;
+            chain_map.new_child().await;
+            chain_map.insert("x", 1).await;
+            assert_eq!(chain_map.get("x").await, Some(1));
+            chain_map.remove_child().await;
+            assert_eq!(chain_map.get("x").await, Some(0));
+            assert_eq!(chain_map.to_chain_map().await.get("x"), Some(&0));
+        });
+    }
+
+    #[test]
+    fn owned_guard_across_threads() {
+        let chain_map = AsyncChainMap::default();
+        block_on(chain_map.insert("x".to_string(), 1));
+        let guard = block_on(chain_map.read_owned());
+        let handle = std::thread::spawn(move || *guard.get("x").unwrap());
+        assert_eq!(handle.join().unwrap(), 1);
+        assert_eq!(block_on(chain_map.child_len()), 1);
+    }
+
+    #[test]
+    fn consistent_reads() {
+        block_on(async {
+            let chain_map = AsyncChainMap::default();
+            chain_map.insert("host", "a").await;
+            chain_map.insert("port", "1").await;
+            let guard = chain_map.read().await;
+            let writer = chain_map.clone();
+            let mut write = Box::pin(async move {
+                writer.new_child().await;
+                writer.insert("host", "b").await;
+            });
+            // the writer can't make progress while the guard is held
+            assert!(futures_lite::future::poll_once(&mut write).await.is_none());
+            assert_eq!(
+                (guard.get("host"), guard.get("port")),
+                (Some(&"a"), Some(&"1"))
+            );
+            assert_eq!(guard.get_last_index("host"), Some(0));
+            drop(guard);
+            write.await;
+            assert_eq!(chain_map.get("host").await, Some("b"));
+            assert_eq!(chain_map.read().await.get_before(1, "host"), Some(&"a"));
+        });
+    }
+}
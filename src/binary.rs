@@ -0,0 +1,181 @@
+use crate::{ChainMap, Error, MergeOnLoad};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+    hash::{BuildHasher, Hash},
+    io::{self, Read, Write},
+    mem::take,
+};
+
+/// Written at the start of every encoded chain.
+const MAGIC: &[u8; 4] = b"HCHN";
+/// The version of the encoding that follows the header. Bumped whenever the
+/// layout of the payload changes.
+const VERSION: u16 = 1;
+/// The oldest version that can still be read.
+const MIN_VERSION: u16 = 1;
+const HEADER_LEN: usize = MAGIC.len() + 2;
+
+fn io_error(e: io::Error) -> Error {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof => Error::InvalidHeader,
+        _ => Error::Serialization(e.to_string()),
+    }
+}
+
+impl<K, V, S> ChainMap<K, V, S>
+where
+    K: Serialize + Hash + Eq,
+    V: Serialize,
+    S: BuildHasher,
+{
+    /// Encodes the chain in a compact binary format, prefixed with a
+    /// header recording the format version.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, Error> {
+        let mut ret = Vec::with_capacity(HEADER_LEN);
+        self.save_to(&mut ret)?;
+        Ok(ret)
+    }
+
+    /// Writes the chain to `writer` in the format of
+    /// [`to_bytes`](Self::to_bytes), e.g. to save a REPL session.
+    pub fn save_to<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        writer.write_all(MAGIC).map_err(io_error)?;
+        writer.write_all(&VERSION.to_le_bytes()).map_err(io_error)?;
+        bincode::serialize_into(writer, self).map_err(|e| Error::Serialization(e.to_string()))
+    }
+}
+
+impl<K, V, S> ChainMap<K, V, S>
+where
+    K: DeserializeOwned + Hash + Eq,
+    V: DeserializeOwned,
+    S: BuildHasher + Default,
+{
+    /// Decodes a chain written by [`to_bytes`](Self::to_bytes), failing
+    /// if the header is missing or records a version this one can't read.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        Self::load_from(bytes)
+    }
+
+    /// Reads a chain written by [`save_to`](Self::save_to), failing if the
+    /// header is missing or records a version this one can't read.
+    pub fn load_from<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut header = [0; HEADER_LEN];
+        reader.read_exact(&mut header).map_err(io_error)?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(Error::InvalidHeader);
+        }
+        let version = u16::from_le_bytes([header[MAGIC.len()], header[MAGIC.len() + 1]]);
+        if !(MIN_VERSION..=VERSION).contains(&version) {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        bincode::deserialize_from(reader).map_err(|e| Error::Serialization(e.to_string()))
+    }
+
+    /// Reads a chain written by [`save_to`](Self::save_to) into this one,
+    /// combining the layers as `merge_on_load` says. Policies, validators
+    /// and the like set on this chain are kept.
+    ///
+    /// Nothing is changed if reading fails.
+    pub fn load_into<R: Read>(&mut self, reader: R, merge_on_load: MergeOnLoad) -> Result<(), Error> {
+        let mut saved = Self::load_from(reader)?;
+        if merge_on_load == MergeOnLoad::Replace {
+            self.lazy.clear();
+            self.forget_providers_from(0);
+            self.maps = take(&mut saved.maps);
+            return Ok(());
+        }
+        for (idx, map) in take(&mut saved.maps).into_iter().enumerate() {
+            if idx == self.maps.len() {
+                self.new_child_with(map);
+                continue;
+            }
+            let layer = self.layer_mut(idx);
+            for (key, value) in map {
+                match merge_on_load {
+                    MergeOnLoad::KeepCurrent => {
+                        layer.entry(key).or_insert(value);
+                    }
+                    _ => {
+                        layer.insert(key, value);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bytes_round_trip() {
+        let mut chain_map: ChainMap<String, Vec<u8>> = ChainMap::default();
+        chain_map.insert("x".to_string(), vec![0]);
+        chain_map.new_child();
+        chain_map.insert("x".to_string(), vec![1, 2]);
+        let bytes = chain_map.to_bytes().unwrap();
+        assert_eq!(&bytes[..4], MAGIC);
+        let round_trip: ChainMap<String, Vec<u8>> = ChainMap::from_bytes(&bytes).unwrap();
+        assert_eq!(round_trip, chain_map);
+    }
+
+    #[test]
+    fn borrowed_to_bytes() {
+        let mut borrowed: ChainMap<&str, &str> = ChainMap::default();
+        borrowed.insert("x", "y");
+        let bytes = borrowed.to_bytes().unwrap();
+        let owned: ChainMap<String, String> = ChainMap::from_bytes(&bytes).unwrap();
+        assert_eq!(owned.get("x").map(String::as_str), Some("y"));
+    }
+
+    #[test]
+    fn bytes_header_checked() {
+        type Map = ChainMap<String, u8>;
+        assert!(matches!(
+            Map::from_bytes(b"nope"),
+            Err(Error::InvalidHeader)
+        ));
+        let mut bytes = Map::default().to_bytes().unwrap();
+        bytes[4] = 2;
+        assert!(matches!(
+            Map::from_bytes(&bytes),
+            Err(Error::UnsupportedVersion(2))
+        ));
+        bytes[4] = 0;
+        assert!(matches!(
+            Map::from_bytes(&bytes),
+            Err(Error::UnsupportedVersion(0))
+        ));
+    }
+
+    #[test]
+    fn save_and_load_session() {
+        let mut session: ChainMap<String, u8> = ChainMap::default();
+        session.insert("x".to_string(), 0);
+        session.new_child();
+        session.insert("y".to_string(), 1);
+        let mut file = Vec::new();
+        session.save_to(&mut file).unwrap();
+        assert_eq!(ChainMap::load_from(file.as_slice()).unwrap(), session);
+
+        let mut current: ChainMap<String, u8> = ChainMap::default();
+        current.insert("x".to_string(), 9);
+        current.insert("z".to_string(), 9);
+        let mut kept = current.clone();
+        kept.load_into(file.as_slice(), MergeOnLoad::KeepCurrent).unwrap();
+        assert_eq!(kept.get("x"), Some(&9));
+        assert_eq!(kept.get("y"), Some(&1));
+        assert_eq!(kept.child_len(), 2);
+        let mut preferred = current.clone();
+        preferred.load_into(file.as_slice(), MergeOnLoad::PreferSaved).unwrap();
+        assert_eq!(preferred.get_at(0, "x"), Some(&0));
+        assert_eq!(preferred.get("z"), Some(&9));
+        current.load_into(file.as_slice(), MergeOnLoad::Replace).unwrap();
+        assert_eq!(current, session);
+        assert!(current.load_into(&file[..3], MergeOnLoad::Replace).is_err());
+        assert_eq!(current, session);
+    }
+}
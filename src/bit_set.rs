@@ -0,0 +1,185 @@
+use std::mem::take;
+
+const BITS: usize = u64::BITS as usize;
+
+/// A chain of bitsets over small integers, for dense elements like
+/// register or variable numbers where hashing would be wasted work.
+///
+/// Each layer uses one bit per element up to the largest element it holds,
+/// and a value is in the chain if it is in any layer.
+pub struct ChainBitSet {
+    sets: Vec<Vec<u64>>,
+}
+
+impl ChainBitSet {
+    pub fn new() -> Self {
+        Self {
+            sets: vec![Vec::new()],
+        }
+    }
+
+    /// Adds a value to the current scope, returning `false` if the current
+    /// scope already contained it.
+    pub fn insert(&mut self, value: usize) -> bool {
+        let Some(set) = self.sets.last_mut() else {
+            return false;
+        };
+        let (word, bit) = (value / BITS, 1 << (value % BITS));
+        if set.len() <= word {
+            set.resize(word + 1, 0);
+        }
+        let added = set[word] & bit == 0;
+        set[word] |= bit;
+        added
+    }
+
+    /// Removes a value from the current scope, returning `true` if the
+    /// current scope contained it. Outer scopes are not affected.
+    pub fn remove(&mut self, value: usize) -> bool {
+        let Some(word) = self
+            .sets
+            .last_mut()
+            .and_then(|set| set.get_mut(value / BITS))
+        else {
+            return false;
+        };
+        let bit = 1 << (value % BITS);
+        let removed = *word & bit != 0;
+        *word &= !bit;
+        removed
+    }
+
+    pub fn contains(&self, value: usize) -> bool {
+        let (word, bit) = (value / BITS, 1 << (value % BITS));
+        self.sets
+            .iter()
+            .any(|set| set.get(word).is_some_and(|w| w & bit != 0))
+    }
+
+    /// Returns `true` if the current scope contains the value.
+    pub fn last_has(&self, value: usize) -> bool {
+        let (word, bit) = (value / BITS, 1 << (value % BITS));
+        self.sets
+            .last()
+            .and_then(|set| set.get(word))
+            .is_some_and(|w| w & bit != 0)
+    }
+
+    /// The union of every layer, one bit per element.
+    fn union(&self) -> Vec<u64> {
+        let len = self.sets.iter().map(Vec::len).max().unwrap_or(0);
+        let mut ret = vec![0; len];
+        for set in &self.sets {
+            for (acc, word) in ret.iter_mut().zip(set) {
+                *acc |= word;
+            }
+        }
+        ret
+    }
+
+    /// Iterates over every element in the chain, in ascending order,
+    /// yielding elements in more than one scope once.
+    pub fn iter(&self) -> impl Iterator<Item = usize> {
+        self.union().into_iter().enumerate().flat_map(|(i, word)| {
+            (0..BITS)
+                .filter(move |bit| word & (1 << bit) != 0)
+                .map(move |bit| i * BITS + bit)
+        })
+    }
+
+    /// The number of distinct elements in the chain.
+    pub fn len(&self) -> usize {
+        self.union().iter().map(|w| w.count_ones() as usize).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sets.iter().flatten().all(|w| *w == 0)
+    }
+
+    pub fn new_child(&mut self) {
+        self.sets.push(Vec::new());
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.sets.len()
+    }
+
+    /// Removes the current scope, returning its elements in ascending
+    /// order. As with [`ChainSet::remove_child`](crate::ChainSet::remove_child),
+    /// the last scope is cleared rather than removed.
+    pub fn remove_child(&mut self) -> Option<Vec<usize>> {
+        let set = if self.sets.len() == 1 {
+            take(&mut self.sets[0])
+        } else {
+            self.sets.pop()?
+        };
+        let mut ret = Vec::new();
+        for (i, word) in set.into_iter().enumerate() {
+            ret.extend(
+                (0..BITS)
+                    .filter(|bit| word & (1 << bit) != 0)
+                    .map(|bit| i * BITS + bit),
+            );
+        }
+        Some(ret)
+    }
+}
+
+impl Default for ChainBitSet {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Extend<usize> for ChainBitSet {
+    /// Adds every value to the current scope.
+    fn extend<I: IntoIterator<Item = usize>>(&mut self, iter: I) {
+        for value in iter {
+            self.insert(value);
+        }
+    }
+}
+
+impl core::fmt::Debug for ChainBitSet {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ChainBitSet")
+            .field("sets", &self.sets)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bit_set_scopes() {
+        let mut chain_set = ChainBitSet::new();
+        assert!(chain_set.insert(3));
+        assert!(!chain_set.insert(3));
+        chain_set.new_child();
+        chain_set.extend([3, 70, 200]);
+        assert!(chain_set.contains(3));
+        assert!(chain_set.last_has(200));
+        assert!(!chain_set.contains(4));
+        assert_eq!(chain_set.iter().collect::<Vec<_>>(), vec![3, 70, 200]);
+        assert_eq!(chain_set.len(), 3);
+        assert_eq!(chain_set.remove_child(), Some(vec![3, 70, 200]));
+        assert!(chain_set.contains(3));
+        assert!(!chain_set.contains(70));
+    }
+
+    #[test]
+    fn bit_set_remove() {
+        let mut chain_set = ChainBitSet::default();
+        chain_set.insert(1);
+        chain_set.new_child();
+        chain_set.insert(1);
+        assert!(chain_set.remove(1));
+        assert!(!chain_set.remove(1));
+        assert!(chain_set.contains(1));
+        chain_set.remove_child();
+        chain_set.remove_child();
+        assert!(chain_set.is_empty());
+    }
+}
@@ -0,0 +1,27 @@
+use std::ops::Range;
+
+/// Restricts a lookup to a contiguous run of layers in a chain.
+///
+/// Layers are indexed from the bottom, `0` being the outermost scope. Lookups
+/// still search the selected layers from the innermost outward.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LookupBounds {
+    /// Every layer in the chain
+    All,
+    /// Layers strictly below `idx`, i.e. `0..idx`
+    Before(usize),
+    /// Layers at or above `idx`, i.e. `idx..`
+    After(usize),
+}
+
+impl LookupBounds {
+    /// The range of layer indices selected for a chain of `len` layers.
+    /// Indices past the end of the chain are clamped to `len`.
+    pub fn range(self, len: usize) -> Range<usize> {
+        match self {
+            LookupBounds::All => 0..len,
+            LookupBounds::Before(idx) => 0..idx.min(len),
+            LookupBounds::After(idx) => idx.min(len)..len,
+        }
+    }
+}
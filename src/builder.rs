@@ -0,0 +1,148 @@
+use crate::ChainMap;
+use std::collections::{hash_map::RandomState, HashMap};
+use std::hash::{BuildHasher, Hash};
+
+/// Builds a [`ChainMap`] one layer at a time in a single expression.
+///
+/// Entries go into the most recently added layer; the first entry added
+/// before any layer creates one.
+///
+/// ```
+/// # use hash_chain::ChainMapBuilder;
+/// let chain_map = ChainMapBuilder::new()
+///     .with_entry("x", 0)
+///     .with_child()
+///     .with_entry("x", 1)
+///     .build();
+/// assert_eq!(chain_map.get_before(1, "x"), Some(&0));
+/// assert_eq!(chain_map.get("x"), Some(&1));
+/// ```
+pub struct ChainMapBuilder<K, V, S = RandomState> {
+    maps: Vec<HashMap<K, V, S>>,
+    hasher: S,
+    capacity: usize,
+}
+
+impl<K, V> ChainMapBuilder<K, V>
+where
+    K: Hash + Eq,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<K, V, S> ChainMapBuilder<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Clone,
+{
+    /// Uses `hasher` for every layer, rehashing the layers added so far.
+    pub fn with_hasher<T>(self, hasher: T) -> ChainMapBuilder<K, V, T>
+    where
+        T: BuildHasher + Clone,
+    {
+        let maps = self
+            .maps
+            .into_iter()
+            .map(|map| {
+                let mut layer = HashMap::with_capacity_and_hasher(map.capacity(), hasher.clone());
+                layer.extend(map);
+                layer
+            })
+            .collect();
+        ChainMapBuilder {
+            maps,
+            hasher,
+            capacity: self.capacity,
+        }
+    }
+
+    /// Reserves room for `n` entries in the current layer and in every
+    /// layer added by [`with_child`](Self::with_child) afterwards.
+    pub fn with_capacity(mut self, n: usize) -> Self {
+        self.capacity = n;
+        if let Some(map) = self.maps.last_mut() {
+            map.reserve(n.saturating_sub(map.len()));
+        }
+        self
+    }
+
+    /// Adds an empty layer.
+    pub fn with_child(mut self) -> Self {
+        self.maps.push(HashMap::with_capacity_and_hasher(
+            self.capacity,
+            self.hasher.clone(),
+        ));
+        self
+    }
+
+    /// Adds `map` as a layer.
+    pub fn with_layer(mut self, map: HashMap<K, V, S>) -> Self {
+        self.maps.push(map);
+        self
+    }
+
+    /// Inserts a key-value pair into the most recently added layer.
+    pub fn with_entry(mut self, key: K, value: V) -> Self {
+        if self.maps.is_empty() {
+            self = self.with_child();
+        }
+        if let Some(map) = self.maps.last_mut() {
+            map.insert(key, value);
+        }
+        self
+    }
+
+    pub fn build(self) -> ChainMap<K, V, S> {
+        let mut builder = self;
+        if builder.maps.is_empty() {
+            builder = builder.with_child();
+        }
+        ChainMap::from_maps(builder.maps)
+    }
+}
+
+impl<K, V, S> Default for ChainMapBuilder<K, V, S>
+where
+    S: Default,
+{
+    fn default() -> Self {
+        Self {
+            maps: Vec::new(),
+            hasher: S::default(),
+            capacity: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use hashers::oz::DJB2Hasher;
+    use std::hash::BuildHasherDefault;
+
+    #[test]
+    fn builds_layers() {
+        let mut base = HashMap::new();
+        base.insert("y", 2);
+        let chain_map = ChainMapBuilder::new()
+            .with_layer(base)
+            .with_entry("x", 0)
+            .with_capacity(8)
+            .with_child()
+            .with_entry("x", 1)
+            .with_hasher(BuildHasherDefault::<DJB2Hasher>::default())
+            .build();
+        assert_eq!(chain_map.child_len(), 2);
+        assert_eq!(chain_map.get("x"), Some(&1));
+        assert_eq!(chain_map.get("y"), Some(&2));
+        assert!(chain_map.maps[1].capacity() >= 8);
+    }
+
+    #[test]
+    fn empty_build() {
+        let chain_map: ChainMap<u8, u8> = ChainMapBuilder::new().build();
+        assert_eq!(chain_map.child_len(), 1);
+    }
+}
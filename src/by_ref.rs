@@ -0,0 +1,200 @@
+use crate::ChainMap;
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+/// Access to the layers of a chain that a [`ChainMapRef`] is layered over.
+pub(crate) trait Scopes<K, V, S> {
+    fn scope_count(&self) -> usize;
+    fn scope(&self, idx: usize) -> &HashMap<K, V, S>;
+    fn scope_mut(&mut self, idx: usize) -> &mut HashMap<K, V, S>;
+}
+
+impl<K, V, S> Scopes<K, V, S> for ChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    fn scope_count(&self) -> usize {
+        self.maps.len()
+    }
+    fn scope(&self, idx: usize) -> &HashMap<K, V, S> {
+        self.layer(idx)
+    }
+    fn scope_mut(&mut self, idx: usize) -> &mut HashMap<K, V, S> {
+        self.layer_mut(idx)
+    }
+}
+
+/// Child scopes layered over a borrowed chain, created with
+/// [`ChainMap::by_ref_child`].
+///
+/// Lookups fall through to the parent, and bindings in the parent can be
+/// modified through `get_mut` and `insert_at`, but the child's own layers
+/// are dropped along with the `ChainMapRef`, leaving the parent as it was.
+/// A `ChainMapRef` can itself be borrowed to create further children.
+pub struct ChainMapRef<'a, K, V, S = RandomState> {
+    parent: &'a mut (dyn Scopes<K, V, S> + 'a),
+    maps: Vec<HashMap<K, V, S>>,
+}
+
+impl<K, V, S> Scopes<K, V, S> for ChainMapRef<'_, K, V, S> {
+    fn scope_count(&self) -> usize {
+        self.parent.scope_count() + self.maps.len()
+    }
+    fn scope(&self, idx: usize) -> &HashMap<K, V, S> {
+        let parent_len = self.parent.scope_count();
+        if idx < parent_len {
+            self.parent.scope(idx)
+        } else {
+            &self.maps[idx - parent_len]
+        }
+    }
+    fn scope_mut(&mut self, idx: usize) -> &mut HashMap<K, V, S> {
+        let parent_len = self.parent.scope_count();
+        if idx < parent_len {
+            self.parent.scope_mut(idx)
+        } else {
+            &mut self.maps[idx - parent_len]
+        }
+    }
+}
+
+impl<K, V, S> ChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    /// Borrows the chain to layer a new, empty scope over it without
+    /// taking ownership of or cloning the chain.
+    pub fn by_ref_child(&mut self) -> ChainMapRef<'_, K, V, S> {
+        ChainMapRef {
+            parent: self,
+            maps: vec![HashMap::default()],
+        }
+    }
+}
+
+impl<'a, K, V, S> ChainMapRef<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// The total number of layers, including the parent's.
+    pub fn child_len(&self) -> usize {
+        self.scope_count()
+    }
+
+    /// Inserts a key-value pair into the innermost layer.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.maps.last_mut()?.insert(key, value)
+    }
+
+    /// Inserts a key-value pair into the layer at `idx`, counting from the
+    /// outermost layer of the root chain.
+    pub fn insert_at(&mut self, idx: usize, key: K, value: V) -> Result<Option<V>, crate::Error> {
+        if idx < self.scope_count() {
+            Ok(self.scope_mut(idx).insert(key, value))
+        } else {
+            Err(crate::Error::IndexOutOfRange)
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        (0..self.scope_count())
+            .rev()
+            .find_map(|i| self.scope(i).get(key))
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = (0..self.scope_count())
+            .rev()
+            .find(|i| self.scope(*i).contains_key(key))?;
+        self.scope_mut(idx).get_mut(key)
+    }
+
+    pub fn new_child_with(&mut self, map: HashMap<K, V, S>) {
+        self.maps.push(map);
+    }
+
+    /// Removes the innermost layer owned by this `ChainMapRef`. Like
+    /// [`ChainMap::remove_child`], the last owned layer is cleared instead
+    /// of being removed, and the parent's layers are never touched.
+    pub fn remove_child(&mut self) -> Option<HashMap<K, V, S>>
+    where
+        S: Default,
+    {
+        if self.maps.len() == 1 {
+            Some(std::mem::take(&mut self.maps[0]))
+        } else {
+            self.maps.pop()
+        }
+    }
+
+    /// Borrows this chain to layer a further scope over it.
+    pub fn by_ref_child(&mut self) -> ChainMapRef<'_, K, V, S>
+    where
+        S: Default,
+    {
+        ChainMapRef {
+            parent: self,
+            maps: vec![HashMap::default()],
+        }
+    }
+
+    pub fn new_child(&mut self)
+    where
+        S: Default,
+    {
+        self.maps.push(HashMap::default());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn borrowed_child() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        {
+            let mut child = chain_map.by_ref_child();
+            child.insert("y", 1);
+            assert_eq!(child.get("x"), Some(&0));
+            assert_eq!(child.get("y"), Some(&1));
+            *child.get_mut("x").unwrap() += 1;
+            assert_eq!(child.child_len(), 2);
+        }
+        assert_eq!(chain_map.get("x"), Some(&1));
+        assert_eq!(chain_map.get("y"), None);
+        assert_eq!(chain_map.child_len(), 1);
+    }
+
+    #[test]
+    fn nested_borrowed_child() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        let mut child = chain_map.by_ref_child();
+        child.insert("x", 1);
+        {
+            let mut grandchild = child.by_ref_child();
+            grandchild.insert("z", 2);
+            assert_eq!(grandchild.get("x"), Some(&1));
+            grandchild.insert_at(0, "w", 3).unwrap();
+            assert_eq!(grandchild.child_len(), 3);
+        }
+        assert_eq!(child.get("z"), None);
+        assert_eq!(child.get("w"), Some(&3));
+    }
+}
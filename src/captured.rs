@@ -0,0 +1,59 @@
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    sync::Arc,
+};
+
+/// A read-only snapshot of a chain's scopes, shared by atomic reference
+/// counting, so it can be sent to other threads.
+///
+/// Neither taking nor cloning a `CapturedEnv` copies any scope, which
+/// makes it suitable for storing in closures. Use
+/// [`ChainMap::from_captured`](crate::ChainMap::from_captured) to run code
+/// against the captured scopes.
+pub struct CapturedEnv<K, V, S = RandomState> {
+    pub(crate) maps: Arc<[Arc<HashMap<K, V, S>>]>,
+}
+
+impl<K, V, S> CapturedEnv<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Returns the value corresponding to the key, searching the captured
+    /// scopes from the innermost outward.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.iter().rev().find_map(|map| map.get(key))
+    }
+
+    /// The number of captured scopes.
+    pub fn child_len(&self) -> usize {
+        self.maps.len()
+    }
+}
+
+impl<K, V, S> Clone for CapturedEnv<K, V, S> {
+    fn clone(&self) -> Self {
+        Self {
+            maps: Arc::clone(&self.maps),
+        }
+    }
+}
+
+impl<K, V, S> core::fmt::Debug for CapturedEnv<K, V, S>
+where
+    K: Eq + Hash + core::fmt::Debug,
+    V: core::fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("CapturedEnv")
+            .field("maps", &self.maps)
+            .finish()
+    }
+}
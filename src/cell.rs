@@ -0,0 +1,203 @@
+use crate::ChainMap;
+use std::{
+    borrow::Borrow,
+    cell::RefCell,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+/// A chain that can be modified through a shared reference, for
+/// single-threaded interpreters whose environment is shared through `Rc`.
+///
+/// Every method borrows the chain only for its own duration and never
+/// calls back into user code while doing so, so evaluation may freely
+/// re-enter the chain. In exchange, lookups return copies of values
+/// rather than references; values are usually `Rc`s in this setting.
+pub struct CellChainMap<K, V, S = RandomState> {
+    maps: RefCell<Vec<HashMap<K, V, S>>>,
+}
+
+impl<K, V, S> CellChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn new(map: HashMap<K, V, S>) -> Self {
+        Self {
+            maps: RefCell::new(vec![map]),
+        }
+    }
+
+    /// Inserts a key-value pair into the current scope.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.maps.borrow_mut().last_mut()?.insert(key, value)
+    }
+
+    pub fn insert_at(&self, idx: usize, key: K, value: V) -> Result<Option<V>, crate::Error> {
+        match self.maps.borrow_mut().get_mut(idx) {
+            Some(map) => Ok(map.insert(key, value)),
+            None => Err(crate::Error::IndexOutOfRange),
+        }
+    }
+
+    /// Replaces the value of the nearest binding of the key, failing with
+    /// `Error::KeyNotFound` if it is unbound.
+    pub fn assign<Q>(&self, key: &Q, value: V) -> Result<V, crate::Error>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut maps = self.maps.borrow_mut();
+        let slot = maps
+            .iter_mut()
+            .rev()
+            .find_map(|map| map.get_mut(key))
+            .ok_or(crate::Error::KeyNotFound)?;
+        Ok(std::mem::replace(slot, value))
+    }
+
+    /// Returns a copy of the value corresponding to the key.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.maps
+            .borrow()
+            .iter()
+            .rev()
+            .find_map(|map| map.get(key))
+            .cloned()
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.borrow().iter().any(|map| map.contains_key(key))
+    }
+
+    pub fn last_has<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps
+            .borrow()
+            .last()
+            .is_some_and(|map| map.contains_key(key))
+    }
+
+    pub fn get_last_index<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps
+            .borrow()
+            .iter()
+            .rposition(|map| map.contains_key(key))
+    }
+
+    pub fn new_child_with(&self, map: HashMap<K, V, S>) {
+        self.maps.borrow_mut().push(map);
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.maps.borrow().len()
+    }
+
+    pub fn into_chain_map(self) -> ChainMap<K, V, S> {
+        ChainMap::from_maps(self.maps.into_inner())
+    }
+}
+
+impl<K, V, S> CellChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    pub fn new_child(&self) {
+        self.new_child_with(HashMap::default());
+    }
+
+    /// Removes the current scope. As with [`ChainMap::remove_child`], the
+    /// last scope is cleared rather than removed.
+    pub fn remove_child(&self) -> Option<HashMap<K, V, S>> {
+        let mut maps = self.maps.borrow_mut();
+        if maps.len() == 1 {
+            Some(std::mem::take(&mut maps[0]))
+        } else {
+            maps.pop()
+        }
+    }
+}
+
+impl<K, V> Default for CellChainMap<K, V>
+where
+    K: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new(HashMap::new())
+    }
+}
+
+impl<K, V, S> From<ChainMap<K, V, S>> for CellChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    fn from(mut chain: ChainMap<K, V, S>) -> Self {
+        chain.force_lazy();
+        Self {
+            maps: RefCell::new(std::mem::take(&mut chain.maps)),
+        }
+    }
+}
+
+impl<K, V, S> core::fmt::Debug for CellChainMap<K, V, S>
+where
+    K: Eq + Hash + core::fmt::Debug,
+    V: core::fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("CellChainMap")
+            .field("maps", &self.maps.borrow())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn shared_environment() {
+        let env = Rc::new(CellChainMap::default());
+        let closure_env = Rc::clone(&env);
+        let define = move |name, value| closure_env.insert(name, value);
+        define("x", 0);
+        env.new_child();
+        define("x", 1);
+        assert_eq!(env.get("x"), Some(1));
+        assert_eq!(env.assign("x", 2).unwrap(), 1);
+        assert!(env.assign("y", 0).is_err());
+        env.remove_child();
+        assert_eq!(env.get("x"), Some(0));
+        assert_eq!(env.get_last_index("x"), Some(0));
+    }
+
+    #[test]
+    fn from_chain_map() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        let cell = CellChainMap::from(chain_map);
+        cell.insert("y", 1);
+        let chain_map = cell.into_chain_map();
+        assert_eq!(chain_map.get("y"), Some(&1));
+    }
+}
@@ -0,0 +1,256 @@
+use crate::{ChainMap, LockedChainMap};
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    hash::{BuildHasher, Hash},
+};
+
+/// The scoping API shared by [`ChainMap`] and [`LockedChainMap`], so code
+/// handling environments can be generic over how the layers are stored.
+///
+/// Each method behaves like the inherent method of the same name.
+pub trait ChainMapLike<K, V> {
+    /// The map type of a single layer
+    type Layer;
+
+    fn insert(&mut self, key: K, value: V) -> Option<V>;
+
+    fn insert_at(&mut self, idx: usize, key: K, value: V) -> Result<Option<V>, crate::Error>;
+
+    fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized;
+
+    fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized;
+
+    fn get_at<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized;
+
+    fn get_before<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized;
+
+    fn get_after<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized;
+
+    fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized;
+
+    fn has_at<Q>(&self, idx: usize, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized;
+
+    fn last_has<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized;
+
+    fn get_last_index<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized;
+
+    fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized;
+
+    fn remove_at<Q>(&mut self, idx: usize, key: &Q) -> Result<Option<V>, crate::Error>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool;
+
+    fn child_len(&self) -> usize;
+
+    fn new_child(&mut self);
+
+    fn new_child_with(&mut self, layer: Self::Layer);
+
+    fn remove_child(&mut self) -> Option<Self::Layer>;
+}
+
+macro_rules! forward_chain_map_like {
+    ($ty:ident) => {
+        fn insert(&mut self, key: K, value: V) -> Option<V> {
+            $ty::insert(self, key, value)
+        }
+
+        fn insert_at(&mut self, idx: usize, key: K, value: V) -> Result<Option<V>, crate::Error> {
+            $ty::insert_at(self, idx, key, value)
+        }
+
+        fn get<Q>(&self, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            $ty::get(self, key)
+        }
+
+        fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            $ty::get_mut(self, key)
+        }
+
+        fn get_at<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            $ty::get_at(self, idx, key)
+        }
+
+        fn get_before<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            $ty::get_before(self, idx, key)
+        }
+
+        fn get_after<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            $ty::get_after(self, idx, key)
+        }
+
+        fn contains_key<Q>(&self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            $ty::contains_key(self, key)
+        }
+
+        fn has_at<Q>(&self, idx: usize, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            $ty::has_at(self, idx, key)
+        }
+
+        fn last_has<Q>(&self, key: &Q) -> bool
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            $ty::last_has(self, key)
+        }
+
+        fn get_last_index<Q>(&self, key: &Q) -> Option<usize>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            $ty::get_last_index(self, key)
+        }
+
+        fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            $ty::remove(self, key)
+        }
+
+        fn remove_at<Q>(&mut self, idx: usize, key: &Q) -> Result<Option<V>, crate::Error>
+        where
+            K: Borrow<Q>,
+            Q: Hash + Eq + ?Sized,
+        {
+            $ty::remove_at(self, idx, key)
+        }
+
+        fn len(&self) -> usize {
+            $ty::len(self)
+        }
+
+        fn is_empty(&self) -> bool {
+            $ty::is_empty(self)
+        }
+
+        fn child_len(&self) -> usize {
+            $ty::child_len(self)
+        }
+
+        fn new_child(&mut self) {
+            $ty::new_child(self)
+        }
+
+        fn new_child_with(&mut self, layer: Self::Layer) {
+            $ty::new_child_with(self, layer)
+        }
+
+        fn remove_child(&mut self) -> Option<Self::Layer> {
+            $ty::remove_child(self)
+        }
+    };
+}
+
+impl<K, V, S> ChainMapLike<K, V> for ChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    type Layer = HashMap<K, V, S>;
+
+    forward_chain_map_like!(ChainMap);
+}
+
+impl<K, V, S> ChainMapLike<K, V> for LockedChainMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Default + Clone,
+{
+    type Layer = crate::imutable::Layer<K, V, S>;
+
+    forward_chain_map_like!(LockedChainMap);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn resolve<C: ChainMapLike<&'static str, i32>>(env: &mut C) -> Option<i32> {
+        env.insert("x", 0);
+        env.new_child();
+        env.insert("x", 1);
+        let inner = env.get("x").copied();
+        assert_eq!(env.get_at(0, "x"), Some(&0));
+        assert_eq!(env.remove("x"), Some(1));
+        assert!(env.contains_key("x"));
+        assert_eq!(env.len(), 1);
+        env.remove_child();
+        assert_eq!(env.get_last_index("x"), Some(0));
+        inner.zip(env.get("x").copied()).map(|(a, b)| a + b)
+    }
+
+    #[test]
+    fn generic_over_backing() {
+        assert_eq!(resolve(&mut ChainMap::<_, _>::default()), Some(1));
+        assert_eq!(resolve(&mut LockedChainMap::<_, _>::default()), Some(1));
+    }
+}
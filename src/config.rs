@@ -0,0 +1,108 @@
+use crate::ChainMap;
+use std::collections::HashMap;
+
+/// A source of configuration values, each of which becomes one layer of a
+/// chain built with [`ChainMap::from_sources`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// Values that are already known, e.g. hard coded defaults or the
+    /// result of parsing a config file
+    Map(HashMap<String, String>),
+    /// Every environment variable starting with `prefix`. The prefix is
+    /// stripped and the rest of the name is lowercased, so with a prefix of
+    /// `APP_` the variable `APP_PORT` is bound to the key `port`.
+    Env { prefix: String },
+    /// Command line style overrides of the form `key=value` or
+    /// `--key=value`. Arguments without an `=` are ignored.
+    Args(Vec<String>),
+}
+
+impl Source {
+    pub fn env(prefix: impl Into<String>) -> Self {
+        Source::Env {
+            prefix: prefix.into(),
+        }
+    }
+
+    pub fn args<I, A>(args: I) -> Self
+    where
+        I: IntoIterator<Item = A>,
+        A: Into<String>,
+    {
+        Source::Args(args.into_iter().map(Into::into).collect())
+    }
+
+    /// Reads the source into a single layer.
+    pub fn load(self) -> HashMap<String, String> {
+        match self {
+            Source::Map(map) => map,
+            Source::Env { prefix } => std::env::vars()
+                .filter_map(|(k, v)| {
+                    let key = k.strip_prefix(&prefix)?;
+                    Some((key.to_lowercase(), v))
+                })
+                .collect(),
+            Source::Args(args) => args
+                .iter()
+                .filter_map(|arg| {
+                    let arg = arg.strip_prefix("--").unwrap_or(arg);
+                    let (k, v) = arg.split_once('=')?;
+                    Some((k.to_string(), v.to_string()))
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<HashMap<String, String>> for Source {
+    fn from(map: HashMap<String, String>) -> Self {
+        Source::Map(map)
+    }
+}
+
+impl ChainMap<String, String> {
+    /// Builds a chain with one layer per source, in order, so later sources
+    /// take precedence over earlier ones. A typical order is defaults, then
+    /// a config file, then the environment, then command line overrides.
+    pub fn from_sources<I>(sources: I) -> Self
+    where
+        I: IntoIterator<Item = Source>,
+    {
+        let mut maps: Vec<_> = sources.into_iter().map(Source::load).collect();
+        if maps.is_empty() {
+            maps.push(HashMap::new());
+        }
+        Self::from_maps(maps)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn from_sources() {
+        std::env::set_var("HASH_CHAIN_CONFIG_TEST_PORT", "8080");
+        let mut defaults = HashMap::new();
+        defaults.insert("host".to_string(), "localhost".to_string());
+        defaults.insert("port".to_string(), "80".to_string());
+        defaults.insert("mode".to_string(), "debug".to_string());
+        let chain_map = ChainMap::from_sources(vec![
+            Source::from(defaults),
+            Source::env("HASH_CHAIN_CONFIG_TEST_"),
+            Source::args(vec!["--mode=release", "positional"]),
+        ]);
+
+        assert_eq!(chain_map.child_len(), 3);
+        assert_eq!(chain_map.get("host").map(String::as_str), Some("localhost"));
+        assert_eq!(chain_map.get("port").map(String::as_str), Some("8080"));
+        assert_eq!(chain_map.get("mode").map(String::as_str), Some("release"));
+        assert_eq!(chain_map.get("positional"), None);
+    }
+
+    #[test]
+    fn from_no_sources() {
+        let chain_map = ChainMap::from_sources(vec![]);
+        assert_eq!(chain_map.child_len(), 1);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::ChainMap;
+use std::{borrow::Cow, hash::BuildHasher};
+
+/// Helpers for chains keyed by `Cow<'a, str>`, so parsers that already hold
+/// the source text can bind identifiers without allocating a `String` for
+/// each one. Lookups work with plain `&str` through [`ChainMap::get`].
+impl<'a, V, S> ChainMap<Cow<'a, str>, V, S>
+where
+    S: BuildHasher,
+{
+    /// Creates a chain with a single scope whose keys borrow from the
+    /// source.
+    pub fn from_borrowed<I>(iter: I) -> Self
+    where
+        I: IntoIterator<Item = (&'a str, V)>,
+        S: Default,
+    {
+        let mut map = std::collections::HashMap::default();
+        map.extend(iter.into_iter().map(|(k, v)| (Cow::Borrowed(k), v)));
+        Self::from_maps(vec![map])
+    }
+
+    /// Inserts a key borrowed for `'a` into the current scope without
+    /// allocating.
+    pub fn insert_borrowed(&mut self, key: &'a str, value: V) -> Option<V> {
+        self.insert(Cow::Borrowed(key), value)
+    }
+
+    /// Inserts into the current scope, reusing the stored key if the scope
+    /// already binds it and only allocating an owned key otherwise.
+    pub fn insert_str(&mut self, key: &str, value: V) -> Option<V> {
+        let idx = self.maps.len().checked_sub(1)?;
+        let layer = self.layer_mut(idx);
+        if let Some(slot) = layer.get_mut(key) {
+            return Some(std::mem::replace(slot, value));
+        }
+        layer.insert(Cow::Owned(key.to_owned()), value)
+    }
+
+    /// Inserts into the current scope, borrowing the key from `source` when
+    /// it is a slice of it and allocating only when it is not.
+    pub fn insert_from(&mut self, source: &'a str, key: &str, value: V) -> Option<V> {
+        match borrow_from(source, key) {
+            Some(key) => self.insert_borrowed(key, value),
+            None => self.insert_str(key, value),
+        }
+    }
+}
+
+/// Re-borrows `key` from `source` if it points into it.
+fn borrow_from<'a>(source: &'a str, key: &str) -> Option<&'a str> {
+    let start = (key.as_ptr() as usize).checked_sub(source.as_ptr() as usize)?;
+    source.get(start..start + key.len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn borrowed_keys() {
+        let source = String::from("let x = y");
+        let mut chain_map: ChainMap<Cow<str>, i32> = ChainMap::from_borrowed([(&source[8..], 0)]);
+        chain_map.new_child();
+        chain_map.insert_from(&source, &source[4..5], 1);
+        let synthetic = String::from("tmp");
+        chain_map.insert_from(&source, &synthetic, 2);
+        chain_map.insert_str("x", 3);
+        assert!(matches!(
+            chain_map.maps[1].get_key_value("x"),
+            Some((Cow::Borrowed(_), 3))
+        ));
+        assert!(matches!(
+            chain_map.maps[1].get_key_value("tmp"),
+            Some((Cow::Owned(_), 2))
+        ));
+        assert_eq!(chain_map.get("y"), Some(&0));
+        assert!(matches!(
+            chain_map.maps[0].get_key_value("y"),
+            Some((Cow::Borrowed(_), 0))
+        ));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::ChainMap;
+use std::{
+    fmt::{self, Debug, Write},
+    hash::{BuildHasher, Hash},
+};
+
+/// How a single binding differs between two chains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingChange<'a, K, V> {
+    /// Bound only in the right chain
+    Added(&'a K, &'a V),
+    /// Bound only in the left chain
+    Removed(&'a K, &'a V),
+    /// Bound in both chains to different values, left then right
+    Changed(&'a K, &'a V, &'a V),
+}
+
+/// The bindings that differ in one layer of two chains.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LayerDiff<'a, K, V> {
+    pub layer: usize,
+    pub changes: Vec<BindingChange<'a, K, V>>,
+}
+
+/// The differences between two chains, layer by layer, see
+/// [`ChainMap::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChainDiff<'a, K, V> {
+    pub left_layers: usize,
+    pub right_layers: usize,
+    /// The layers with differing bindings, outermost first
+    pub layers: Vec<LayerDiff<'a, K, V>>,
+}
+
+impl<K, V, S> ChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    /// Compares the chain with `other` layer by layer, a layer missing
+    /// from one of them counting as empty.
+    pub fn diff<'a>(&'a self, other: &'a Self) -> ChainDiff<'a, K, V> {
+        let left: Vec<_> = self.layers().collect();
+        let right: Vec<_> = other.layers().collect();
+        let mut layers = Vec::new();
+        for layer in 0..left.len().max(right.len()) {
+            let mut changes = Vec::new();
+            if let Some(left) = left.get(layer) {
+                for (k, v) in left.iter() {
+                    match right.get(layer).and_then(|r| r.get(k)) {
+                        Some(other) if other == v => {}
+                        Some(other) => changes.push(BindingChange::Changed(k, v, other)),
+                        None => changes.push(BindingChange::Removed(k, v)),
+                    }
+                }
+            }
+            if let Some(right) = right.get(layer) {
+                for (k, v) in right.iter() {
+                    if !left.get(layer).is_some_and(|l| l.contains_key(k)) {
+                        changes.push(BindingChange::Added(k, v));
+                    }
+                }
+            }
+            if !changes.is_empty() {
+                layers.push(LayerDiff { layer, changes });
+            }
+        }
+        ChainDiff {
+            left_layers: left.len(),
+            right_layers: right.len(),
+            layers,
+        }
+    }
+}
+
+impl<K, V> ChainDiff<'_, K, V> {
+    /// Returns `true` if the chains have the same layers.
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty() && self.left_layers == self.right_layers
+    }
+}
+
+impl<K: Debug, V: Debug> ChainDiff<'_, K, V> {
+    /// Renders the differences as a unified diff style report, with the
+    /// bindings of each layer sorted by their `Debug` output so the report
+    /// is stable.
+    pub fn render(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl<K: Debug, V: Debug> fmt::Display for ChainDiff<'_, K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "--- left ({} layers)", self.left_layers)?;
+        writeln!(f, "+++ right ({} layers)", self.right_layers)?;
+        for layer in &self.layers {
+            let only_in = if layer.layer >= self.right_layers {
+                ", only in left"
+            } else if layer.layer >= self.left_layers {
+                ", only in right"
+            } else {
+                ""
+            };
+            writeln!(f, "@@ layer {}{} @@", layer.layer, only_in)?;
+            let mut lines: Vec<(String, String)> = layer
+                .changes
+                .iter()
+                .map(|change| {
+                    let mut lines = String::new();
+                    let key = match change {
+                        BindingChange::Added(k, v) => {
+                            let _ = writeln!(lines, "+ {:?}: {:?}", k, v);
+                            k
+                        }
+                        BindingChange::Removed(k, v) => {
+                            let _ = writeln!(lines, "- {:?}: {:?}", k, v);
+                            k
+                        }
+                        BindingChange::Changed(k, old, new) => {
+                            let _ = writeln!(lines, "- {:?}: {:?}", k, old);
+                            let _ = writeln!(lines, "+ {:?}: {:?}", k, new);
+                            k
+                        }
+                    };
+                    (format!("{:?}", key), lines)
+                })
+                .collect();
+            lines.sort();
+            for (_, lines) in lines {
+                f.write_str(&lines)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Asserts that two [`ChainMap`]s are equal, printing a
+/// [`ChainDiff`] of them on failure.
+///
+/// ```should_panic
+/// # use hash_chain::{assert_chain_eq, ChainMap};
+/// let mut left: ChainMap<_, _> = ChainMap::default();
+/// left.insert("x", 1);
+/// let mut right = left.clone();
+/// right.insert("x", 2);
+/// assert_chain_eq!(left, right);
+/// ```
+#[macro_export]
+macro_rules! assert_chain_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                let diff = left.diff(right);
+                if !diff.is_empty() {
+                    panic!("assertion `left == right` failed\n{}", diff.render());
+                }
+            }
+        }
+    };
+    ($left:expr, $right:expr, $($arg:tt)+) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                let diff = left.diff(right);
+                if !diff.is_empty() {
+                    panic!(
+                        "assertion `left == right` failed: {}\n{}",
+                        format_args!($($arg)+),
+                        diff.render()
+                    );
+                }
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let mut left: ChainMap<_, _> = ChainMap::default();
+        left.insert("a", 0);
+        left.insert("b", 1);
+        left.new_child();
+        left.insert("c", 2);
+        let mut right: ChainMap<_, _> = ChainMap::default();
+        right.insert("a", 0);
+        right.insert("b", 2);
+        right.insert("d", 3);
+        assert_eq!(
+            left.diff(&right).render(),
+            "--- left (2 layers)\n\
+             +++ right (1 layers)\n\
+             @@ layer 0 @@\n\
+             - \"b\": 1\n\
+             + \"b\": 2\n\
+             + \"d\": 3\n\
+             @@ layer 1, only in left @@\n\
+             - \"c\": 2\n"
+        );
+        assert!(left.diff(&left).is_empty());
+        assert_chain_eq!(left, left.clone());
+    }
+
+    #[test]
+    #[should_panic(expected = "@@ layer 1, only in right @@")]
+    fn assert_chain_eq_reports_diff() {
+        let left: ChainMap<&str, i32> = ChainMap::default();
+        let mut right = left.clone();
+        right.new_child();
+        right.insert("x", 1);
+        assert_chain_eq!(left, right, "scopes should match");
+    }
+}
@@ -0,0 +1,89 @@
+use crate::ChainMap;
+use std::{
+    fmt::{Debug, Write},
+    hash::{BuildHasher, Hash},
+};
+
+impl<K, V, S> ChainMap<K, V, S>
+where
+    K: Hash + Eq + Debug,
+    S: BuildHasher,
+{
+    /// Renders the layers as a Graphviz DOT graph.
+    ///
+    /// Each layer is a record listing its keys, with a solid edge to the
+    /// layer it is nested in and a dashed edge from every key to the
+    /// binding it shadows.
+    pub fn to_dot(&self) -> String {
+        let layers: Vec<Vec<(String, &K)>> = self
+            .layers()
+            .map(|map| {
+                let mut keys: Vec<_> = map.keys().map(|k| (format!("{:?}", k), k)).collect();
+                keys.sort_by(|a, b| a.0.cmp(&b.0));
+                keys
+            })
+            .collect();
+        let mut out = String::from("digraph ChainMap {\n    node [shape=record];\n");
+        for (idx, keys) in layers.iter().enumerate() {
+            let _ = write!(out, "    layer{} [label=\"{{layer {}", idx, idx);
+            for (port, (name, _)) in keys.iter().enumerate() {
+                let _ = write!(out, "|<k{}> {}", port, escape(name));
+            }
+            out.push_str("}\"];\n");
+            if idx > 0 {
+                let _ = writeln!(out, "    layer{} -> layer{};", idx, idx - 1);
+            }
+        }
+        for (idx, keys) in layers.iter().enumerate() {
+            for (port, (_, key)) in keys.iter().enumerate() {
+                let shadowed = layers[..idx]
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .find_map(|(outer, keys)| {
+                        keys.iter().position(|(_, k)| k == key).map(|p| (outer, p))
+                    });
+                if let Some((outer, outer_port)) = shadowed {
+                    let _ = writeln!(
+                        out,
+                        "    layer{}:k{} -> layer{}:k{} [style=dashed];",
+                        idx, port, outer, outer_port
+                    );
+                }
+            }
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// Escapes the characters that are special inside a record label.
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '{' | '}' | '|' | '<' | '>' | '"' | '\\') {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dot_output() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.insert("y", 0);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        let dot = chain_map.to_dot();
+        assert!(dot.starts_with("digraph ChainMap {"));
+        assert!(dot.contains("layer0 [label=\"{layer 0|<k0> \\\"x\\\"|<k1> \\\"y\\\"}\"];"));
+        assert!(dot.contains("layer1 -> layer0;"));
+        assert!(dot.contains("layer1:k0 -> layer0:k0 [style=dashed];"));
+    }
+}
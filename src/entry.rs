@@ -0,0 +1,147 @@
+use crate::ChainMap;
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+/// A view into a single binding of the current scope, created from a
+/// borrowed key by [`ChainMap::entry_ref`]. The owned key is only built if
+/// a value is actually inserted.
+pub enum EntryRef<'a, 'q, K, Q: ?Sized, V, S = RandomState> {
+    Occupied(OccupiedEntryRef<'a, V>),
+    Vacant(VacantEntryRef<'a, 'q, K, Q, V, S>),
+}
+
+pub struct OccupiedEntryRef<'a, V> {
+    value: &'a mut V,
+}
+
+pub struct VacantEntryRef<'a, 'q, K, Q: ?Sized, V, S> {
+    map: &'a mut HashMap<K, V, S>,
+    key: &'q Q,
+}
+
+impl<K, V, S> ChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Returns the entry for a key in the current scope, looked up by a
+    /// borrowed form of the key. Outer scopes are not consulted.
+    pub fn entry_ref<'a, 'q, Q>(&'a mut self, key: &'q Q) -> EntryRef<'a, 'q, K, Q, V, S>
+    where
+        K: Borrow<Q> + From<&'q Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let top = self.maps.len() - 1;
+        let map = self.layer_mut(top);
+        if map.contains_key(key) {
+            EntryRef::Occupied(OccupiedEntryRef {
+                value: map.get_mut(key).unwrap(),
+            })
+        } else {
+            EntryRef::Vacant(VacantEntryRef { map, key })
+        }
+    }
+}
+
+impl<'a, 'q, K, Q, V, S> EntryRef<'a, 'q, K, Q, V, S>
+where
+    K: Hash + Eq + Borrow<Q> + From<&'q Q>,
+    Q: Hash + Eq + ?Sized,
+    S: BuildHasher,
+{
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F>(self, default: F) -> &'a mut V
+    where
+        F: FnOnce() -> V,
+    {
+        match self {
+            EntryRef::Occupied(entry) => entry.into_mut(),
+            EntryRef::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut V),
+    {
+        if let EntryRef::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, V> OccupiedEntryRef<'a, V> {
+    pub fn get(&self) -> &V {
+        self.value
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        self.value
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        self.value
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.value, value)
+    }
+}
+
+impl<'a, 'q, K, Q, V, S> VacantEntryRef<'a, 'q, K, Q, V, S>
+where
+    K: Hash + Eq + Borrow<Q> + From<&'q Q>,
+    Q: Hash + Eq + ?Sized,
+    S: BuildHasher,
+{
+    pub fn key(&self) -> &'q Q {
+        self.key
+    }
+
+    /// Builds the owned key and binds it to `value` in the current scope.
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.map.entry(K::from(self.key)).or_insert(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn entry_ref_vacant() {
+        let mut chain_map: ChainMap<String, i32> = ChainMap::default();
+        chain_map.insert("x".to_string(), 0);
+        chain_map.new_child();
+        *chain_map.entry_ref("x").or_insert(1) += 1;
+        assert_eq!(chain_map.get("x"), Some(&2));
+        assert_eq!(chain_map.maps[0].get("x"), Some(&0));
+    }
+
+    #[test]
+    fn entry_ref_occupied() {
+        let mut chain_map: ChainMap<String, i32> = ChainMap::default();
+        chain_map.insert("x".to_string(), 0);
+        chain_map.entry_ref("x").and_modify(|v| *v += 5).or_default();
+        assert_eq!(chain_map.get("x"), Some(&5));
+        match chain_map.entry_ref("y") {
+            EntryRef::Vacant(entry) => assert_eq!(entry.key(), "y"),
+            EntryRef::Occupied(_) => panic!("y should be vacant"),
+        }
+        assert_eq!(chain_map.get("y"), None);
+    }
+}
@@ -0,0 +1,217 @@
+use crate::{Layer, LayerStorage};
+use std::cell::OnceCell;
+
+/// How an [`EnvLayer`] maps a chain key to an environment variable name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum CaseMapping {
+    /// Use the key as is
+    #[default]
+    Preserve,
+    /// Upper case the key, so `port` reads `PORT`
+    Upper,
+    /// Lower case the key
+    Lower,
+}
+
+/// A read-through view of the process environment, usable as the outermost
+/// layer of a `String` keyed chain of boxed layers.
+///
+/// Nothing is read up front and nothing is cached: every lookup that
+/// reaches the layer reads the variable with [`std::env::var`], and
+/// iterating reads [`std::env::vars_os`], so changes to the environment
+/// are seen straight away. Variables that are unset or not valid unicode
+/// are treated as unbound. The layer is read only.
+///
+/// Since lookups hand out references, each distinct value read is kept by
+/// the layer for as long as it lives; reading a variable again while its
+/// value is unchanged reuses the kept copy.
+///
+/// ```
+/// # use hash_chain::{ChainMap, DynLayer, EnvLayer};
+/// # use std::collections::hash_map::RandomState;
+/// std::env::set_var("HASH_CHAIN_DOC_HOST", "example.com");
+/// let env = EnvLayer::new().with_prefix("HASH_CHAIN_DOC_");
+/// let mut config: ChainMap<String, String, RandomState, Box<dyn DynLayer<String, String, str>>> =
+///     ChainMap::from_layer(Box::new(env));
+/// config.new_child();
+/// config.insert("port".to_string(), "8080".to_string());
+/// assert_eq!(config.get("HOST").map(String::as_str), Some("example.com"));
+/// assert_eq!(config.get("port").map(String::as_str), Some("8080"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EnvLayer {
+    prefix: String,
+    case: CaseMapping,
+    read: OnceCell<Box<Var>>,
+}
+
+/// A value read by an [`EnvLayer`], kept in a list that only grows, so
+/// references to it stay valid for as long as the layer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Var {
+    key: String,
+    value: String,
+    next: OnceCell<Box<Var>>,
+}
+
+impl EnvLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only consider variables starting with `prefix`; the key `port` with
+    /// a prefix of `APP_` reads `APP_port`, subject to the case mapping.
+    /// The prefix itself is never case mapped.
+    pub fn with_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    pub fn with_case(mut self, case: CaseMapping) -> Self {
+        self.case = case;
+        self
+    }
+
+    /// The environment variable name a key is read from.
+    pub fn var_name(&self, key: &str) -> String {
+        let key = match self.case {
+            CaseMapping::Preserve => key.to_string(),
+            CaseMapping::Upper => key.to_uppercase(),
+            CaseMapping::Lower => key.to_lowercase(),
+        };
+        format!("{}{}", self.prefix, key)
+    }
+
+    /// Reads the variable for `key` from the environment as it is now.
+    pub fn var(&self, key: &str) -> Option<String> {
+        std::env::var(self.var_name(key)).ok()
+    }
+
+    /// The keys and values of every variable the layer can see, read from
+    /// the environment as it is now. A variable is visible if its name
+    /// starts with the prefix and the rest of it is unchanged by the case
+    /// mapping.
+    fn visible(&self) -> impl Iterator<Item = (String, String)> + '_ {
+        std::env::vars_os().filter_map(move |(name, value)| {
+            let name = name.into_string().ok()?;
+            let key = name.strip_prefix(self.prefix.as_str())?;
+            if self.var_name(key) != name {
+                return None;
+            }
+            Some((key.to_string(), value.into_string().ok()?))
+        })
+    }
+
+    fn lookup(&self, key: &str) -> Option<&Var> {
+        let value = self.var(key)?;
+        Some(self.keep(key, value))
+    }
+
+    /// Returns the kept copy of `key` bound to `value`, keeping a new one
+    /// if the pair hasn't been read before.
+    fn keep(&self, key: &str, value: String) -> &Var {
+        let mut cell = &self.read;
+        while let Some(var) = cell.get() {
+            if var.key == key && var.value == value {
+                return var;
+            }
+            cell = &var.next;
+        }
+        cell.get_or_init(|| {
+            Box::new(Var {
+                key: key.to_string(),
+                value,
+                next: OnceCell::new(),
+            })
+        })
+    }
+}
+
+impl LayerStorage<String, String> for EnvLayer {
+    type Iter<'a> = Box<dyn Iterator<Item = (&'a String, &'a String)> + 'a>;
+
+    fn insert(&mut self, _key: String, _value: String) -> Result<Option<String>, crate::Error> {
+        Err(crate::Error::ReadOnlyLayer)
+    }
+
+    /// Iterates over every variable the layer can see.
+    fn iter(&self) -> Self::Iter<'_> {
+        Box::new(self.visible().map(move |(key, value)| {
+            let var = self.keep(&key, value);
+            (&var.key, &var.value)
+        }))
+    }
+
+    fn len(&self) -> usize {
+        self.visible().count()
+    }
+}
+
+impl Layer<String, String, str> for EnvLayer {
+    fn get(&self, key: &str) -> Option<&String> {
+        self.lookup(key).map(|var| &var.value)
+    }
+    fn get_key_value(&self, key: &str) -> Option<(&String, &String)> {
+        self.lookup(key).map(|var| (&var.key, &var.value))
+    }
+    fn get_mut(&mut self, _key: &str) -> Option<&mut String> {
+        None
+    }
+    fn remove(&mut self, _key: &str) -> Option<String> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ChainMap, DynLayer};
+    use std::collections::hash_map::RandomState;
+
+    #[test]
+    fn env_layer() {
+        let env = EnvLayer::new()
+            .with_prefix("HASH_CHAIN_ENV_TEST_")
+            .with_case(CaseMapping::Upper);
+        assert_eq!(env.var_name("port"), "HASH_CHAIN_ENV_TEST_PORT");
+        assert_eq!(env.get("port"), None);
+        std::env::set_var("HASH_CHAIN_ENV_TEST_PORT", "8080");
+        assert_eq!(env.get("port").map(String::as_str), Some("8080"));
+        std::env::set_var("HASH_CHAIN_ENV_TEST_PORT", "9090");
+        assert_eq!(env.get("port").map(String::as_str), Some("9090"));
+        assert_eq!(env.var("port"), Some("9090".to_string()));
+        std::env::set_var("HASH_CHAIN_ENV_TEST_port", "ignored");
+        assert_eq!(env.len(), 1);
+        assert_eq!(
+            env.iter().collect::<Vec<_>>(),
+            [(&"PORT".to_string(), &"9090".to_string())]
+        );
+        std::env::remove_var("HASH_CHAIN_ENV_TEST_PORT");
+        assert_eq!(env.get("port"), None);
+        assert!(env.is_empty());
+    }
+
+    #[test]
+    fn outermost_layer() {
+        std::env::set_var("HASH_CHAIN_ENV_LAYER_TEST_HOST", "from-env");
+        std::env::set_var("HASH_CHAIN_ENV_LAYER_TEST_USER", "from-env");
+        let env = EnvLayer::new()
+            .with_prefix("HASH_CHAIN_ENV_LAYER_TEST_")
+            .with_case(CaseMapping::Upper);
+        let mut chain_map: ChainMap<
+            String,
+            String,
+            RandomState,
+            Box<dyn DynLayer<String, String, str>>,
+        > = ChainMap::from_layer(Box::new(env));
+        chain_map.new_child();
+        chain_map.insert("user".to_string(), "from-chain".to_string());
+        assert_eq!(chain_map.get("host").unwrap(), "from-env");
+        assert_eq!(chain_map.get("user").unwrap(), "from-chain");
+        assert_eq!(chain_map.get("shell"), None);
+        assert_eq!(chain_map.get_last_index("host"), Some(0));
+        assert!(chain_map
+            .insert_at(0, "host".to_string(), String::new())
+            .is_err());
+    }
+}
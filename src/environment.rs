@@ -0,0 +1,366 @@
+use crate::ChainMap;
+use std::{
+    cell::RefCell,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    rc::Rc,
+};
+
+/// A parent-linked scope for tree-walking interpreters.
+///
+/// Each `Environment` is a handle to a single shared scope; cloning the
+/// handle does not copy any bindings. Child environments keep their
+/// enclosing environment alive, so closures can hold on to the scope they
+/// were created in while the interpreter moves on.
+///
+/// A [`ChainMap`] owns its whole stack of scopes, so two closures created
+/// in the same scope can't each extend it; that is why the scopes here are
+/// linked to their parent instead. [`to_chain_map`](Self::to_chain_map)
+/// flattens the scopes visible from an environment into a chain.
+///
+/// No borrow of a scope is held while user code runs, so the closure
+/// passed to [`with_value`](Self::with_value) may define or assign
+/// bindings. Dropping a deep chain of environments, or asking for its
+/// depth, doesn't recurse.
+pub struct Environment<K, V, S = RandomState> {
+    inner: Rc<RefCell<Scope<K, V, S>>>,
+}
+
+struct Scope<K, V, S> {
+    values: HashMap<K, Rc<V>, S>,
+    enclosing: Option<Environment<K, V, S>>,
+}
+
+impl<K, V, S> Environment<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Creates a root environment holding the bindings in `map`.
+    pub fn with_map(map: HashMap<K, V, S>) -> Self
+    where
+        S: Clone,
+    {
+        Self::from_scope(map, None)
+    }
+
+    /// Creates a child environment holding the bindings in `map`, whose
+    /// lookups fall through to `self`.
+    pub fn child_with(&self, map: HashMap<K, V, S>) -> Self
+    where
+        S: Clone,
+    {
+        Self::from_scope(map, Some(self.clone()))
+    }
+
+    fn from_scope(map: HashMap<K, V, S>, enclosing: Option<Self>) -> Self
+    where
+        S: Clone,
+    {
+        let mut values = HashMap::with_capacity_and_hasher(map.len(), map.hasher().clone());
+        values.extend(map.into_iter().map(|(k, v)| (k, Rc::new(v))));
+        Self {
+            inner: Rc::new(RefCell::new(Scope { values, enclosing })),
+        }
+    }
+
+    /// The environment this one was created from, if any.
+    pub fn enclosing(&self) -> Option<Self> {
+        self.inner.borrow().enclosing.clone()
+    }
+
+    /// Binds `key` in this environment, shadowing any binding in an
+    /// enclosing one. If the key was already bound here, the old value
+    /// is returned, cloned if a [`with_value`](Self::with_value) callback
+    /// is still reading it.
+    pub fn define(&self, key: K, value: V) -> Option<V>
+    where
+        V: Clone,
+    {
+        let old = self.inner.borrow_mut().values.insert(key, Rc::new(value))?;
+        Some(Rc::unwrap_or_clone(old))
+    }
+
+    /// Updates the nearest existing binding of `key`, returning the old
+    /// value, or `Error::KeyNotFound` if no environment in the chain binds it.
+    pub fn assign<Q>(&self, key: &Q, value: V) -> Result<V, crate::Error>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        let mut env = self.clone();
+        loop {
+            let enclosing = {
+                let mut scope = env.inner.borrow_mut();
+                if let Some(slot) = scope.values.get_mut(key) {
+                    let old = std::mem::replace(slot, Rc::new(value));
+                    return Ok(Rc::unwrap_or_clone(old));
+                }
+                scope.enclosing.clone()
+            };
+            env = enclosing.ok_or(crate::Error::KeyNotFound)?;
+        }
+    }
+
+    /// Calls `f` with the nearest binding of `key`, if any. No scope is
+    /// borrowed while `f` runs.
+    pub fn with_value<Q, F, R>(&self, key: &Q, f: F) -> Option<R>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: FnOnce(&V) -> R,
+    {
+        self.lookup(key).map(|value| f(&value))
+    }
+
+    /// Returns a clone of the nearest binding of `key`, if any.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.with_value(key, V::clone)
+    }
+
+    fn lookup<Q>(&self, key: &Q) -> Option<Rc<V>>
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let mut env = self.clone();
+        loop {
+            let enclosing = {
+                let scope = env.inner.borrow();
+                if let Some(value) = scope.values.get(key) {
+                    return Some(Rc::clone(value));
+                }
+                scope.enclosing.clone()
+            };
+            env = enclosing?;
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.lookup(key).is_some()
+    }
+
+    /// Returns `true` if `key` is bound in this environment itself,
+    /// ignoring any enclosing ones.
+    pub fn has_local<Q>(&self, key: &Q) -> bool
+    where
+        K: std::borrow::Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.inner.borrow().values.contains_key(key)
+    }
+
+    /// The number of environments from this one to the root, inclusive.
+    pub fn depth(&self) -> usize {
+        let mut depth = 1;
+        let mut env = self.enclosing();
+        while let Some(current) = env {
+            depth += 1;
+            env = current.enclosing();
+        }
+        depth
+    }
+
+    /// Returns `true` if both handles refer to the same scope.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// Copies the scopes visible from this environment into a chain, the
+    /// root as its first layer and this environment as its last.
+    pub fn to_chain_map(&self) -> ChainMap<K, V, S>
+    where
+        K: Clone,
+        V: Clone,
+        S: Clone,
+    {
+        let mut maps = Vec::with_capacity(self.depth());
+        let mut env = Some(self.clone());
+        while let Some(current) = env {
+            let scope = current.inner.borrow();
+            let mut map = HashMap::with_capacity_and_hasher(
+                scope.values.len(),
+                scope.values.hasher().clone(),
+            );
+            map.extend(scope.values.iter().map(|(k, v)| (k.clone(), V::clone(v))));
+            maps.push(map);
+            env = scope.enclosing.clone();
+        }
+        maps.reverse();
+        ChainMap::from_maps(maps)
+    }
+}
+
+impl<K, V> Environment<K, V>
+where
+    K: Hash + Eq,
+{
+    pub fn new() -> Self {
+        Self::with_map(HashMap::new())
+    }
+}
+
+impl<K, V, S> Environment<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default + Clone,
+{
+    /// Creates an empty child environment whose lookups fall through to
+    /// `self`. The parent is shared, not copied.
+    pub fn child(&self) -> Self {
+        self.child_with(HashMap::default())
+    }
+}
+
+impl<K, V> Default for Environment<K, V>
+where
+    K: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Unlinks the enclosing environments one at a time, so dropping the last
+/// handle to a deep chain doesn't recurse once per scope.
+impl<K, V, S> Drop for Scope<K, V, S> {
+    fn drop(&mut self) {
+        let mut enclosing = self.enclosing.take();
+        while let Some(env) = enclosing {
+            enclosing = match Rc::try_unwrap(env.inner) {
+                Ok(mut scope) => scope.get_mut().enclosing.take(),
+                Err(_) => None,
+            };
+        }
+    }
+}
+
+impl<K, V, S> Clone for Environment<K, V, S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Rc::clone(&self.inner),
+        }
+    }
+}
+
+impl<K, V, S> core::fmt::Debug for Environment<K, V, S>
+where
+    K: Eq + Hash + core::fmt::Debug,
+    V: core::fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> std::fmt::Result {
+        let scope = self.inner.borrow();
+        f.debug_struct("Environment")
+            .field("values", &scope.values)
+            .field("enclosing", &scope.enclosing)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn define_and_get() {
+        let env = Environment::new();
+        env.define("x", 0);
+        assert_eq!(env.get("x"), Some(0));
+        assert_eq!(env.get("y"), None);
+    }
+
+    #[test]
+    fn child_shares_parent() {
+        let global = Environment::new();
+        global.define("x", 0);
+        let local = global.child();
+        local.define("y", 1);
+        global.define("z", 2);
+
+        assert_eq!(local.get("x"), Some(0));
+        assert_eq!(local.get("z"), Some(2));
+        assert_eq!(global.get("y"), None);
+        assert!(local.enclosing().unwrap().ptr_eq(&global));
+        assert_eq!(local.depth(), 2);
+    }
+
+    #[test]
+    fn shadowing() {
+        let global = Environment::new();
+        global.define("x", 0);
+        let local = global.child();
+        local.define("x", 1);
+        assert_eq!(local.get("x"), Some(1));
+        assert_eq!(global.get("x"), Some(0));
+    }
+
+    #[test]
+    fn assign_outer() {
+        let global = Environment::new();
+        global.define("x", 0);
+        let local = global.child();
+        assert_eq!(local.assign("x", 5).unwrap(), 0);
+        assert_eq!(global.get("x"), Some(5));
+        assert!(!local.has_local("x"));
+    }
+
+    #[test]
+    #[should_panic = "KeyNotFound"]
+    fn assign_undefined() {
+        let env: Environment<&str, i32> = Environment::new();
+        env.assign("x", 5).unwrap();
+    }
+
+    #[test]
+    fn redefine_while_reading() {
+        let env = Environment::new();
+        env.define("x", 0);
+        let local = env.child();
+        let seen = local.with_value("x", |x| {
+            env.define("x", x + 1);
+            local.assign("x", x + 2).unwrap();
+            *x
+        });
+        assert_eq!(seen, Some(0));
+        assert_eq!(env.get("x"), Some(2));
+    }
+
+    #[test]
+    fn to_chain_map() {
+        let global = Environment::new();
+        global.define("x", 0);
+        global.define("y", 0);
+        let local = global.child();
+        local.define("x", 1);
+
+        let chain = local.to_chain_map();
+        assert_eq!(chain.child_len(), 2);
+        assert_eq!(chain.get("x"), Some(&1));
+        assert_eq!(chain.get("y"), Some(&0));
+    }
+
+    #[test]
+    fn deep_chain() {
+        let root = Environment::new();
+        root.define("x", 0);
+        let mut env = root.child();
+        for _ in 0..100_000 {
+            env = env.child();
+        }
+        assert_eq!(env.depth(), 100_002);
+        assert_eq!(env.get("x"), Some(0));
+        drop(root);
+        drop(env);
+    }
+}
@@ -1,14 +1,73 @@
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     IndexOutOfRange,
+    Shadowing,
+    KeyNotFound,
+    ReadOnlyLayer,
+    KeyCollision,
+    InvalidHeader,
+    UnsupportedVersion(u16),
+    Serialization(String),
+    /// No scope of the requested kind is open
+    ScopeNotFound,
+    /// A layer couldn't allocate room for more entries
+    Allocation(std::collections::TryReserveError),
+    /// No layer binds the key, along with the closest key some layer does
+    /// bind, if any is close enough to be a likely typo
+    UnknownKey {
+        key: String,
+        suggestion: Option<String>,
+    },
+    /// A value was rejected by the chain's validator
+    Invalid(Box<dyn std::error::Error + Send + Sync>),
+    /// The layer is still shared with a captured environment, so it can't
+    /// be changed in place
+    SharedLayer,
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             Error::IndexOutOfRange => write!(f, "Index out of range"),
+            Error::Shadowing => write!(f, "Key shadows a binding in an outer scope"),
+            Error::KeyNotFound => write!(f, "Key not found"),
+            Error::ReadOnlyLayer => write!(f, "Layer is read only"),
+            Error::KeyCollision => write!(f, "Key is already bound"),
+            Error::InvalidHeader => write!(f, "Missing or invalid header"),
+            Error::UnsupportedVersion(v) => write!(f, "Unsupported format version {}", v),
+            Error::Serialization(msg) => write!(f, "Serialization failed: {}", msg),
+            Error::ScopeNotFound => write!(f, "No scope of the requested kind"),
+            Error::Allocation(e) => write!(f, "Allocation failed: {:?}", e),
+            Error::UnknownKey {
+                key,
+                suggestion: Some(suggestion),
+            } => write!(f, "Unknown key `{}`, did you mean `{}`?", key, suggestion),
+            Error::UnknownKey { key, .. } => write!(f, "Unknown key `{}`", key),
+            Error::Invalid(e) => write!(f, "Invalid value: {}", e),
+            Error::SharedLayer => write!(f, "Layer is shared with a captured environment"),
         }
     }
 }
 
 impl std::error::Error for Error {}
+
+/// Returned by [`ChainMap::declare`](crate::ChainMap::declare) when the
+/// current scope already binds the key.
+#[derive(Debug)]
+pub struct AlreadyDeclared<'a, V> {
+    /// The index of the layer holding the existing declaration
+    pub layer: usize,
+    /// The value the key is already bound to
+    pub existing: &'a V,
+    /// The value that was not inserted
+    pub value: V,
+}
+
+impl<V> std::fmt::Display for AlreadyDeclared<'_, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "Key is already declared in layer {}", self.layer)
+    }
+}
+
+impl<V: std::fmt::Debug> std::error::Error for AlreadyDeclared<'_, V> {}
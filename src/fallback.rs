@@ -0,0 +1,19 @@
+use std::sync::Arc;
+
+pub(crate) type Handler<K, V> = Arc<dyn Fn(&K) -> Option<V> + Send + Sync>;
+
+/// A handler consulted when no layer of a chain binds a key, see
+/// [`ChainMap::set_fallback`](crate::ChainMap::set_fallback).
+pub(crate) struct Fallback<K, V> {
+    pub(crate) handler: Handler<K, V>,
+    pub(crate) cache: bool,
+}
+
+impl<K, V> Clone for Fallback<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            handler: Arc::clone(&self.handler),
+            cache: self.cache,
+        }
+    }
+}
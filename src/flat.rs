@@ -0,0 +1,500 @@
+use crate::{ChainMap, LookupBounds};
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    ops::Index,
+};
+
+/// A chain stored as a single map from each key to the stack of its
+/// bindings, the classic symbol table layout.
+///
+/// Every lookup is a single hash lookup no matter how many scopes the
+/// chain has, at the cost of `remove_child` and `insert_at` having to
+/// touch the binding stack of every affected key. Keys are stored once in
+/// the map and once in the list of keys bound by their scope, so they must
+/// be `Clone`.
+pub struct FlatChainMap<K, V, S = RandomState> {
+    bindings: HashMap<K, Vec<(usize, V)>, S>,
+    scopes: Vec<Vec<K>>,
+}
+
+impl<K, V, S> FlatChainMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    S: BuildHasher,
+{
+    /// Inserts a key-value pair into the current scope.
+    /// If the current scope did not have this key present, None is returned.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let top = self.scopes.len().checked_sub(1)?;
+        self.insert_into(top, key, value)
+    }
+
+    pub fn insert_at(&mut self, idx: usize, key: K, value: V) -> Result<Option<V>, crate::Error> {
+        if idx >= self.scopes.len() {
+            return Err(crate::Error::IndexOutOfRange);
+        }
+        Ok(self.insert_into(idx, key, value))
+    }
+
+    fn insert_into(&mut self, idx: usize, key: K, value: V) -> Option<V> {
+        if let Some(stack) = self.bindings.get_mut(&key) {
+            let pos = stack.partition_point(|(i, _)| *i < idx);
+            if let Some((i, v)) = stack.get_mut(pos) {
+                if *i == idx {
+                    return Some(std::mem::replace(v, value));
+                }
+            }
+            stack.insert(pos, (idx, value));
+        } else {
+            self.bindings.insert(key.clone(), vec![(idx, value)]);
+        }
+        self.scopes[idx].push(key);
+        None
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.bindings.get(key)?.last().map(|(_, v)| v)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.bindings.contains_key(key)
+    }
+
+    /// Removes the key from the current scope, returning its value. A
+    /// binding in an outer scope becomes visible again.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let top = self.scopes.len().checked_sub(1)?;
+        let value = self.pop_binding(top, key)?;
+        let keys = &mut self.scopes[top];
+        if let Some(pos) = keys.iter().position(|k| k.borrow() == key) {
+            keys.swap_remove(pos);
+        }
+        Some(value)
+    }
+
+    /// Pops the binding the scope at `idx` holds for the key off its
+    /// stack. A stack whose innermost binding belongs to another scope is
+    /// left alone, so one bad key cannot leave the rest of a scope behind.
+    fn pop_binding<Q>(&mut self, idx: usize, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let stack = self.bindings.get_mut(key)?;
+        if stack.last()?.0 != idx {
+            return None;
+        }
+        let (_, value) = stack.pop()?;
+        if stack.is_empty() {
+            self.bindings.remove(key);
+        }
+        Some(value)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.bindings.get_mut(key)?.last_mut().map(|(_, v)| v)
+    }
+
+    /// Returns the value corresponding to the key, searching only the
+    /// layers selected by `bounds`, innermost first.
+    pub fn get_in<Q>(&self, bounds: LookupBounds, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let range = bounds.range(self.scopes.len());
+        self.bindings
+            .get(key)?
+            .iter()
+            .rev()
+            .find(|(i, _)| range.contains(i))
+            .map(|(_, v)| v)
+    }
+
+    pub fn get_in_mut<Q>(&mut self, bounds: LookupBounds, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let range = bounds.range(self.scopes.len());
+        self.bindings
+            .get_mut(key)?
+            .iter_mut()
+            .rev()
+            .find(|(i, _)| range.contains(i))
+            .map(|(_, v)| v)
+    }
+
+    pub fn get_before<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_in(LookupBounds::Before(idx), key)
+    }
+
+    pub fn get_before_mut<Q>(&mut self, idx: usize, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_in_mut(LookupBounds::Before(idx), key)
+    }
+
+    pub fn get_after<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_in(LookupBounds::After(idx), key)
+    }
+
+    pub fn get_after_mut<Q>(&mut self, idx: usize, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_in_mut(LookupBounds::After(idx), key)
+    }
+
+    pub fn last_has<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let top = self.scopes.len().checked_sub(1);
+        top.is_some() && self.get_last_index(key) == top
+    }
+
+    pub fn has_at<Q>(&self, idx: usize, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.bindings
+            .get(key)
+            .is_some_and(|stack| stack.iter().any(|(i, _)| *i == idx))
+    }
+
+    pub fn get_last_index<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.bindings.get(key)?.last().map(|(i, _)| *i)
+    }
+
+    pub fn new_child_with(&mut self, map: HashMap<K, V, S>) {
+        let idx = self.scopes.len();
+        self.scopes.push(Vec::with_capacity(map.len()));
+        for (k, v) in map {
+            self.insert_into(idx, k, v);
+        }
+    }
+
+    pub fn new_child(&mut self) {
+        self.scopes.push(Vec::new());
+    }
+
+    /// Moves every scope of `other` on top of this chain, leaving `other`
+    /// without any scopes.
+    pub fn append(&mut self, other: &mut Self) {
+        let offset = self.scopes.len();
+        for (key, stack) in other.bindings.drain() {
+            self.bindings
+                .entry(key)
+                .or_default()
+                .extend(stack.into_iter().map(|(idx, v)| (idx + offset, v)));
+        }
+        self.scopes.append(&mut other.scopes);
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.scopes.len()
+    }
+
+    /// The number of visible keys, counting each key once no matter how
+    /// many scopes bind it.
+    pub fn len(&self) -> usize {
+        self.bindings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bindings.is_empty()
+    }
+
+    /// The number of bindings across every scope, including ones that are
+    /// shadowed by an inner scope.
+    pub fn total_len(&self) -> usize {
+        self.scopes.iter().map(Vec::len).sum()
+    }
+}
+
+impl<K, V, S> FlatChainMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    S: BuildHasher + Default,
+{
+    pub fn new(map: HashMap<K, V, S>) -> Self {
+        let mut ret = Self {
+            bindings: HashMap::with_capacity_and_hasher(map.len(), S::default()),
+            scopes: Vec::new(),
+        };
+        ret.new_child_with(map);
+        ret
+    }
+
+    /// Removes the current scope, popping its binding off the stack of
+    /// every key it bound. As with [`ChainMap::remove_child`], the last
+    /// scope is cleared rather than removed.
+    pub fn remove_child(&mut self) -> Option<HashMap<K, V, S>> {
+        let idx = self.scopes.len().checked_sub(1)?;
+        let keys = if idx == 0 {
+            std::mem::take(&mut self.scopes[0])
+        } else {
+            self.scopes.pop()?
+        };
+        let mut ret = HashMap::with_capacity_and_hasher(keys.len(), S::default());
+        for key in keys {
+            if let Some(value) = self.pop_binding(idx, &key) {
+                ret.insert(key, value);
+            }
+        }
+        Some(ret)
+    }
+
+    /// Splits the chain in two at `idx`, returning the scopes from `idx`
+    /// on as a new chain.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is greater than the number of scopes.
+    pub fn split_off(&mut self, idx: usize) -> Self {
+        let scopes = self.scopes.split_off(idx);
+        let mut bindings = HashMap::with_hasher(S::default());
+        for key in scopes.iter().flatten() {
+            if bindings.contains_key(key) {
+                continue;
+            }
+            if let Some(stack) = self.bindings.get_mut(key) {
+                let pos = stack.partition_point(|(i, _)| *i < idx);
+                let moved: Vec<_> = stack.drain(pos..).map(|(i, v)| (i - idx, v)).collect();
+                if stack.is_empty() {
+                    self.bindings.remove(key);
+                }
+                bindings.insert(key.clone(), moved);
+            }
+        }
+        Self { bindings, scopes }
+    }
+
+    /// Converts the symbol table into a regular [`ChainMap`] with the
+    /// same scopes.
+    pub fn into_chain_map(self) -> ChainMap<K, V, S> {
+        let mut maps: Vec<HashMap<K, V, S>> = self
+            .scopes
+            .iter()
+            .map(|keys| HashMap::with_capacity_and_hasher(keys.len(), S::default()))
+            .collect();
+        for (key, stack) in self.bindings {
+            for (idx, value) in stack {
+                maps[idx].insert(key.clone(), value);
+            }
+        }
+        ChainMap::from_maps(maps)
+    }
+}
+
+impl<K, V> Default for FlatChainMap<K, V>
+where
+    K: Hash + Eq + Clone,
+{
+    fn default() -> Self {
+        Self::new(HashMap::new())
+    }
+}
+
+impl<K, Q: ?Sized, V, S> Index<&Q> for FlatChainMap<K, V, S>
+where
+    K: Eq + Hash + Clone + Borrow<Q>,
+    Q: Eq + Hash,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `FlatChainMap`.
+    #[inline]
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K, V, S> PartialEq for FlatChainMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &FlatChainMap<K, V, S>) -> bool {
+        self.scopes.len() == other.scopes.len() && self.bindings == other.bindings
+    }
+}
+
+impl<K, V, S> Eq for FlatChainMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+impl<K, V, S> core::fmt::Debug for FlatChainMap<K, V, S>
+where
+    K: Eq + Hash + core::fmt::Debug,
+    V: core::fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("FlatChainMap")
+            .field("bindings", &self.bindings)
+            .field("scopes", &self.scopes)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn flat_scopes() {
+        let mut flat = FlatChainMap::default();
+        flat.insert("x", 0);
+        flat.insert("y", 0);
+        flat.new_child();
+        assert_eq!(flat.insert("x", 1), None);
+        assert_eq!(flat.insert("x", 2), Some(1));
+        assert_eq!(flat["x"], 2);
+        assert_eq!(flat.get_before(1, "x"), Some(&0));
+        assert_eq!(flat.len(), 2);
+        assert_eq!(flat.total_len(), 3);
+        let removed = flat.remove_child().unwrap();
+        assert_eq!(removed.get("x"), Some(&2));
+        assert_eq!(flat["x"], 0);
+        assert_eq!(flat.child_len(), 1);
+        let removed = flat.remove_child().unwrap();
+        assert_eq!(removed.len(), 2);
+        assert!(flat.is_empty());
+        assert_eq!(flat.child_len(), 1);
+    }
+
+    #[test]
+    fn flat_insert_at() {
+        let mut flat = FlatChainMap::default();
+        flat.new_child();
+        flat.new_child();
+        flat.insert("x", 2);
+        flat.insert_at(0, "x", 0).unwrap();
+        flat.insert_at(1, "x", 1).unwrap();
+        assert!(flat.insert_at(3, "x", 3).is_err());
+        assert_eq!(flat.get_in(LookupBounds::Before(2), "x"), Some(&1));
+        assert!(flat.has_at(0, "x"));
+        assert!(flat.last_has("x"));
+        flat.remove_child();
+        assert_eq!(flat["x"], 1);
+        assert_eq!(flat.get_last_index("x"), Some(1));
+    }
+
+    #[test]
+    fn flat_matches_chain_map() {
+        let mut flat = FlatChainMap::default();
+        flat.insert("a", 1);
+        flat.new_child();
+        flat.insert("b", 2);
+        flat.insert("a", 3);
+        let chain_map = flat.into_chain_map();
+        let mut expected: ChainMap<_, _> = ChainMap::default();
+        expected.insert("a", 1);
+        expected.new_child();
+        expected.insert("b", 2);
+        expected.insert("a", 3);
+        assert_eq!(chain_map, expected);
+    }
+
+    #[test]
+    fn flat_remove() {
+        let mut flat = FlatChainMap::default();
+        flat.insert("x", 0);
+        flat.new_child();
+        assert_eq!(flat.remove("x"), None);
+        flat.insert("x", 1);
+        assert_eq!(flat.remove("x"), Some(1));
+        assert!(flat.contains_key("x"));
+        assert_eq!(flat.total_len(), 1);
+        assert!(flat.remove_child().unwrap().is_empty());
+        assert_eq!(flat.remove("x"), Some(0));
+        assert!(!flat.contains_key("x"));
+    }
+
+    #[test]
+    fn flat_split_off_append() {
+        let mut flat = FlatChainMap::default();
+        flat.insert("x", 0);
+        flat.new_child();
+        flat.insert("x", 1);
+        flat.insert("y", 1);
+        flat.new_child();
+        flat.insert("x", 2);
+        let mut upper = flat.split_off(1);
+        assert_eq!(flat.child_len(), 1);
+        assert_eq!(flat["x"], 0);
+        assert!(!flat.contains_key("y"));
+        assert_eq!(upper.child_len(), 2);
+        assert_eq!(upper.get_before(1, "x"), Some(&1));
+        assert_eq!(upper.get_last_index("x"), Some(1));
+        flat.append(&mut upper);
+        assert_eq!(upper.child_len(), 0);
+        assert!(upper.is_empty());
+        assert_eq!(flat.child_len(), 3);
+        assert_eq!(flat.get_last_index("x"), Some(2));
+        assert_eq!(flat.remove_child().unwrap().get("x"), Some(&2));
+        assert_eq!(flat.remove_child().unwrap().len(), 2);
+        assert_eq!(flat["x"], 0);
+    }
+
+    #[test]
+    fn flat_remove_child_restores_scope() {
+        let mut flat = FlatChainMap::default();
+        flat.new_child();
+        flat.insert("a", 1);
+        flat.insert("b", 1);
+        // a stale key list entry must not stop the rest of the scope from
+        // being popped
+        flat.scopes[1].insert(0, "missing");
+        let removed = flat.remove_child().unwrap();
+        assert_eq!(removed.len(), 2);
+        assert!(flat.is_empty());
+    }
+}
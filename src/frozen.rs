@@ -0,0 +1,261 @@
+use crate::{ChainMap, LookupBounds};
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap, HashSet},
+    hash::{BuildHasher, Hash},
+    ops::Index,
+    sync::Arc,
+};
+
+/// An immutable, point-in-time snapshot of a chain that can be shared
+/// between threads, created with [`ChainMap::freeze_shared`].
+///
+/// Cloning a `FrozenChainMap` only bumps a reference count, and it is
+/// `Send + Sync` whenever its keys, values and hasher are.
+pub struct FrozenChainMap<K, V, S = RandomState> {
+    maps: Arc<[HashMap<K, V, S>]>,
+}
+
+impl<K, V, S> FrozenChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_in(LookupBounds::All, key)
+    }
+
+    /// Returns the value corresponding to the key, searching only the
+    /// layers selected by `bounds`, innermost first.
+    pub fn get_in<Q>(&self, bounds: LookupBounds, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps[bounds.range(self.maps.len())]
+            .iter()
+            .rev()
+            .find_map(|map| map.get(key))
+    }
+
+    pub fn get_before<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_in(LookupBounds::Before(idx), key)
+    }
+
+    pub fn get_after<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_in(LookupBounds::After(idx), key)
+    }
+
+    /// Returns the value bound to the key in the scope `depth` hops out
+    /// from the innermost one, without searching any other scope.
+    pub fn get_at_depth<Q>(&self, depth: usize, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.maps.len().checked_sub(depth)?.checked_sub(1)?;
+        self.maps[idx].get(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn last_has<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.last().is_some_and(|map| map.contains_key(key))
+    }
+
+    pub fn has_at<Q>(&self, idx: usize, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.get(idx).is_some_and(|map| map.contains_key(key))
+    }
+
+    pub fn get_last_index<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.iter().rposition(|map| map.contains_key(key))
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.maps.len()
+    }
+
+    /// The number of visible keys, counting each key once no matter how
+    /// many scopes bind it.
+    pub fn len(&self) -> usize {
+        self.effective_keys().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.maps.iter().all(HashMap::is_empty)
+    }
+
+    /// The number of bindings across every scope, including ones that are
+    /// shadowed by an inner scope.
+    pub fn total_len(&self) -> usize {
+        self.maps.iter().map(HashMap::len).sum()
+    }
+
+    pub fn effective_keys(&self) -> HashSet<&K> {
+        self.maps.iter().flat_map(|map| map.keys()).collect()
+    }
+}
+
+impl<K, V, S> FrozenChainMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    /// Copies the snapshot back into a regular, mutable chain.
+    pub fn thaw(&self) -> ChainMap<K, V, S> {
+        ChainMap::from_maps(self.maps.to_vec())
+    }
+}
+
+impl<K, V, S> ChainMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    /// Takes a snapshot of the chain that can be shared with other threads.
+    ///
+    /// Unlike [`capture`](Self::capture), every scope is copied into the
+    /// snapshot, so the result holds no `Rc`s.
+    pub fn freeze_shared(&self) -> FrozenChainMap<K, V, S> {
+        let maps: Vec<_> = self.layers().cloned().collect();
+        FrozenChainMap { maps: maps.into() }
+    }
+}
+
+impl<K, V, S> Clone for FrozenChainMap<K, V, S> {
+    fn clone(&self) -> Self {
+        Self {
+            maps: Arc::clone(&self.maps),
+        }
+    }
+}
+
+impl<K, Q: ?Sized, V, S> Index<&Q> for FrozenChainMap<K, V, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the snapshot.
+    #[inline]
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K, V, S> PartialEq for FrozenChainMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &FrozenChainMap<K, V, S>) -> bool {
+        self.maps == other.maps
+    }
+}
+
+impl<K, V, S> Eq for FrozenChainMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+impl<K, V, S> core::fmt::Debug for FrozenChainMap<K, V, S>
+where
+    K: Eq + Hash + core::fmt::Debug,
+    V: core::fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("FrozenChainMap")
+            .field("maps", &self.maps)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn frozen_is_a_snapshot() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        let frozen = chain_map.freeze_shared();
+        chain_map.insert("x", 2);
+        assert_eq!(frozen.get("x"), Some(&1));
+        assert_eq!(frozen.get_before(1, "x"), Some(&0));
+        assert_eq!(frozen.child_len(), 2);
+        assert_eq!(frozen.thaw().get("x"), Some(&1));
+    }
+
+    #[test]
+    fn frozen_across_threads() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x".to_string(), 1);
+        let frozen = chain_map.freeze_shared();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let frozen = frozen.clone();
+                thread::spawn(move || frozen["x"])
+            })
+            .collect();
+        for handle in handles {
+            assert_eq!(handle.join().unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn frozen_includes_captured() {
+        let mut outer: ChainMap<_, _> = ChainMap::default();
+        outer.insert("x", 0);
+        let mut inner = ChainMap::from_captured(outer.capture(), HashMap::new());
+        inner.insert("y", 1);
+        let frozen = inner.freeze_shared();
+        assert_eq!(frozen.get("x"), Some(&0));
+        assert_eq!(frozen.get("y"), Some(&1));
+    }
+}
@@ -0,0 +1,125 @@
+//! Entry points for the fuzz targets in `fuzz/`, built under
+//! `cfg(fuzzing)`, which `cargo fuzz` sets.
+//!
+//! Each entry point interprets its input as a sequence of operations,
+//! applies them to a chain and to a model of it built on
+//! `Vec<HashMap>`, and panics as soon as the two resolve a key
+//! differently.
+
+use crate::ChainMap;
+use std::collections::HashMap;
+
+/// The keys the operations use, kept small so that bindings collide and
+/// shadow each other often.
+const KEYS: u8 = 16;
+
+type Model = Vec<HashMap<u8, u8>>;
+
+/// Reads the operations from `data` one byte at a time, stopping at the
+/// end of the input.
+struct Ops<'a> {
+    data: std::slice::Iter<'a, u8>,
+}
+
+impl Ops<'_> {
+    fn byte(&mut self) -> Option<u8> {
+        self.data.next().copied()
+    }
+
+    fn key(&mut self) -> Option<u8> {
+        Some(self.byte()? % KEYS)
+    }
+}
+
+fn resolve(model: &Model, key: u8) -> Option<&u8> {
+    model.iter().rev().find_map(|map| map.get(&key))
+}
+
+fn check(chain: &ChainMap<u8, u8>, model: &Model) {
+    assert_eq!(chain.child_len(), model.len(), "layer count");
+    for key in 0..KEYS {
+        assert_eq!(chain.get(&key), resolve(model, key), "resolving {}", key);
+        assert_eq!(
+            chain.get_last_index(&key),
+            model.iter().rposition(|map| map.contains_key(&key)),
+            "layer of {}",
+            key
+        );
+    }
+}
+
+/// Applies insert, get, remove, new_child, remove_child and split_off
+/// operations decoded from `data` to a [`ChainMap`], checking it against
+/// the model after each one.
+pub fn chain_map_ops(data: &[u8]) {
+    let mut ops = Ops { data: data.iter() };
+    let mut chain: ChainMap<_, _> = ChainMap::default();
+    let mut model: Model = vec![HashMap::new()];
+    while let Some(op) = ops.byte() {
+        match op % 6 {
+            0 => {
+                let (Some(key), Some(value)) = (ops.key(), ops.byte()) else {
+                    break;
+                };
+                let expected = model.last_mut().unwrap().insert(key, value);
+                assert_eq!(chain.insert(key, value), expected, "insert {}", key);
+            }
+            1 => {
+                let Some(key) = ops.key() else { break };
+                assert_eq!(chain.get(&key), resolve(&model, key), "get {}", key);
+            }
+            2 => {
+                let Some(key) = ops.key() else { break };
+                let expected = model.last_mut().unwrap().remove(&key);
+                assert_eq!(chain.remove(&key), expected, "remove {}", key);
+            }
+            3 => {
+                chain.new_child();
+                model.push(HashMap::new());
+            }
+            4 => {
+                let expected = if model.len() == 1 {
+                    std::mem::take(&mut model[0])
+                } else {
+                    model.pop().unwrap()
+                };
+                let removed = chain.remove_child().expect("a chain always has a layer");
+                assert_eq!(removed.into_iter().collect::<HashMap<_, _>>(), expected);
+            }
+            _ => {
+                // both halves must keep at least one layer
+                let Some(byte) = ops.byte() else { break };
+                if model.len() < 2 {
+                    continue;
+                }
+                let idx = 1 + usize::from(byte) % (model.len() - 1);
+                let split = chain.split_off(idx);
+                let expected = model.split_off(idx);
+                check(&split, &expected);
+            }
+        }
+        check(&chain, &model);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn interprets_ops() {
+        chain_map_ops(&[]);
+        // insert, new_child, insert (shadowing), get, split_off, remove_child
+        chain_map_ops(&[0, 1, 10, 3, 0, 1, 20, 1, 1, 5, 0, 4, 4]);
+        let mut seed = 0x2545_f491_u32;
+        let data: Vec<u8> = (0..4096)
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                seed as u8
+            })
+            .collect();
+        chain_map_ops(&data);
+    }
+}
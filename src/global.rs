@@ -0,0 +1,166 @@
+use crate::ChainMap;
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hash},
+    sync::{OnceLock, PoisonError, RwLock},
+};
+
+/// A process-wide chain that can live in a `static`, created empty on
+/// first use.
+///
+/// ```
+/// # use hash_chain::GlobalChainMap;
+/// static CONFIG: GlobalChainMap<String, String> = GlobalChainMap::new();
+///
+/// CONFIG.insert("color".to_string(), "auto".to_string());
+/// {
+///     let _scope = CONFIG.push_scope_guard();
+///     CONFIG.insert("color".to_string(), "never".to_string());
+///     assert_eq!(CONFIG.get_clone("color").as_deref(), Some("never"));
+/// }
+/// assert_eq!(CONFIG.get_clone("color").as_deref(), Some("auto"));
+/// ```
+pub struct GlobalChainMap<K, V, S = RandomState> {
+    cell: OnceLock<RwLock<ChainMap<K, V, S>>>,
+}
+
+/// Removes the scope pushed by [`GlobalChainMap::push_scope_guard`], along
+/// with any scope pushed after it, when dropped.
+#[must_use]
+pub struct ScopeGuard<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    chain: &'a GlobalChainMap<K, V, S>,
+    idx: usize,
+}
+
+impl<K, V, S> GlobalChainMap<K, V, S> {
+    pub const fn new() -> Self {
+        Self {
+            cell: OnceLock::new(),
+        }
+    }
+}
+
+impl<K, V, S> GlobalChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    fn lock(&self) -> &RwLock<ChainMap<K, V, S>> {
+        self.cell.get_or_init(|| RwLock::new(ChainMap::default()))
+    }
+
+    /// Returns a copy of the value corresponding to the key.
+    pub fn get_clone<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.with_read(|view| view.get(key).cloned())
+    }
+
+    /// Calls `f` with a read lock held, so every lookup it makes observes
+    /// the same state.
+    pub fn with_read<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&ChainMap<K, V, S>) -> R,
+    {
+        f(&self.lock().read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Calls `f` with a write lock held. Policies, validators and the like
+    /// set on the chain stay in place for later writes.
+    pub fn with_write<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut ChainMap<K, V, S>) -> R,
+    {
+        f(&mut self.lock().write().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Inserts a key-value pair into the current scope.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        self.with_write(|chain| chain.insert(key, value))
+    }
+
+    /// Pushes a new scope that is removed again when the returned guard is
+    /// dropped.
+    pub fn push_scope_guard(&self) -> ScopeGuard<'_, K, V, S> {
+        let idx = self.with_write(|chain| {
+            chain.new_child();
+            chain.child_len() - 1
+        });
+        ScopeGuard { chain: self, idx }
+    }
+}
+
+impl<K, V, S> Default for GlobalChainMap<K, V, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, S> Drop for ScopeGuard<'_, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    fn drop(&mut self) {
+        self.chain.with_write(|chain| {
+            let idx = self.idx.max(1);
+            if chain.child_len() > idx {
+                chain.split_off(idx);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static GLOBAL: GlobalChainMap<&str, i32> = GlobalChainMap::new();
+
+    #[test]
+    fn scoped_global() {
+        GLOBAL.insert("x", 0);
+        let guard = GLOBAL.push_scope_guard();
+        GLOBAL.insert("x", 1);
+        assert_eq!(GLOBAL.get_clone("x"), Some(1));
+        GLOBAL.with_write(|chain| chain.new_child());
+        drop(guard);
+        GLOBAL.with_read(|view| {
+            assert_eq!(view.child_len(), 1);
+            assert_eq!(view.get("x"), Some(&0));
+        });
+    }
+
+    #[test]
+    fn write_panic_keeps_layers() {
+        let global = GlobalChainMap::<&str, i32>::new();
+        global.insert("x", 0);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            global.with_write(|chain| {
+                chain.insert("y", 1);
+                panic!("oops")
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(global.get_clone("x"), Some(0));
+        assert_eq!(global.get_clone("y"), Some(1));
+    }
+
+    #[test]
+    fn write_keeps_policies() {
+        let global = GlobalChainMap::<&str, i32>::new();
+        global.with_write(|chain| {
+            chain.set_validator(|_, v| if *v < 0 { Err("negative") } else { Ok(()) })
+        });
+        global.with_write(|chain| assert!(chain.insert_checked("x", -1).is_err()));
+        assert_eq!(global.get_clone("x"), None);
+    }
+}
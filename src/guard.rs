@@ -0,0 +1,149 @@
+use crate::ChainMap;
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hash},
+    ops::{Deref, DerefMut},
+};
+
+/// A child scope pushed by [`ChainMap::push_scope_guard`], removed again
+/// along with any scope pushed after it when the guard is dropped, whether
+/// by falling off the end of a block, an early `return` or `?`, or a
+/// panic.
+///
+/// Dereferences to the chain.
+#[must_use]
+pub struct ChildGuard<'a, K, V, S = RandomState>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    chain: &'a mut ChainMap<K, V, S>,
+    depth: usize,
+}
+
+impl<K, V, S> ChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    /// Pushes a child scope that is removed when the returned guard is
+    /// dropped, see also [`scoped!`](crate::scoped).
+    pub fn push_scope_guard(&mut self) -> ChildGuard<'_, K, V, S> {
+        let depth = self.child_len();
+        self.new_child();
+        ChildGuard { chain: self, depth }
+    }
+}
+
+impl<K, V, S> Deref for ChildGuard<'_, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    type Target = ChainMap<K, V, S>;
+
+    fn deref(&self) -> &Self::Target {
+        self.chain
+    }
+}
+
+impl<K, V, S> DerefMut for ChildGuard<'_, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.chain
+    }
+}
+
+impl<K, V, S> Drop for ChildGuard<'_, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    fn drop(&mut self) {
+        while self.chain.child_len() > self.depth.max(1) {
+            self.chain.remove_child();
+        }
+    }
+}
+
+/// Runs a block in a new child scope of a chain, removing the scope once
+/// the block is left by any means, including `return`, `?` and panics.
+///
+/// Inside the block the chain's name refers to a [`ChildGuard`], which
+/// dereferences to the chain.
+///
+/// ```
+/// # use hash_chain::{scoped, ChainMap};
+/// fn declare(chain: &mut ChainMap<&'static str, i32>, fail: bool) -> Result<(), ()> {
+///     scoped!(chain => {
+///         chain.insert("x", 1);
+///         if fail {
+///             return Err(());
+///         }
+///         assert_eq!(chain.get("x"), Some(&1));
+///     });
+///     Ok(())
+/// }
+///
+/// let mut chain: ChainMap<_, _> = ChainMap::default();
+/// assert!(declare(&mut chain, true).is_err());
+/// declare(&mut chain, false).unwrap();
+/// assert_eq!(chain.child_len(), 1);
+/// assert_eq!(chain.get("x"), None);
+/// ```
+#[macro_export]
+macro_rules! scoped {
+    ($chain:ident => $body:block) => {{
+        #[allow(unused_mut)]
+        let mut $chain = $chain.push_scope_guard();
+        $body
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn depth(chain: &mut ChainMap<usize, usize>, n: usize) -> Option<usize> {
+        scoped!(chain => {
+            chain.insert(n, n);
+            if n == 3 {
+                return chain.get(&0).copied();
+            }
+            depth(&mut chain, n + 1)
+        })
+    }
+
+    #[test]
+    fn balanced_scopes() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        assert_eq!(depth(&mut chain_map, 0), Some(0));
+        assert_eq!(chain_map.child_len(), 1);
+        assert!(chain_map.is_empty());
+        {
+            let mut guard = chain_map.push_scope_guard();
+            guard.insert(1, 1);
+            guard.new_child();
+            assert_eq!(guard.child_len(), 3);
+        }
+        assert_eq!(chain_map.child_len(), 1);
+    }
+
+    #[test]
+    fn removed_on_panic() {
+        let mut chain_map: ChainMap<usize, usize> = ChainMap::default();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let chain_map = &mut chain_map;
+            scoped!(chain_map => {
+                chain_map.insert(0, 0);
+                panic!("oops");
+            })
+        }));
+        assert!(result.is_err());
+        assert_eq!(chain_map.child_len(), 1);
+        assert_eq!(chain_map.get(&0), None);
+    }
+}
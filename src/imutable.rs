@@ -1,15 +1,26 @@
 use std::{
     borrow::Borrow,
-    collections::{hash_map::RandomState},
+    collections::{hash_map::RandomState, HashSet},
     hash::{BuildHasher, Hash},
-    mem::take,
     ops::Index,
 };
-use im_rc::{Vector, HashMap};
-
+use crate::LookupBounds;
+#[cfg(not(feature = "imutable"))]
+use crate::PersistentMap as HashMap;
+#[cfg(feature = "imutable")]
+use im_rc::HashMap;
+
+/// The map type of a [`LockedChainMap`] layer
+pub(crate) type Layer<K, V, S> = HashMap<K, V, S>;
+
+/// A chain whose layers are persistent maps, so cloning the chain only
+/// copies the layer stack and the clones share every layer's contents.
+///
+/// The layers are [`PersistentMap`](crate::PersistentMap)s, or `im_rc`
+/// hash maps with the `imutable` feature.
 #[derive(Clone)]
 pub struct LockedChainMap<K, V, S = RandomState> {
-    pub(crate) maps: Vector<HashMap<K, V, S>>,
+    pub(crate) maps: Vec<HashMap<K, V, S>>,
 }
 
 impl<K, V, S: BuildHasher> LockedChainMap<K, V, S>
@@ -19,15 +30,29 @@ where
     S: BuildHasher + Clone,
 {
     pub fn new(map: HashMap<K, V, S>) -> Self {
-        Self { maps: Vector::unit(map) }
+        Self { maps: vec![map] }
+    }
+
+    /// Creates a chain with a single empty layer using `hasher`.
+    pub fn with_hasher(hasher: S) -> Self {
+        Self::new(HashMap::with_hasher(hasher))
     }
     /// Inserts a key-value pair into the map.
     /// If the map did not have this key present, None is returned.
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
-        let map = self.maps.get_mut(self.maps.len() - 1)?;
+        let map = self.maps.last_mut()?;
         map.insert(key, value)
     }
 
+    /// Returns a new chain with the pair inserted into the current scope,
+    /// leaving this one untouched. The two chains share every layer but
+    /// the path to the new entry.
+    pub fn with_insert(&self, key: K, value: V) -> Self {
+        let mut ret = self.clone();
+        ret.insert(key, value);
+        ret
+    }
+
     pub fn insert_at(&mut self, idx: usize, key: K, value: V) -> Result<Option<V>, crate::Error> {
         if let Some(map) = self.maps.get_mut(idx) {
             Ok(map.insert(key, value))
@@ -41,10 +66,10 @@ where
     /// The supplied key may be any borrowed form of the map's key type, but
     /// `Hash` and `Eq` on the borrowed form *must* match those for
     /// the key type.
-    pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + ?Sized,
     {
         for map in self.maps.iter().rev() {
             if let Some(v) = map.get(key) {
@@ -58,10 +83,10 @@ where
     /// The supplied key may be any borrowed form of the map's key type, but
     /// `Hash` and `Eq` on the borrowed form *must* match those for
     /// the key type.
-    pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + ?Sized,
     {
         for map in self.maps.iter_mut().rev() {
             if let Some(v) = map.get_mut(key) {
@@ -71,30 +96,33 @@ where
         None
     }
 
-    pub fn get_before<Q: ?Sized>(&self, idx: usize, key: &Q) -> Option<&V>
+    /// Returns the value corresponding to the key, searching only the
+    /// layers selected by `bounds`, innermost first.
+    pub fn get_in<Q>(&self, bounds: LookupBounds, key: &Q) -> Option<&V>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + ?Sized,
     {
-        for i in (0..idx).rev() {
-            if let Some(map) = self.maps.get(i) {
-                if let Some(v) = map.get(key) {
-                    return Some(v)
-                }
+        for i in bounds.range(self.maps.len()).rev() {
+            if let Some(v) = self.maps[i].get(key) {
+                return Some(v)
             }
         }
         None
     }
 
-    pub fn get_before_mut<Q: ?Sized>(&mut self, idx: usize, key: &Q) -> Option<&mut V>
+    /// Returns a mutable reference to the value corresponding to the key,
+    /// searching only the layers selected by `bounds`, innermost first.
+    pub fn get_in_mut<Q>(&mut self, bounds: LookupBounds, key: &Q) -> Option<&mut V>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + ?Sized,
     {
+        let range = bounds.range(self.maps.len());
         for (i, map) in self.maps.iter_mut().enumerate().rev() {
-            if i > idx {
+            if !range.contains(&i) {
                 continue;
-            } 
+            }
             if let Some(v) = map.get_mut(key) {
                 return Some(v)
             }
@@ -102,22 +130,134 @@ where
         None
     }
 
+    /// Searches only the layers strictly below `idx`, see
+    /// [`LookupBounds::Before`].
+    pub fn get_before<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_in(LookupBounds::Before(idx), key)
+    }
+
+    pub fn get_before_mut<Q>(&mut self, idx: usize, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_in_mut(LookupBounds::Before(idx), key)
+    }
+
+    /// Searches only the layers at or above `idx`, see
+    /// [`LookupBounds::After`].
+    pub fn get_after<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_in(LookupBounds::After(idx), key)
+    }
+
+    pub fn get_after_mut<Q>(&mut self, idx: usize, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_in_mut(LookupBounds::After(idx), key)
+    }
+
+    /// A read-only view of the layers strictly below `idx`, matching
+    /// [`get_before`](Self::get_before).
+    pub fn view_outer(&self, idx: usize) -> LockedView<'_, K, V, S> {
+        self.view(LookupBounds::Before(idx))
+    }
+
+    /// A read-only view of the layers at or above `idx`, matching
+    /// [`get_after`](Self::get_after).
+    pub fn view_inner(&self, idx: usize) -> LockedView<'_, K, V, S> {
+        self.view(LookupBounds::After(idx))
+    }
+
+    /// A read-only view of the layers selected by `bounds`.
+    ///
+    /// Layer indices passed to the view are relative to its outermost
+    /// layer.
+    pub fn view(&self, bounds: LookupBounds) -> LockedView<'_, K, V, S> {
+        LockedView {
+            maps: &self.maps[bounds.range(self.maps.len())],
+        }
+    }
+
     pub fn new_child_with(&mut self, map: HashMap<K, V, S>) {
-        self.maps.push_back(map);
+        self.maps.push(map);
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
     }
 
-    pub fn last_has<Q: ?Sized>(&self, key: &Q) -> bool
+    /// Returns the value bound to the key in the layer at `idx`, without
+    /// searching any other layer.
+    pub fn get_at<Q>(&self, idx: usize, key: &Q) -> Option<&V>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.get(idx)?.get(key)
+    }
+
+    /// Removes the key from the current scope, returning its value. A
+    /// binding in an outer scope becomes visible again.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.last_mut()?.remove(key)
+    }
+
+    /// Removes the key from the layer at `idx`, returning its value.
+    pub fn remove_at<Q>(&mut self, idx: usize, key: &Q) -> Result<Option<V>, crate::Error>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.maps.get_mut(idx) {
+            Some(map) => Ok(map.remove(key)),
+            None => Err(crate::Error::IndexOutOfRange),
+        }
+    }
+
+    /// The number of visible keys, counting each key once no matter how
+    /// many scopes bind it.
+    pub fn len(&self) -> usize {
+        self.maps
+            .iter()
+            .flat_map(|map| map.keys())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.maps.iter().all(|map| map.is_empty())
+    }
+
+    pub fn last_has<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
     {
         self.has_at(self.maps.len() - 1, key)
     }
 
-    pub fn has_at<Q: ?Sized>(&self, idx: usize, key: &Q) -> bool
+    pub fn has_at<Q>(&self, idx: usize, key: &Q) -> bool
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + ?Sized,
     {
         if let Some(map) = self.maps.get(idx) {
             map.contains_key(key)
@@ -130,10 +270,10 @@ where
         self.maps.len()
     }
 
-    pub fn get_last_index<Q: ?Sized>(&self, key: &Q) -> Option<usize>
+    pub fn get_last_index<Q>(&self, key: &Q) -> Option<usize>
     where
         K: Borrow<Q>,
-        Q: Hash + Eq,
+        Q: Hash + Eq + ?Sized,
     {
         for (i, map) in self.maps.iter().enumerate().rev() {
             if map.contains_key(key) {
@@ -151,39 +291,162 @@ where
     }
 
     pub fn append(&mut self, other: Self) {
-        self.maps.append(other.maps);
+        self.maps.extend(other.maps);
     }
 }
 
 impl<K: Hash + Eq + Clone, V: Clone, S: BuildHasher + Default + Clone> LockedChainMap<K, V, S> {
+    /// Pushes an empty child layer, hashed like the current scope.
     pub fn new_child(&mut self) {
-        self.maps.push_back(HashMap::default());
+        let map = self.empty_layer();
+        self.maps.push(map);
     }
 
     pub fn remove_child(&mut self) -> Option<HashMap<K, V, S>> {
         if self.maps.len() == 1 {
-            let ret = take(&mut self.maps[0]);
+            let empty = self.empty_layer();
+            let ret = std::mem::replace(&mut self.maps[0], empty);
             Some(ret)
         } else {
-            self.maps.pop_back()
+            self.maps.pop()
+        }
+    }
+
+    /// Returns a new chain with an empty child layer, leaving this one
+    /// untouched.
+    pub fn with_child(&self) -> Self {
+        let mut ret = self.clone();
+        ret.new_child();
+        ret
+    }
+
+    /// Returns a new chain without the current scope, leaving this one
+    /// untouched. As with [`remove_child`](Self::remove_child), the last
+    /// scope is emptied rather than removed.
+    pub fn without_child(&self) -> Self {
+        let mut ret = self.clone();
+        ret.remove_child();
+        ret
+    }
+
+    fn empty_layer(&self) -> HashMap<K, V, S> {
+        match self.maps.last() {
+            Some(map) => HashMap::with_hasher(map.hasher().clone()),
+            None => HashMap::default(),
+        }
+    }
+}
+
+/// A contiguous run of a [`LockedChainMap`]'s layers, see
+/// [`LockedChainMap::view`].
+pub struct LockedView<'a, K, V, S = RandomState> {
+    maps: &'a [HashMap<K, V, S>],
+}
+
+impl<K, V, S> LockedView<'_, K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.iter().rev().find_map(|map| map.get(key))
+    }
+
+    pub fn get_at<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.get(idx)?.get(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn has_at<Q>(&self, idx: usize, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_at(idx, key).is_some()
+    }
+
+    pub fn last_has<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.last().is_some_and(|map| map.contains_key(key))
+    }
+
+    pub fn get_last_index<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.iter().rposition(|map| map.contains_key(key))
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.maps.len()
+    }
+
+    /// The number of visible keys, counting each key once no matter how
+    /// many layers of the view bind it.
+    pub fn len(&self) -> usize {
+        self.maps
+            .iter()
+            .flat_map(|map| map.keys())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.maps.iter().all(|map| map.is_empty())
+    }
+
+    /// Copies the view into its own chain. The layers are shared with the
+    /// original rather than deep copied.
+    pub fn to_chain_map(&self) -> LockedChainMap<K, V, S> {
+        LockedChainMap {
+            maps: self.maps.to_vec(),
         }
     }
 }
 
-impl<K, V> Default for LockedChainMap<K, V>
+impl<K, V, S> Clone for LockedView<'_, K, V, S> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<K, V, S> Copy for LockedView<'_, K, V, S> {}
+
+impl<K, V, S> Default for LockedChainMap<K, V, S>
 where
     K: Hash + Eq + Clone,
-    V: Clone
+    V: Clone,
+    S: BuildHasher + Default,
 {
     fn default() -> Self {
-        let maps = Vector::unit(HashMap::new());
+        let maps = vec![HashMap::default()];
         Self {
             maps,
         }
     }
 }
 
-impl<K, Q: ?Sized, V: Clone, S> Index<&Q> for LockedChainMap<K, V, S>
+impl<K, Q, V: Clone, S> Index<&Q> for LockedChainMap<K, V, S>
 where
     K: Eq + Hash + Borrow<Q> + Clone,
     Q: Eq + Hash + Clone,
@@ -245,7 +508,7 @@ mod test {
         test_map.insert("test", 1);
         let chain_map = LockedChainMap::new(test_map);
 
-        assert!(chain_map.maps.len() > 0);
+        assert!(!chain_map.maps.is_empty());
         assert_eq!(chain_map.maps[0].get("test"), Some(&1));
     }
 
@@ -253,13 +516,13 @@ mod test {
     fn initialization_default() {
         let chain_map: LockedChainMap<(), ()> = LockedChainMap::default();
 
-        assert!(chain_map.maps.len() > 0);
+        assert!(!chain_map.maps.is_empty());
         assert!(chain_map.maps[0].is_empty());
     }
 
     #[test]
     fn insert() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         assert!(chain_map.insert("test", 1).is_none());
 
         assert_eq!(chain_map.maps[0].get("test"), Some(&1));
@@ -267,7 +530,7 @@ mod test {
 
     #[test]
     fn insert_at() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("banana", "milk");
         chain_map.new_child();
 
@@ -279,7 +542,7 @@ mod test {
     #[test]
     #[should_panic = "IndexOutOfRange"]
     fn insert_at_out_of_bounds() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("banana", "milk");
         chain_map.new_child();
 
@@ -288,7 +551,7 @@ mod test {
 
     #[test]
     fn get() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("test", 1);
 
         assert_eq!(chain_map.get(&"test"), Some(&1));
@@ -302,7 +565,7 @@ mod test {
 
     #[test]
     fn get_mut() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("test", 1);
 
         let test_value = chain_map.get_mut(&"test");
@@ -314,7 +577,7 @@ mod test {
 
     #[test]
     fn get_mut_outer() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("outer", 1);
         chain_map.new_child();
         chain_map.insert("inner", 2);
@@ -327,7 +590,7 @@ mod test {
 
     #[test]
     fn index() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("test", 1);
 
         assert_eq!(chain_map[&"test"], 1);
@@ -335,7 +598,7 @@ mod test {
 
     #[test]
     fn new_child() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("test", 1);
         chain_map.new_child();
         assert!(chain_map.maps.len() > 1);
@@ -343,7 +606,7 @@ mod test {
 
     #[test]
     fn scopes() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("x", 0);
         chain_map.insert("y", 2);
         chain_map.new_child();
@@ -354,7 +617,7 @@ mod test {
 
     #[test]
     fn remove_child() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("x", 0);
         chain_map.insert("y", 2);
         chain_map.new_child();
@@ -366,7 +629,7 @@ mod test {
 
     #[test]
     fn remove_child_length_1() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("x", 0);
         let _ = chain_map.remove_child();
         assert_eq!(chain_map.get("x"), None);
@@ -375,7 +638,7 @@ mod test {
 
     #[test]
     fn has_at_exists() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("x", 0);
 
         assert!(chain_map.has_at(0, &"x"));
@@ -390,7 +653,7 @@ mod test {
 
     #[test]
     fn last_has_true() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("x", 0);
         chain_map.new_child();
         chain_map.insert("y", 1);
@@ -400,7 +663,7 @@ mod test {
 
     #[test]
     fn last_has_false() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("x", 0);
         chain_map.new_child();
         chain_map.insert("y", 1);
@@ -421,7 +684,7 @@ mod test {
 
     #[test]
     fn get_before_exists() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("test", 1);
         chain_map.new_child();
         chain_map.insert("test", 2);
@@ -431,7 +694,7 @@ mod test {
 
     #[test]
     fn get_before_mut_exists() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("test", 1);
         chain_map.new_child();
         chain_map.insert("test", 2);
@@ -447,7 +710,7 @@ mod test {
 
     #[test]
     fn get_last_index_exists() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("test1", 1);
         chain_map.new_child();
         chain_map.insert("test2", 2);
@@ -458,11 +721,105 @@ mod test {
 
     #[test]
     fn get_last_index_doesnt_exist() {
-        let mut chain_map = LockedChainMap::default();
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
         chain_map.insert("test1", 1);
         chain_map.new_child();
         chain_map.insert("test2", 2);
 
         assert_eq!(chain_map.get_last_index("shmee"), None);
     }
+
+    #[test]
+    fn get_after() {
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
+        chain_map.insert("test", 1);
+        chain_map.insert("outer", 1);
+        chain_map.new_child();
+        chain_map.insert("test", 2);
+
+        assert_eq!(chain_map.get_after(1, &"test"), Some(&2));
+        assert_eq!(chain_map.get_after(1, &"outer"), None);
+        assert_eq!(chain_map.get_after(0, &"outer"), Some(&1));
+        assert_eq!(chain_map.get_after(5, &"test"), None);
+    }
+
+    #[test]
+    fn get_before_mut_excludes_idx() {
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
+        chain_map.insert("test", 1);
+        chain_map.new_child();
+        chain_map.insert("test", 2);
+
+        assert_eq!(chain_map.get_before_mut(0, &"test"), None);
+        assert_eq!(chain_map.get_before_mut(1, &"test"), Some(&mut 1));
+        assert_eq!(chain_map.get_in_mut(LookupBounds::All, &"test"), Some(&mut 2));
+    }
+
+    #[test]
+    fn remove_and_len() {
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.insert("y", 0);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        assert_eq!(chain_map.len(), 2);
+        assert_eq!(chain_map.get_at(0, "x"), Some(&0));
+        assert_eq!(chain_map.get_at(2, "x"), None);
+        assert_eq!(chain_map.remove("x"), Some(1));
+        assert_eq!(chain_map.get("x"), Some(&0));
+        assert_eq!(chain_map.remove_at(0, "y").unwrap(), Some(0));
+        assert!(chain_map.remove_at(2, "y").is_err());
+        assert!(!chain_map.contains_key("y"));
+        assert_eq!(chain_map.remove_at(0, "x").unwrap(), Some(0));
+        assert!(chain_map.is_empty());
+    }
+
+    #[test]
+    fn views() {
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        chain_map.insert("y", 1);
+        chain_map.new_child();
+        chain_map.insert("x", 2);
+        let outer = chain_map.view_outer(2);
+        assert_eq!(outer.child_len(), 2);
+        assert_eq!(outer.get("x"), Some(&1));
+        assert_eq!(outer.get_last_index("x"), Some(1));
+        let inner = chain_map.view_inner(1);
+        assert_eq!(inner.get_at(0, "y"), Some(&1));
+        assert_eq!(inner.len(), 2);
+        assert!(chain_map.view_inner(2).last_has("x"));
+        assert!(!chain_map.view_inner(3).contains_key("x"));
+        assert_eq!(outer.to_chain_map().get("x"), chain_map.get_before(2, "x"));
+    }
+
+    #[test]
+    fn derived_versions() {
+        let base = LockedChainMap::<_, _>::default().with_insert("x", 0);
+        let child = base.with_child().with_insert("x", 1);
+        assert_eq!(base.get("x"), Some(&0));
+        assert_eq!(base.child_len(), 1);
+        assert_eq!(child.get("x"), Some(&1));
+        let popped = child.without_child();
+        assert_eq!(popped.get("x"), Some(&0));
+        assert_eq!(child.child_len(), 2);
+        assert!(base.without_child().is_empty());
+    }
+
+    #[test]
+    fn with_hasher() {
+        use hashers::oz::DJB2Hasher;
+        use std::hash::BuildHasherDefault;
+        let mut chain_map =
+            LockedChainMap::with_hasher(BuildHasherDefault::<DJB2Hasher>::default());
+        chain_map.insert("x", 0);
+        chain_map.new_child();
+        assert_eq!(chain_map.get("x"), Some(&0));
+
+        let chain_map: LockedChainMap<&str, i32, BuildHasherDefault<DJB2Hasher>> =
+            Default::default();
+        assert_eq!(chain_map.child_len(), 1);
+    }
 }
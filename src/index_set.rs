@@ -0,0 +1,138 @@
+use indexmap::IndexSet;
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashSet},
+    hash::{BuildHasher, Hash},
+    mem::take,
+};
+
+/// A [`ChainSet`](crate::ChainSet) whose layers remember the order values
+/// were inserted in, so iterating over it is deterministic.
+pub struct ChainIndexSet<T, S = RandomState> {
+    pub(crate) sets: Vec<IndexSet<T, S>>,
+}
+
+impl<T, S> ChainIndexSet<T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn new(set: IndexSet<T, S>) -> Self {
+        Self { sets: vec![set] }
+    }
+
+    /// Adds a value to the current scope, returning `false` if the current
+    /// scope already contained it.
+    pub fn insert(&mut self, value: T) -> bool {
+        match self.sets.last_mut() {
+            Some(set) => set.insert(value),
+            None => false,
+        }
+    }
+
+    pub fn get<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.sets.iter().rev().find_map(|set| set.get(value))
+    }
+
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(value).is_some()
+    }
+
+    pub fn new_child_with(&mut self, set: IndexSet<T, S>) {
+        self.sets.push(set);
+    }
+
+    /// Iterates over every value in the chain once, starting with the
+    /// outermost scope and following insertion order within each scope.
+    /// A value bound in several scopes is yielded at its first position.
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        let mut seen = HashSet::new();
+        self.sets
+            .iter()
+            .flat_map(IndexSet::iter)
+            .filter(move |value| seen.insert(*value))
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.sets.len()
+    }
+}
+
+impl<T, S> ChainIndexSet<T, S>
+where
+    T: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    pub fn new_child(&mut self) {
+        self.sets.push(IndexSet::default());
+    }
+
+    pub fn remove_child(&mut self) -> Option<IndexSet<T, S>> {
+        if self.sets.len() == 1 {
+            let ret = take(&mut self.sets[0]);
+            Some(ret)
+        } else {
+            self.sets.pop()
+        }
+    }
+}
+
+impl<T: Hash + Eq> Default for ChainIndexSet<T> {
+    fn default() -> Self {
+        Self {
+            sets: vec![IndexSet::new()],
+        }
+    }
+}
+
+impl<T, S> core::fmt::Debug for ChainIndexSet<T, S>
+where
+    T: Eq + Hash + core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ChainIndexSet")
+            .field("sets", &self.sets)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn insertion_ordered_iter() {
+        let mut chain_set = ChainIndexSet::default();
+        chain_set.insert("c");
+        chain_set.insert("a");
+        chain_set.new_child();
+        chain_set.insert("b");
+        chain_set.insert("c");
+        chain_set.insert("d");
+        assert_eq!(
+            chain_set.iter().copied().collect::<Vec<_>>(),
+            vec!["c", "a", "b", "d"]
+        );
+    }
+
+    #[test]
+    fn scopes() {
+        let mut chain_set = ChainIndexSet::default();
+        chain_set.insert("x");
+        chain_set.new_child();
+        assert!(chain_set.insert("y"));
+        assert!(chain_set.contains("x"));
+        let removed = chain_set.remove_child().unwrap();
+        assert!(removed.contains("y"));
+        assert!(!chain_set.contains("y"));
+        assert_eq!(chain_set.child_len(), 1);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::ChainMap;
+use std::{
+    collections::{hash_map::Iter, HashMap},
+    hash::{BuildHasher, Hash},
+    iter::FusedIterator,
+    ops::Range,
+};
+
+/// An iterator over the layers of a [`ChainMap`], outermost first, see
+/// [`ChainMap::layers`]. Lazy layers are built as they are reached.
+pub struct Layers<'a, K, V, S, L = HashMap<K, V, S>> {
+    chain: &'a ChainMap<K, V, S, L>,
+    range: Range<usize>,
+}
+
+/// An iterator over every binding of a [`ChainMap`], including shadowed
+/// ones, see [`ChainMap::iter_outer_first`] and
+/// [`ChainMap::iter_inner_first`].
+pub struct IterAll<'a, K, V, S> {
+    layers: Layers<'a, K, V, S>,
+    inner_first: bool,
+    current: Option<Iter<'a, K, V>>,
+    remaining: usize,
+}
+
+impl<'a, K, V, S, L> Layers<'a, K, V, S, L> {
+    pub(crate) fn new(chain: &'a ChainMap<K, V, S, L>) -> Self {
+        Self {
+            range: 0..chain.maps.len(),
+            chain,
+        }
+    }
+}
+
+impl<'a, K, V, S> IterAll<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub(crate) fn new(chain: &'a ChainMap<K, V, S>, inner_first: bool) -> Self {
+        Self {
+            layers: Layers::new(chain),
+            inner_first,
+            current: None,
+            remaining: chain.total_len(),
+        }
+    }
+}
+
+impl<'a, K, V, S, L> Iterator for Layers<'a, K, V, S, L> {
+    type Item = &'a L;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.range.next().map(|idx| self.chain.layer(idx))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.range.size_hint()
+    }
+}
+
+impl<K, V, S, L> DoubleEndedIterator for Layers<'_, K, V, S, L> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.range.next_back().map(|idx| self.chain.layer(idx))
+    }
+}
+
+impl<K, V, S, L> ExactSizeIterator for Layers<'_, K, V, S, L> {}
+
+impl<K, V, S, L> FusedIterator for Layers<'_, K, V, S, L> {}
+
+impl<K, V, S, L> Clone for Layers<'_, K, V, S, L> {
+    fn clone(&self) -> Self {
+        Self {
+            chain: self.chain,
+            range: self.range.clone(),
+        }
+    }
+}
+
+impl<'a, K, V, S> Iterator for IterAll<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.current.as_mut().and_then(Iterator::next) {
+                self.remaining -= 1;
+                return Some(item);
+            }
+            let layer = if self.inner_first {
+                self.layers.next_back()
+            } else {
+                self.layers.next()
+            };
+            self.current = Some(layer?.iter());
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V, S> ExactSizeIterator for IterAll<'_, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+}
+
+impl<K, V, S> FusedIterator for IterAll<'_, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn iterator_traits() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        chain_map.insert("y", 1);
+        let mut layers = chain_map.layers();
+        assert_eq!(layers.len(), 2);
+        assert_eq!(layers.next_back().map(HashMap::len), Some(2));
+        assert_eq!(layers.len(), 1);
+        assert!(layers.next().is_some());
+        assert!(layers.next().is_none());
+        assert!(layers.next_back().is_none());
+        let mut all = chain_map.iter_inner_first();
+        assert_eq!(all.len(), 3);
+        all.next();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all.by_ref().count(), 2);
+        assert!(all.next().is_none());
+        assert_eq!(chain_map.iter_outer_first().next(), Some((&"x", &0)));
+    }
+}
@@ -0,0 +1,151 @@
+use crate::ChainMap;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::ops::{Deref, DerefMut};
+
+/// A chain of JSON objects, as produced by layered JSON or YAML
+/// configuration, with lookups by dot separated paths.
+///
+/// Dereferences to the underlying [`ChainMap`] for everything that is not
+/// path based.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct JsonChainMap {
+    chain: ChainMap<String, Value>,
+}
+
+impl JsonChainMap {
+    pub fn new(map: HashMap<String, Value>) -> Self {
+        Self {
+            chain: ChainMap::new(map),
+        }
+    }
+
+    /// Pushes the members of a JSON object as a new child layer. Any other
+    /// kind of value pushes an empty layer.
+    pub fn new_child_with_value(&mut self, value: Value) {
+        let map = match value {
+            Value::Object(obj) => obj.into_iter().collect(),
+            _ => HashMap::new(),
+        };
+        self.chain.new_child_with(map);
+    }
+
+    /// Resolves a dot separated path such as `"server.tls.cert"`.
+    ///
+    /// Each layer is searched in turn from the innermost outward, and the
+    /// first layer in which the full path exists wins. Layers are not
+    /// merged, so a layer that binds `server` without `tls` falls through to
+    /// the outer layers.
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        let mut segments = path.split('.');
+        let first = segments.next()?;
+        let rest: Vec<&str> = segments.collect();
+        self.chain
+            .layers()
+            .rev()
+            .filter_map(|map| map.get(first))
+            .find_map(|value| descend(value, &rest))
+    }
+
+    /// Resolves a dot separated path after deep merging the objects bound
+    /// to its first segment across every layer, inner layers overriding
+    /// outer ones member by member.
+    pub fn get_path_merged(&self, path: &str) -> Option<Value> {
+        let mut segments = path.split('.');
+        let first = segments.next()?;
+        let rest: Vec<&str> = segments.collect();
+        let mut merged: Option<Value> = None;
+        for value in self.chain.layers().filter_map(|map| map.get(first)) {
+            match &mut merged {
+                Some(target) => merge(target, value),
+                None => merged = Some(value.clone()),
+            }
+        }
+        descend(&merged?, &rest).cloned()
+    }
+
+    pub fn into_inner(self) -> ChainMap<String, Value> {
+        self.chain
+    }
+}
+
+fn descend<'a>(mut value: &'a Value, path: &[&str]) -> Option<&'a Value> {
+    for segment in path {
+        value = value.as_object()?.get(*segment)?;
+    }
+    Some(value)
+}
+
+fn merge(target: &mut Value, overlay: &Value) {
+    match (target, overlay) {
+        (Value::Object(target), Value::Object(overlay)) => merge_objects(target, overlay),
+        (target, overlay) => *target = overlay.clone(),
+    }
+}
+
+fn merge_objects(target: &mut Map<String, Value>, overlay: &Map<String, Value>) {
+    for (k, v) in overlay {
+        match target.get_mut(k) {
+            Some(existing) => merge(existing, v),
+            None => {
+                target.insert(k.clone(), v.clone());
+            }
+        }
+    }
+}
+
+impl From<ChainMap<String, Value>> for JsonChainMap {
+    fn from(chain: ChainMap<String, Value>) -> Self {
+        Self { chain }
+    }
+}
+
+impl Deref for JsonChainMap {
+    type Target = ChainMap<String, Value>;
+    fn deref(&self) -> &Self::Target {
+        &self.chain
+    }
+}
+
+impl DerefMut for JsonChainMap {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.chain
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    fn layered() -> JsonChainMap {
+        let mut chain_map = JsonChainMap::default();
+        chain_map.new_child_with_value(json!({
+            "server": { "port": 80, "tls": { "cert": "default.pem", "key": "default.key" } }
+        }));
+        chain_map.new_child_with_value(json!({
+            "server": { "port": 8080, "tls": { "cert": "prod.pem" } }
+        }));
+        chain_map
+    }
+
+    #[test]
+    fn get_path() {
+        let chain_map = layered();
+        assert_eq!(chain_map.get_path("server.port"), Some(&json!(8080)));
+        assert_eq!(chain_map.get_path("server.tls.cert"), Some(&json!("prod.pem")));
+        assert_eq!(chain_map.get_path("server.tls.key"), Some(&json!("default.key")));
+        assert_eq!(chain_map.get_path("server.tls.missing"), None);
+        assert_eq!(chain_map.get_path("server.port.nope"), None);
+    }
+
+    #[test]
+    fn get_path_merged() {
+        let chain_map = layered();
+        assert_eq!(
+            chain_map.get_path_merged("server.tls"),
+            Some(json!({ "cert": "prod.pem", "key": "default.key" }))
+        );
+        assert_eq!(chain_map.get_path("server.tls"), Some(&json!({ "cert": "prod.pem" })));
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{ChainMap, LookupBounds};
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    ops::Deref,
+};
+
+/// What a scope of a [`KindedChainMap`] was opened for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScopeKind {
+    Module,
+    Function,
+    Block,
+    Loop,
+    /// A kind defined by the user of the chain
+    Other(&'static str),
+}
+
+/// A chain that remembers the [`ScopeKind`] of each of its scopes, so
+/// bindings can target the nearest scope of a kind, the way `var` binds to
+/// the nearest function while `let` binds to the nearest block.
+///
+/// Dereferences to the underlying [`ChainMap`] for lookups that don't care
+/// about kinds.
+///
+/// ```
+/// # use hash_chain::{KindedChainMap, ScopeKind};
+/// let mut chain_map = KindedChainMap::new(ScopeKind::Module);
+/// chain_map.new_scope(ScopeKind::Function);
+/// chain_map.new_scope(ScopeKind::Block);
+/// chain_map.insert("x", "let");
+/// chain_map.insert_in_nearest(ScopeKind::Function, "y", "var").unwrap();
+/// assert_eq!(chain_map.nearest_scope_of(ScopeKind::Function), Some(1));
+/// assert_eq!(chain_map.get_at(1, "y"), Some(&"var"));
+/// ```
+pub struct KindedChainMap<K, V, S = RandomState> {
+    chain: ChainMap<K, V, S>,
+    kinds: Vec<ScopeKind>,
+}
+
+impl<K, V> KindedChainMap<K, V>
+where
+    K: Hash + Eq,
+{
+    /// Creates a chain whose outermost scope has the given kind.
+    pub fn new(kind: ScopeKind) -> Self {
+        Self::with_hasher(kind, RandomState::default())
+    }
+}
+
+impl<K, V, S> KindedChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn with_hasher(kind: ScopeKind, hash_builder: S) -> Self
+    where
+        S: Clone,
+    {
+        Self {
+            chain: ChainMap::new(HashMap::with_hasher(hash_builder)),
+            kinds: vec![kind],
+        }
+    }
+
+    pub fn kind_at(&self, idx: usize) -> Option<ScopeKind> {
+        self.kinds.get(idx).copied()
+    }
+
+    pub fn current_kind(&self) -> ScopeKind {
+        self.kinds[self.kinds.len() - 1]
+    }
+
+    /// The index of the innermost scope of the given kind.
+    pub fn nearest_scope_of(&self, kind: ScopeKind) -> Option<usize> {
+        self.kinds.iter().rposition(|k| *k == kind)
+    }
+
+    /// Inserts a key-value pair into the current scope.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.chain.insert(key, value)
+    }
+
+    /// Inserts a key-value pair into the innermost scope of the given
+    /// kind, failing with
+    /// [`Error::ScopeNotFound`](crate::Error::ScopeNotFound) if there is
+    /// none.
+    pub fn insert_in_nearest(
+        &mut self,
+        kind: ScopeKind,
+        key: K,
+        value: V,
+    ) -> Result<Option<V>, crate::Error> {
+        let idx = self
+            .nearest_scope_of(kind)
+            .ok_or(crate::Error::ScopeNotFound)?;
+        self.chain.insert_at(idx, key, value)
+    }
+
+    /// Looks the key up in the scopes from the current one out to the
+    /// nearest scope of the given kind, e.g. to find a redeclaration
+    /// within the same function.
+    pub fn get_within<Q>(&self, kind: ScopeKind, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.nearest_scope_of(kind)?;
+        self.chain.get_in(LookupBounds::After(idx), key)
+    }
+
+    /// Moves the chain out, dropping the scope kinds.
+    pub fn into_chain_map(self) -> ChainMap<K, V, S> {
+        self.chain
+    }
+}
+
+impl<K, V, S> KindedChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    /// Pushes a new scope of the given kind.
+    pub fn new_scope(&mut self, kind: ScopeKind) {
+        self.chain.new_child();
+        self.kinds.push(kind);
+    }
+
+    /// Removes the current scope along with its kind. As with
+    /// [`ChainMap::remove_child`], the last scope is cleared rather than
+    /// removed, and keeps its kind.
+    pub fn remove_scope(&mut self) -> Option<(ScopeKind, HashMap<K, V, S>)> {
+        let kind = if self.kinds.len() == 1 {
+            self.kinds[0]
+        } else {
+            self.kinds.pop()?
+        };
+        Some((kind, self.chain.remove_child()?))
+    }
+}
+
+impl<K, V, S> Deref for KindedChainMap<K, V, S> {
+    type Target = ChainMap<K, V, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.chain
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn var_and_let() {
+        let mut chain_map = KindedChainMap::new(ScopeKind::Module);
+        chain_map.new_scope(ScopeKind::Function);
+        chain_map.insert("a", 0);
+        chain_map.new_scope(ScopeKind::Loop);
+        chain_map.new_scope(ScopeKind::Block);
+        assert_eq!(chain_map.current_kind(), ScopeKind::Block);
+        chain_map
+            .insert_in_nearest(ScopeKind::Function, "b", 1)
+            .unwrap();
+        assert!(chain_map
+            .insert_in_nearest(ScopeKind::Other("class"), "c", 2)
+            .is_err());
+        assert_eq!(chain_map.get_within(ScopeKind::Loop, "b"), None);
+        assert_eq!(chain_map.get_within(ScopeKind::Function, "b"), Some(&1));
+        assert_eq!(chain_map.get("a"), Some(&0));
+        let (kind, _) = chain_map.remove_scope().unwrap();
+        assert_eq!(kind, ScopeKind::Block);
+        chain_map.remove_scope();
+        chain_map.remove_scope();
+        assert_eq!(chain_map.kind_at(0), Some(ScopeKind::Module));
+        assert_eq!(chain_map.remove_scope().unwrap().0, ScopeKind::Module);
+        assert_eq!(chain_map.child_len(), 1);
+    }
+}
@@ -0,0 +1,375 @@
+use std::{
+    borrow::Borrow,
+    collections::{btree_map, hash_map, BTreeMap, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+/// The part of a scope's storage that doesn't depend on how keys are
+/// looked up, see [`Layer`].
+pub trait LayerStorage<K, V> {
+    /// The iterator returned by [`iter`](Self::iter)
+    type Iter<'a>: Iterator<Item = (&'a K, &'a V)>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    /// Binds the key in this layer, returning the old value. Layers that
+    /// can't be written to return `Error::ReadOnlyLayer`.
+    fn insert(&mut self, key: K, value: V) -> Result<Option<V>, crate::Error>;
+    fn iter(&self) -> Self::Iter<'_>;
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The storage behind a single scope of a [`ChainMap`](crate::ChainMap),
+/// looked up by `&Q`, any borrowed form of `K` the layer supports.
+///
+/// This is implemented for the std maps and for boxed layers. A chain of
+/// `Box<dyn DynLayer<K, V, Q>>` can mix different kinds of storage, e.g. a
+/// read-only base layer under mutable hash layers.
+pub trait Layer<K, V, Q: ?Sized = K>: LayerStorage<K, V> {
+    fn get(&self, key: &Q) -> Option<&V>;
+    fn get_key_value(&self, key: &Q) -> Option<(&K, &V)>;
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V>;
+    fn remove(&mut self, key: &Q) -> Option<V>;
+
+    fn contains_key(&self, key: &Q) -> bool {
+        self.get_key_value(key).is_some()
+    }
+}
+
+impl<K, V, S> LayerStorage<K, V> for HashMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    type Iter<'a>
+        = hash_map::Iter<'a, K, V>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    fn insert(&mut self, key: K, value: V) -> Result<Option<V>, crate::Error> {
+        Ok(HashMap::insert(self, key, value))
+    }
+    fn iter(&self) -> Self::Iter<'_> {
+        HashMap::iter(self)
+    }
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+}
+
+impl<K, V, S, Q> Layer<K, V, Q> for HashMap<K, V, S>
+where
+    K: Hash + Eq + Borrow<Q>,
+    Q: Hash + Eq + ?Sized,
+    S: BuildHasher,
+{
+    fn get(&self, key: &Q) -> Option<&V> {
+        HashMap::get(self, key)
+    }
+    fn get_key_value(&self, key: &Q) -> Option<(&K, &V)> {
+        HashMap::get_key_value(self, key)
+    }
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        HashMap::get_mut(self, key)
+    }
+    fn remove(&mut self, key: &Q) -> Option<V> {
+        HashMap::remove(self, key)
+    }
+}
+
+impl<K: Ord, V> LayerStorage<K, V> for BTreeMap<K, V> {
+    type Iter<'a>
+        = btree_map::Iter<'a, K, V>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    fn insert(&mut self, key: K, value: V) -> Result<Option<V>, crate::Error> {
+        Ok(BTreeMap::insert(self, key, value))
+    }
+    fn iter(&self) -> Self::Iter<'_> {
+        BTreeMap::iter(self)
+    }
+    fn len(&self) -> usize {
+        BTreeMap::len(self)
+    }
+}
+
+impl<K, V, Q> Layer<K, V, Q> for BTreeMap<K, V>
+where
+    K: Ord + Borrow<Q>,
+    Q: Ord + ?Sized,
+{
+    fn get(&self, key: &Q) -> Option<&V> {
+        BTreeMap::get(self, key)
+    }
+    fn get_key_value(&self, key: &Q) -> Option<(&K, &V)> {
+        BTreeMap::get_key_value(self, key)
+    }
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        BTreeMap::get_mut(self, key)
+    }
+    fn remove(&mut self, key: &Q) -> Option<V> {
+        BTreeMap::remove(self, key)
+    }
+}
+
+impl<K, V, L> LayerStorage<K, V> for Box<L>
+where
+    L: LayerStorage<K, V>,
+{
+    type Iter<'a>
+        = L::Iter<'a>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    fn insert(&mut self, key: K, value: V) -> Result<Option<V>, crate::Error> {
+        (**self).insert(key, value)
+    }
+    fn iter(&self) -> Self::Iter<'_> {
+        (**self).iter()
+    }
+    fn len(&self) -> usize {
+        (**self).len()
+    }
+}
+
+impl<K, V, Q, L> Layer<K, V, Q> for Box<L>
+where
+    Q: ?Sized,
+    L: Layer<K, V, Q>,
+{
+    fn get(&self, key: &Q) -> Option<&V> {
+        (**self).get(key)
+    }
+    fn get_key_value(&self, key: &Q) -> Option<(&K, &V)> {
+        (**self).get_key_value(key)
+    }
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        (**self).get_mut(key)
+    }
+    fn remove(&mut self, key: &Q) -> Option<V> {
+        (**self).remove(key)
+    }
+}
+
+/// An object safe form of [`Layer`], implemented for every layer, so
+/// boxed layers of different kinds can share a chain.
+///
+/// `Box<dyn DynLayer<K, V, Q>>` is itself a [`Layer`]; iterating it
+/// allocates a boxed iterator, while every other layer iterates without
+/// allocating.
+pub trait DynLayer<K, V, Q: ?Sized = K> {
+    fn dyn_insert(&mut self, key: K, value: V) -> Result<Option<V>, crate::Error>;
+    fn dyn_iter(&self) -> Box<dyn Iterator<Item = (&K, &V)> + '_>;
+    fn dyn_len(&self) -> usize;
+    fn dyn_get(&self, key: &Q) -> Option<&V>;
+    fn dyn_get_key_value(&self, key: &Q) -> Option<(&K, &V)>;
+    fn dyn_get_mut(&mut self, key: &Q) -> Option<&mut V>;
+    fn dyn_remove(&mut self, key: &Q) -> Option<V>;
+}
+
+impl<K, V, Q, L> DynLayer<K, V, Q> for L
+where
+    Q: ?Sized,
+    L: Layer<K, V, Q>,
+{
+    fn dyn_insert(&mut self, key: K, value: V) -> Result<Option<V>, crate::Error> {
+        LayerStorage::insert(self, key, value)
+    }
+    fn dyn_iter(&self) -> Box<dyn Iterator<Item = (&K, &V)> + '_> {
+        Box::new(LayerStorage::iter(self))
+    }
+    fn dyn_len(&self) -> usize {
+        LayerStorage::len(self)
+    }
+    fn dyn_get(&self, key: &Q) -> Option<&V> {
+        Layer::get(self, key)
+    }
+    fn dyn_get_key_value(&self, key: &Q) -> Option<(&K, &V)> {
+        Layer::get_key_value(self, key)
+    }
+    fn dyn_get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        Layer::get_mut(self, key)
+    }
+    fn dyn_remove(&mut self, key: &Q) -> Option<V> {
+        Layer::remove(self, key)
+    }
+}
+
+impl<'l, K, V, Q> LayerStorage<K, V> for Box<dyn DynLayer<K, V, Q> + 'l>
+where
+    Q: ?Sized,
+{
+    type Iter<'a>
+        = Box<dyn Iterator<Item = (&'a K, &'a V)> + 'a>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    fn insert(&mut self, key: K, value: V) -> Result<Option<V>, crate::Error> {
+        (**self).dyn_insert(key, value)
+    }
+    fn iter(&self) -> Self::Iter<'_> {
+        (**self).dyn_iter()
+    }
+    fn len(&self) -> usize {
+        (**self).dyn_len()
+    }
+}
+
+impl<'l, K, V, Q> Layer<K, V, Q> for Box<dyn DynLayer<K, V, Q> + 'l>
+where
+    Q: ?Sized,
+{
+    fn get(&self, key: &Q) -> Option<&V> {
+        (**self).dyn_get(key)
+    }
+    fn get_key_value(&self, key: &Q) -> Option<(&K, &V)> {
+        (**self).dyn_get_key_value(key)
+    }
+    fn get_mut(&mut self, key: &Q) -> Option<&mut V> {
+        (**self).dyn_get_mut(key)
+    }
+    fn remove(&mut self, key: &Q) -> Option<V> {
+        (**self).dyn_remove(key)
+    }
+}
+
+/// An empty `HashMap`, so [`new_child`](crate::ChainMap::new_child) works
+/// on chains of boxed layers.
+impl<K, V, Q> Default for Box<dyn DynLayer<K, V, Q>>
+where
+    K: Hash + Eq + Borrow<Q> + 'static,
+    V: 'static,
+    Q: Hash + Eq + ?Sized + 'static,
+{
+    fn default() -> Self {
+        Box::new(HashMap::<K, V>::new())
+    }
+}
+
+/// Wraps a layer so that it can be read but never modified through the
+/// chain, e.g. a shared or memory mapped base layer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadOnly<L>(pub L);
+
+impl<K, V, L> LayerStorage<K, V> for ReadOnly<L>
+where
+    L: LayerStorage<K, V>,
+{
+    type Iter<'a>
+        = L::Iter<'a>
+    where
+        Self: 'a,
+        K: 'a,
+        V: 'a;
+
+    fn insert(&mut self, _key: K, _value: V) -> Result<Option<V>, crate::Error> {
+        Err(crate::Error::ReadOnlyLayer)
+    }
+    fn iter(&self) -> Self::Iter<'_> {
+        self.0.iter()
+    }
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+}
+
+impl<K, V, Q, L> Layer<K, V, Q> for ReadOnly<L>
+where
+    Q: ?Sized,
+    L: Layer<K, V, Q>,
+{
+    fn get(&self, key: &Q) -> Option<&V> {
+        self.0.get(key)
+    }
+    fn get_key_value(&self, key: &Q) -> Option<(&K, &V)> {
+        self.0.get_key_value(key)
+    }
+    fn get_mut(&mut self, _key: &Q) -> Option<&mut V> {
+        None
+    }
+    fn remove(&mut self, _key: &Q) -> Option<V> {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ChainMap;
+    use std::collections::hash_map::RandomState;
+
+    type Mixed =
+        ChainMap<&'static str, i32, RandomState, Box<dyn DynLayer<&'static str, i32, str>>>;
+
+    #[test]
+    fn mixed_layers() {
+        let mut base = BTreeMap::new();
+        base.insert("x", 0);
+        base.insert("y", 0);
+        let mut chain: Mixed = ChainMap::from_layer(Box::new(ReadOnly(base)));
+        chain.new_child();
+        chain.insert("x", 1);
+
+        assert_eq!(chain.get("x"), Some(&1));
+        assert_eq!(chain.get("y"), Some(&0));
+        assert!(chain.get_mut("y").is_none());
+        assert!(matches!(
+            chain.insert_at(0, "z", 1),
+            Err(crate::Error::ReadOnlyLayer)
+        ));
+        assert_eq!(chain.remove("x"), Some(1));
+        assert_eq!(chain.remove("x"), None);
+        assert_eq!(chain.get("x"), Some(&0));
+
+        let mut seen = Vec::new();
+        chain.for_each(|k, v| seen.push((*k, *v)));
+        seen.sort();
+        assert_eq!(seen, vec![("x", 0), ("y", 0)]);
+        assert!(chain.remove_child().is_some());
+        assert_eq!(chain.child_len(), 1);
+    }
+
+    #[test]
+    fn remove_stops_at_read_only_scope() {
+        let mut chain: Mixed = ChainMap::from_layer(Box::new(HashMap::new()));
+        chain.insert("x", 0);
+        let mut inner = HashMap::new();
+        inner.insert("x", 1);
+        chain.new_child_with(Box::new(ReadOnly(inner)));
+
+        assert_eq!(chain.remove("x"), None);
+        assert!(matches!(chain.remove_at(1, "x"), Ok(None)));
+        assert_eq!(chain.get("x"), Some(&1));
+        assert_eq!(chain.remove_at(0, "x").unwrap(), Some(0));
+    }
+
+    #[test]
+    fn from_layer_with_hasher() {
+        use hashers::oz::DJB2Hasher;
+        use std::hash::BuildHasherDefault;
+        let mut base = BTreeMap::new();
+        base.insert("x", 0);
+        let mut chain: ChainMap<_, _, BuildHasherDefault<DJB2Hasher>, _> =
+            ChainMap::from_layer(base);
+        chain.new_child();
+        chain.insert("y", 1);
+        let iter: btree_map::Iter<'_, _, _> = LayerStorage::iter(chain.layer(1));
+        assert_eq!(iter.collect::<Vec<_>>(), [(&"y", &1)]);
+        assert_eq!(chain.get("x"), Some(&0));
+    }
+}
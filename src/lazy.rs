@@ -0,0 +1,83 @@
+use std::sync::{Arc, OnceLock};
+
+pub(crate) type Init<L> = Arc<dyn Fn() -> L + Send + Sync>;
+
+type CopyLayer<L> = fn(&L) -> L;
+
+/// A layer whose contents live outside the chain's own maps until the
+/// chain first writes to it.
+pub(crate) struct LazyLayer<L> {
+    pub(crate) idx: usize,
+    source: Source<L>,
+}
+
+enum Source<L> {
+    /// Built the first time a lookup reaches the layer, see
+    /// [`ChainMap::new_child_lazy`](crate::ChainMap::new_child_lazy).
+    Init(Init<L>, OnceLock<L>),
+    /// Shared with a [`CapturedEnv`](crate::CapturedEnv), and copied by
+    /// the function on the first write if it is still shared.
+    Shared(Arc<L>, CopyLayer<L>),
+}
+
+impl<L> LazyLayer<L> {
+    pub(crate) fn new(idx: usize, init: Init<L>) -> Self {
+        Self {
+            idx,
+            source: Source::Init(init, OnceLock::new()),
+        }
+    }
+
+    pub(crate) fn shared(idx: usize, map: Arc<L>) -> Self
+    where
+        L: Clone,
+    {
+        Self {
+            idx,
+            source: Source::Shared(map, L::clone),
+        }
+    }
+
+    pub(crate) fn is_materialized(&self) -> bool {
+        match &self.source {
+            Source::Init(_, cell) => cell.get().is_some(),
+            Source::Shared(..) => true,
+        }
+    }
+
+    /// The map shared with a captured environment, if this is a shared
+    /// layer.
+    pub(crate) fn as_shared(&self) -> Option<&Arc<L>> {
+        match &self.source {
+            Source::Shared(map, _) => Some(map),
+            Source::Init(..) => None,
+        }
+    }
+
+    pub(crate) fn force(&self) -> &L {
+        match &self.source {
+            Source::Init(init, cell) => cell.get_or_init(|| init()),
+            Source::Shared(map, _) => map,
+        }
+    }
+
+    pub(crate) fn into_map(self) -> L {
+        match self.source {
+            Source::Init(init, cell) => cell.into_inner().unwrap_or_else(|| init()),
+            Source::Shared(map, copy) => Arc::try_unwrap(map).unwrap_or_else(|map| copy(&map)),
+        }
+    }
+}
+
+impl<L: Clone> Clone for LazyLayer<L> {
+    fn clone(&self) -> Self {
+        let source = match &self.source {
+            Source::Init(init, cell) => Source::Init(Arc::clone(init), cell.clone()),
+            Source::Shared(map, copy) => Source::Shared(Arc::clone(map), *copy),
+        };
+        Self {
+            idx: self.idx,
+            source,
+        }
+    }
+}
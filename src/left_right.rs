@@ -0,0 +1,353 @@
+use crate::{ChainMap, ReadView};
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    mem::take,
+    ops::Deref,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+    thread,
+};
+
+/// The two copies of the chain and the readers looking at them.
+struct Shared<K, V, S> {
+    copies: [RwLock<ReadView<K, V, S>>; 2],
+    /// The copy new reads go to, the writer owns the other one.
+    active: AtomicUsize,
+    /// One counter per reader, odd while that reader holds a guard.
+    epochs: Mutex<Vec<Arc<AtomicUsize>>>,
+}
+
+/// A change made by the writer, kept to be applied again to the second
+/// copy once the readers have moved off it.
+enum Op<K, V> {
+    Insert(K, V),
+    Remove(K),
+    NewChild,
+    RemoveChild,
+}
+
+/// The writing side of a chain kept in two copies, the left-right
+/// pattern: readers look at one copy while the single writer changes the
+/// other, and [`publish`](Self::publish) swaps them.
+///
+/// Reads never wait on the writer and never allocate; in exchange, changes
+/// are only visible to readers once published, and every change is
+/// applied twice, so keys and values are cloned.
+///
+/// ```
+/// # use hash_chain::LeftRightChainMap;
+/// let mut writer = LeftRightChainMap::new();
+/// let mut reader = writer.reader();
+/// writer.insert("flag", true);
+/// assert_eq!(reader.get_clone("flag"), None);
+/// writer.publish();
+/// assert_eq!(reader.get_clone("flag"), Some(true));
+/// ```
+pub struct LeftRightChainMap<K, V, S = RandomState> {
+    shared: Arc<Shared<K, V, S>>,
+    hasher: S,
+    ops: Vec<Op<K, V>>,
+}
+
+/// A handle reading the published state of a [`LeftRightChainMap`].
+///
+/// Each handle belongs to one thread at a time; clone it to read from
+/// another.
+pub struct LeftRightReader<K, V, S = RandomState> {
+    shared: Arc<Shared<K, V, S>>,
+    epoch: Arc<AtomicUsize>,
+}
+
+/// A read of a [`LeftRightChainMap`], see [`LeftRightReader::read`].
+pub struct LeftRightGuard<'a, K, V, S = RandomState> {
+    view: RwLockReadGuard<'a, ReadView<K, V, S>>,
+    // dropped after the view, so the writer never finds the lock held
+    _epoch: EpochGuard<'a>,
+}
+
+struct EpochGuard<'a>(&'a AtomicUsize);
+
+impl<K, V> LeftRightChainMap<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    pub fn new() -> Self {
+        Self::from(ChainMap::default())
+    }
+}
+
+impl<K, V, S> LeftRightChainMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    /// Creates a handle reading the published state of the chain.
+    pub fn reader(&self) -> LeftRightReader<K, V, S> {
+        LeftRightReader::register(Arc::clone(&self.shared))
+    }
+
+    /// Inserts a key-value pair into the current scope, visible to readers
+    /// once published.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let old = self
+            .pending()
+            .maps
+            .last_mut()?
+            .insert(key.clone(), value.clone());
+        self.ops.push(Op::Insert(key, value));
+        old
+    }
+
+    /// Removes a key from the current scope, returning its value.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let (key, value) = self.pending().maps.last_mut()?.remove_entry(key)?;
+        self.ops.push(Op::Remove(key));
+        Some(value)
+    }
+
+    pub fn new_child(&mut self) {
+        let hasher = self.hasher.clone();
+        Self::apply(&mut self.pending(), &Op::NewChild, &hasher);
+        self.ops.push(Op::NewChild);
+    }
+
+    /// Removes the current scope. As with [`ChainMap::remove_child`], the
+    /// last scope is cleared rather than removed.
+    pub fn remove_child(&mut self) {
+        let hasher = self.hasher.clone();
+        Self::apply(&mut self.pending(), &Op::RemoveChild, &hasher);
+        self.ops.push(Op::RemoveChild);
+    }
+
+    /// Returns `true` if there are changes readers don't see yet.
+    pub fn has_pending(&self) -> bool {
+        !self.ops.is_empty()
+    }
+
+    /// Makes every change so far visible to readers.
+    ///
+    /// Waits for the reads still looking at the old copy to finish, then
+    /// brings that copy up to date, so it can take the next changes.
+    pub fn publish(&mut self) {
+        if self.ops.is_empty() {
+            return;
+        }
+        let old = self.shared.active.load(Ordering::SeqCst);
+        self.shared.active.store(1 - old, Ordering::SeqCst);
+        self.wait_for_readers();
+        let mut stale = self.pending();
+        for op in &self.ops {
+            Self::apply(&mut stale, op, &self.hasher);
+        }
+        drop(stale);
+        self.ops.clear();
+    }
+
+    fn wait_for_readers(&self) {
+        let mut epochs = self
+            .shared
+            .epochs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        // readers whose handles are gone can't read anymore
+        epochs.retain(|epoch| Arc::strong_count(epoch) > 1);
+        for epoch in epochs.iter() {
+            let seen = epoch.load(Ordering::SeqCst);
+            if seen % 2 == 1 {
+                while epoch.load(Ordering::SeqCst) == seen {
+                    thread::yield_now();
+                }
+            }
+        }
+    }
+
+    /// The copy readers aren't looking at.
+    fn pending(&self) -> RwLockWriteGuard<'_, ReadView<K, V, S>> {
+        let idx = 1 - self.shared.active.load(Ordering::SeqCst);
+        self.shared.copies[idx]
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn apply(view: &mut ReadView<K, V, S>, op: &Op<K, V>, hasher: &S) {
+        let maps = &mut view.maps;
+        match op {
+            Op::Insert(key, value) => {
+                if let Some(map) = maps.last_mut() {
+                    map.insert(key.clone(), value.clone());
+                }
+            }
+            Op::Remove(key) => {
+                if let Some(map) = maps.last_mut() {
+                    map.remove(key);
+                }
+            }
+            Op::NewChild => maps.push(HashMap::with_hasher(hasher.clone())),
+            Op::RemoveChild => {
+                if maps.len() == 1 {
+                    maps[0].clear();
+                } else {
+                    maps.pop();
+                }
+            }
+        }
+    }
+}
+
+impl<K, V, S> From<ChainMap<K, V, S>> for LeftRightChainMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    fn from(mut chain: ChainMap<K, V, S>) -> Self {
+        chain.force_lazy();
+        let maps = take(&mut chain.maps);
+        let hasher = maps[0].hasher().clone();
+        Self {
+            shared: Arc::new(Shared {
+                copies: [
+                    RwLock::new(ReadView { maps: maps.clone() }),
+                    RwLock::new(ReadView { maps }),
+                ],
+                active: AtomicUsize::new(0),
+                epochs: Mutex::new(Vec::new()),
+            }),
+            hasher,
+            ops: Vec::new(),
+        }
+    }
+}
+
+impl<K, V> Default for LeftRightChainMap<K, V>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, S> LeftRightReader<K, V, S> {
+    fn register(shared: Arc<Shared<K, V, S>>) -> Self {
+        let epoch = Arc::new(AtomicUsize::new(0));
+        shared
+            .epochs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::clone(&epoch));
+        Self { shared, epoch }
+    }
+
+    /// Starts a read of the published state. Every lookup through the
+    /// guard sees the same state, and the writer's next publish waits for
+    /// the guard to be dropped.
+    pub fn read(&mut self) -> LeftRightGuard<'_, K, V, S> {
+        self.epoch.fetch_add(1, Ordering::SeqCst);
+        let epoch = EpochGuard(&self.epoch);
+        let idx = self.shared.active.load(Ordering::SeqCst);
+        let view = self.shared.copies[idx]
+            .read()
+            .unwrap_or_else(PoisonError::into_inner);
+        LeftRightGuard {
+            view,
+            _epoch: epoch,
+        }
+    }
+
+    /// Returns a copy of the published value corresponding to the key.
+    pub fn get_clone<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Hash + Eq + Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+        S: BuildHasher,
+    {
+        self.read().get(key).cloned()
+    }
+}
+
+impl<K, V, S> Clone for LeftRightReader<K, V, S> {
+    fn clone(&self) -> Self {
+        Self::register(Arc::clone(&self.shared))
+    }
+}
+
+impl<K, V, S> Deref for LeftRightGuard<'_, K, V, S> {
+    type Target = ReadView<K, V, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.view
+    }
+}
+
+impl Drop for EpochGuard<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn publish() {
+        let mut writer = LeftRightChainMap::new();
+        let mut reader = writer.reader();
+        writer.insert("x", 0);
+        writer.new_child();
+        writer.insert("x", 1);
+        assert!(writer.has_pending());
+        assert_eq!(reader.get_clone("x"), None);
+        writer.publish();
+        assert!(!writer.has_pending());
+        assert_eq!(reader.get_clone("x"), Some(1));
+        writer.remove_child();
+        writer.publish();
+        assert_eq!(reader.get_clone("x"), Some(0));
+        // both copies took every change
+        assert_eq!(writer.remove("x"), Some(0));
+        writer.publish();
+        assert_eq!(reader.read().child_len(), 1);
+        assert_eq!(reader.get_clone("x"), None);
+    }
+
+    #[test]
+    fn readers_on_threads() {
+        let mut writer = LeftRightChainMap::new();
+        writer.insert(0, 0);
+        writer.publish();
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let mut reader = writer.reader();
+                thread::spawn(move || {
+                    let mut last = 0;
+                    while last < 100 {
+                        let value = reader.get_clone(&0).unwrap();
+                        assert!(value >= last);
+                        last = value;
+                    }
+                })
+            })
+            .collect();
+        for i in 1..=100 {
+            writer.insert(0, i);
+            writer.publish();
+        }
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+}
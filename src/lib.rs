@@ -1,11 +1,139 @@
+mod adapter;
+#[cfg(feature = "arbitrary")]
+mod arbitrary_impl;
+#[cfg(feature = "allocator_api")]
+mod alloc;
+#[cfg(feature = "async")]
+mod async_map;
+#[cfg(feature = "binary")]
+mod binary;
+mod bit_set;
+mod bounds;
+mod builder;
+mod by_ref;
+mod captured;
+mod cell;
+mod chain_map_like;
+mod cow_keys;
+#[cfg(feature = "config")]
+mod config;
+mod diff;
+#[cfg(feature = "graphviz")]
+mod dot;
+#[cfg(feature = "std-env")]
+mod env;
+mod entry;
+mod environment;
 mod error;
+mod fallback;
+mod flat;
+#[cfg(feature = "std-sync")]
+mod global;
+mod guard;
+#[cfg(feature = "indexmap")]
+mod index_set;
+mod iter;
+mod frozen;
+#[cfg(feature = "json")]
+mod json;
+mod kind;
+mod layer;
+#[cfg(feature = "std-sync")]
+mod left_right;
+mod lazy;
 mod map;
+mod policy;
+mod persistent;
+mod prefix;
+mod provider;
+mod read_view;
+mod redact;
+#[cfg(feature = "rkyv")]
+mod rkyv_impl;
+#[cfg(feature = "serde")]
+mod serde_impl;
+mod scoped_view;
+#[cfg(feature = "deterministic")]
+mod seeded;
+mod seen;
 mod set;
-#[cfg(feature = "imutable")]
+#[cfg(feature = "std-sync")]
+mod sharded;
+mod slot;
+mod snapshot;
+mod stacks;
+#[cfg(feature = "suggestions")]
+mod suggest;
+mod tracking;
+mod transient;
+mod undo;
+mod validator;
+mod vec_map;
+#[cfg(feature = "zeroize")]
+mod zeroize_impl;
+#[cfg(any(fuzzing, test))]
+pub mod fuzz;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+#[cfg(feature = "test-util")]
+pub mod test_util;
 mod imutable;
 
-pub use error::Error;
+pub use adapter::{AdaptedChainMap, CaseInsensitive, KeyAdapter};
+#[cfg(feature = "allocator_api")]
+pub use alloc::ChainMapIn;
+#[cfg(feature = "bumpalo")]
+pub use alloc::BumpChainMap;
+#[cfg(feature = "async")]
+pub use async_map::{AsyncChainMap, AsyncReadGuard, ReadGuard};
+pub use bit_set::ChainBitSet;
+pub use bounds::LookupBounds;
+pub use builder::ChainMapBuilder;
+pub use by_ref::ChainMapRef;
+pub use captured::CapturedEnv;
+pub use cell::CellChainMap;
+pub use chain_map_like::ChainMapLike;
+#[cfg(feature = "config")]
+pub use config::Source;
+pub use diff::{BindingChange, ChainDiff, LayerDiff};
+#[cfg(feature = "std-env")]
+pub use env::{CaseMapping, EnvLayer};
+pub use entry::{EntryRef, OccupiedEntryRef, VacantEntryRef};
+pub use environment::Environment;
+pub use error::{AlreadyDeclared, Error};
+pub use flat::FlatChainMap;
+#[cfg(feature = "indexmap")]
+pub use index_set::ChainIndexSet;
+pub use iter::{IterAll, Layers};
+pub use frozen::FrozenChainMap;
+#[cfg(feature = "std-sync")]
+pub use global::{GlobalChainMap, ScopeGuard};
+pub use guard::ChildGuard;
+#[cfg(feature = "json")]
+pub use json::JsonChainMap;
+pub use kind::{KindedChainMap, ScopeKind};
+pub use layer::{DynLayer, Layer, LayerStorage, ReadOnly};
+#[cfg(feature = "std-sync")]
+pub use left_right::{LeftRightChainMap, LeftRightGuard, LeftRightReader};
 pub use map::ChainMap;
+pub use policy::{Insertion, MergeOnLoad, RenameCollision, ShadowPolicy, WritePolicy};
+pub use persistent::PersistentMap;
+pub use prefix::PrefixView;
+pub use provider::LayerProvider;
+pub use read_view::ReadView;
+#[cfg(feature = "rkyv")]
+pub use rkyv_impl::{ArchivedChainMap, ArchivedChainSet};
+pub use scoped_view::ScopedView;
+#[cfg(feature = "deterministic")]
+pub use seeded::{SeededHasher, SeededState};
 pub use set::ChainSet;
-#[cfg(feature = "imutable")]
-pub use imutable::LockedChainMap;
\ No newline at end of file
+#[cfg(feature = "std-sync")]
+pub use sharded::ShardedChainMap;
+pub use slot::{SlotChainMap, ValueHandle};
+pub use snapshot::{Snapshot, SnapshotChainMap};
+pub use transient::TransientChainMap;
+pub use undo::UndoChainMap;
+pub use vec_map::ChainVecMap;
+#[cfg(feature = "zeroize")]
+pub use zeroize_impl::{ZeroizingChainMap, ZeroizingChainSet};
+pub use imutable::{LockedChainMap, LockedView};
\ No newline at end of file
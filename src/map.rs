@@ -1,34 +1,174 @@
+use crate::{
+    fallback::Fallback,
+    iter::{IterAll, Layers},
+    layer::{Layer, LayerStorage},
+    redact::{RedactedLayer, Redactor},
+    seen::Seen,
+    tracking::ReadTracker,
+    validator::Validator,
+    lazy::LazyLayer,
+    provider::ProvidedLayer,
+    AlreadyDeclared, CapturedEnv, Insertion, LayerProvider, LookupBounds, RenameCollision, ShadowPolicy,
+    WritePolicy,
+};
 use std::{
-    borrow::Borrow,
-    collections::{hash_map::RandomState, HashMap},
+    borrow::{Borrow, Cow},
+    collections::{
+        hash_map::{Entry, RandomState},
+        BTreeMap, HashMap, HashSet,
+    },
     hash::{BuildHasher, Hash},
     mem::take,
     ops::Index,
+    sync::Arc,
 };
 
 #[derive(Clone)]
-pub struct ChainMap<K, V, S = RandomState> {
-    pub(crate) maps: Vec<HashMap<K, V, S>>,
+pub struct ChainMap<K, V, S = RandomState, L = HashMap<K, V, S>> {
+    pub(crate) maps: Vec<L>,
+    pub(crate) shadow_policy: ShadowPolicy,
+    pub(crate) write_policy: WritePolicy,
+
+    pub(crate) providers: Vec<ProvidedLayer<K, V, S>>,
+    pub(crate) fallback: Option<Fallback<K, V>>,
+    pub(crate) lazy: Vec<LazyLayer<L>>,
+    pub(crate) pool: Vec<L>,
+    pub(crate) validator: Option<Validator<K, V>>,
+    pub(crate) redactor: Option<Redactor<K>>,
+    pub(crate) tracker: Option<ReadTracker<K>>,
+    /// Builds an empty layer like an existing one, so a chain created
+    /// with a hasher passes it on to every scope it pushes later.
+    pub(crate) new_layer: Option<fn(&L) -> L>,
 }
 
-impl<K: Hash + Eq, V, S: BuildHasher> ChainMap<K, V, S>
+impl<K, V, S, L> ChainMap<K, V, S, L> {
+    pub(crate) fn from_maps(maps: Vec<L>) -> Self {
+        Self {
+            maps,
+            shadow_policy: ShadowPolicy::default(),
+            write_policy: WritePolicy::default(),
+
+            providers: Vec::new(),
+            fallback: None,
+            lazy: Vec::new(),
+            pool: Vec::new(),
+            validator: None,
+            redactor: None,
+            tracker: None,
+            new_layer: None,
+        }
+    }
+
+    /// An empty layer for a new scope, built like the innermost layer if
+    /// the chain was created with a hasher.
+    fn empty_layer(&self) -> L
+    where
+        L: Default,
+    {
+        match (self.new_layer, self.maps.last()) {
+            (Some(new_layer), Some(layer)) => new_layer(layer),
+            _ => L::default(),
+        }
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.maps.len()
+    }
+
+    /// Returns `true` unless the layer at `idx` is a lazy layer whose
+    /// contents have not been built yet.
+    pub fn is_materialized(&self, idx: usize) -> bool {
+        self.lazy
+            .iter()
+            .find(|l| l.idx == idx)
+            .is_none_or(LazyLayer::is_materialized)
+    }
+
+    /// Returns the layer at `idx`, building it if it is lazy.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of range.
+    pub(crate) fn layer(&self, idx: usize) -> &L {
+        match self.lazy.iter().find(|l| l.idx == idx) {
+            Some(lazy) => lazy.force(),
+            None => &self.maps[idx],
+        }
+    }
+
+    /// Iterates over every layer from the outermost inward, building lazy
+    /// layers as they are reached.
+    pub fn layers(&self) -> Layers<'_, K, V, S, L> {
+        Layers::new(self)
+    }
+
+    /// Returns the layer at `idx` for mutation, moving a lazy layer's
+    /// contents into the chain for good.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is out of range.
+    pub(crate) fn layer_mut(&mut self, idx: usize) -> &mut L {
+        if let Some(pos) = self.lazy.iter().position(|l| l.idx == idx) {
+            self.maps[idx] = self.lazy.swap_remove(pos).into_map();
+        }
+        &mut self.maps[idx]
+    }
+
+    /// Builds every outstanding lazy layer.
+    pub(crate) fn force_lazy(&mut self) {
+        for lazy in take(&mut self.lazy) {
+            let idx = lazy.idx;
+            self.maps[idx] = lazy.into_map();
+        }
+    }
+
+    pub fn new_child_with(&mut self, layer: L) {
+        self.maps.push(layer);
+    }
+
+    /// Detaches the providers of every layer at or above `idx`.
+    pub(crate) fn forget_providers_from(&mut self, idx: usize) {
+        self.providers.retain(|p| p.idx < idx);
+    }
+
+    fn depth_index(&self, depth: usize) -> Option<usize> {
+        self.maps.len().checked_sub(depth)?.checked_sub(1)
+    }
+}
+
+impl<K, V, S, L> ChainMap<K, V, S, L>
 where
     K: Hash + Eq,
-    S: BuildHasher,
+    L: LayerStorage<K, V>,
 {
-    pub fn new(map: HashMap<K, V, S>) -> Self {
-        Self { maps: vec![map] }
+    /// Creates a chain with `layer` as its only scope, which may be any
+    /// kind of [`Layer`], e.g. a boxed read-only base layer.
+    pub fn from_layer(layer: L) -> Self {
+        Self::from_maps(vec![layer])
+    }
+
+    fn record_read(&self, idx: usize, key: &K) {
+        if let Some(tracker) = &self.tracker {
+            tracker.record(idx, self.maps.len(), key);
+        }
     }
+
     /// Inserts a key-value pair into the map.
     /// If the map did not have this key present, None is returned.
+    ///
+    /// This does not consult the shadowing policy, see
+    /// [`insert_checked`](Self::insert_checked). If the current scope is a
+    /// layer that can't be written to, the pair is dropped; use
+    /// [`insert_at`](Self::insert_at) to get the error instead.
     pub fn insert(&mut self, key: K, value: V) -> Option<V> {
-        let map = self.maps.last_mut()?;
-        map.insert(key, value)
+        let idx = self.maps.len().checked_sub(1)?;
+        self.layer_mut(idx).insert(key, value).ok().flatten()
     }
 
     pub fn insert_at(&mut self, idx: usize, key: K, value: V) -> Result<Option<V>, crate::Error> {
-        if let Some(map) = self.maps.get_mut(idx) {
-            Ok(map.insert(key, value))
+        if idx < self.maps.len() {
+            self.layer_mut(idx).insert(key, value)
         } else {
             Err(crate::Error::IndexOutOfRange)
         }
@@ -41,16 +181,43 @@ where
     /// the key type.
     pub fn get<Q: ?Sized>(&self, key: &Q) -> Option<&V>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        L: Layer<K, V, Q>,
     {
-        for map in self.maps.iter().rev() {
-            if let Some(v) = map.get(key) {
-                return Some(v);
-            }
-        }
-        None
+        self.get_in(LookupBounds::All, key)
+    }
+
+    /// Like [`get`](Self::get), failing with `Error::KeyNotFound` if the
+    /// key is unbound.
+    pub fn try_get<Q>(&self, key: &Q) -> Result<&V, crate::Error>
+    where
+        L: Layer<K, V, Q>,
+        Q: ?Sized,
+    {
+        self.get(key).ok_or(crate::Error::KeyNotFound)
+    }
+
+    /// Returns a copy of the value corresponding to the key.
+    pub fn get_owned<Q>(&self, key: &Q) -> Option<V>
+    where
+        L: Layer<K, V, Q>,
+        Q: ?Sized,
+        V: Clone,
+    {
+        self.get(key).cloned()
+    }
+
+    /// Returns a copy of the value each key resolves to, in the order of
+    /// `keys`.
+    pub fn resolve_owned<'q, Q, I>(&self, keys: I) -> Vec<Option<V>>
+    where
+        L: Layer<K, V, Q>,
+        Q: ?Sized + 'q,
+        I: IntoIterator<Item = &'q Q>,
+        V: Clone,
+    {
+        keys.into_iter().map(|key| self.get_owned(key)).collect()
     }
+
     /// Returns a mutable reference to the value corresponding to the key.
     ///
     /// The supplied key may be any borrowed form of the map's key type, but
@@ -58,429 +225,2674 @@ where
     /// the key type.
     pub fn get_mut<Q: ?Sized>(&mut self, key: &Q) -> Option<&mut V>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        L: Layer<K, V, Q>,
     {
-        for map in self.maps.iter_mut().rev() {
-            if let Some(v) = map.get_mut(key) {
+        self.get_in_mut(LookupBounds::All, key)
+    }
+
+    /// Returns the value corresponding to the key, searching only the
+    /// layers selected by `bounds`, innermost first.
+    pub fn get_in<Q>(&self, bounds: LookupBounds, key: &Q) -> Option<&V>
+    where
+        L: Layer<K, V, Q>,
+        Q: ?Sized,
+    {
+        let range = bounds.range(self.maps.len());
+        for i in range.rev() {
+            if let Some((k, v)) = self.layer(i).get_key_value(key) {
+                self.record_read(i, k);
                 return Some(v);
             }
         }
         None
     }
 
-    pub fn get_before<Q: ?Sized>(&self, idx: usize, key: &Q) -> Option<&V>
+    /// Returns a mutable reference to the value corresponding to the key,
+    /// searching only the layers selected by `bounds`, innermost first.
+    pub fn get_in_mut<Q>(&mut self, bounds: LookupBounds, key: &Q) -> Option<&mut V>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        L: Layer<K, V, Q>,
+        Q: ?Sized,
     {
-        let iter = if idx >= self.maps.len() {
-            self.maps.iter()
-        } else {
-            self.maps[0..idx].iter()
-        };
+        let range = bounds.range(self.maps.len());
+        let idx = range.rev().find(|i| self.layer(*i).contains_key(key))?;
+        self.layer_mut(idx).get_mut(key)
+    }
 
-        for map in iter.rev() {
-            if let Some(v) = map.get(key) {
-                return Some(v);
-            }
-        }
-        None
+    /// Searches only the layers strictly below `idx`, see
+    /// [`LookupBounds::Before`].
+    pub fn get_before<Q: ?Sized>(&self, idx: usize, key: &Q) -> Option<&V>
+    where
+        L: Layer<K, V, Q>,
+    {
+        self.get_in(LookupBounds::Before(idx), key)
     }
 
     pub fn get_before_mut<Q: ?Sized>(&mut self, idx: usize, key: &Q) -> Option<&mut V>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        L: Layer<K, V, Q>,
     {
-        let iter = if idx >= self.maps.len() {
-            self.maps.iter_mut()
-        } else {
-            self.maps[0..idx].iter_mut()
-        };
+        self.get_in_mut(LookupBounds::Before(idx), key)
+    }
 
-        for map in iter.rev() {
-            if let Some(v) = map.get_mut(key) {
-                return Some(v);
-            }
-        }
-        None
+    /// Searches only the layers at or above `idx`, see
+    /// [`LookupBounds::After`].
+    pub fn get_after<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+    where
+        L: Layer<K, V, Q>,
+        Q: ?Sized,
+    {
+        self.get_in(LookupBounds::After(idx), key)
     }
 
-    pub fn new_child_with(&mut self, map: HashMap<K, V, S>) {
-        self.maps.push(map);
+    pub fn get_after_mut<Q>(&mut self, idx: usize, key: &Q) -> Option<&mut V>
+    where
+        L: Layer<K, V, Q>,
+        Q: ?Sized,
+    {
+        self.get_in_mut(LookupBounds::After(idx), key)
     }
 
     pub fn last_has<Q: ?Sized>(&self, key: &Q) -> bool
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        L: Layer<K, V, Q>,
     {
-        self.has_at(self.maps.len() - 1, key)
+        self.maps
+            .len()
+            .checked_sub(1)
+            .is_some_and(|idx| self.has_at(idx, key))
     }
 
     pub fn has_at<Q: ?Sized>(&self, idx: usize, key: &Q) -> bool
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        L: Layer<K, V, Q>,
     {
-        if let Some(map) = self.maps.get(idx) {
-            map.contains_key(key)
+        if idx < self.maps.len() {
+            self.layer(idx).contains_key(key)
         } else {
             false
         }
     }
 
-    pub fn child_len(&self) -> usize {
-        self.maps.len()
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        L: Layer<K, V, Q>,
+        Q: ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    /// The number of the chain's layers that bind the key, counting
+    /// shadowed bindings.
+    pub fn occurrences<Q>(&self, key: &Q) -> usize
+    where
+        L: Layer<K, V, Q>,
+        Q: ?Sized,
+    {
+        self.layers().filter(|map| map.contains_key(key)).count()
+    }
+
+    /// Returns the value bound to the key in the layer at `idx`, without
+    /// searching any other layer.
+    pub fn get_at<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+    where
+        L: Layer<K, V, Q>,
+        Q: ?Sized,
+    {
+        if idx < self.maps.len() {
+            self.layer(idx).get(key)
+        } else {
+            None
+        }
+    }
+
+    /// Removes the key from the current scope, returning its value. A
+    /// binding in an outer scope becomes visible again.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        L: Layer<K, V, Q>,
+        Q: ?Sized,
+    {
+        let idx = self.maps.len().checked_sub(1)?;
+        self.layer_mut(idx).remove(key)
+    }
+
+    /// Removes the key from the layer at `idx`, returning its value.
+    pub fn remove_at<Q>(&mut self, idx: usize, key: &Q) -> Result<Option<V>, crate::Error>
+    where
+        L: Layer<K, V, Q>,
+        Q: ?Sized,
+    {
+        if idx < self.maps.len() {
+            Ok(self.layer_mut(idx).remove(key))
+        } else {
+            Err(crate::Error::IndexOutOfRange)
+        }
+    }
+
+    /// The number of visible keys, counting each key once no matter how
+    /// many scopes bind it.
+    pub fn len(&self) -> usize {
+        self.effective_keys().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers().all(L::is_empty)
+    }
+
+    /// The number of bindings across every scope, including ones that are
+    /// shadowed by an inner scope.
+    pub fn total_len(&self) -> usize {
+        self.layers().map(L::len).sum()
+    }
+
+    /// Returns the value bound to the key in the scope `depth` hops out
+    /// from the innermost one, without searching any other scope.
+    ///
+    /// Unlike the `*_at` methods, which index from the outermost scope,
+    /// a `depth` of `0` is the current scope.
+    pub fn get_at_depth<Q>(&self, depth: usize, key: &Q) -> Option<&V>
+    where
+        L: Layer<K, V, Q>,
+        Q: ?Sized,
+    {
+        let idx = self.depth_index(depth)?;
+        self.layer(idx).get(key)
+    }
+
+    pub fn get_at_depth_mut<Q>(&mut self, depth: usize, key: &Q) -> Option<&mut V>
+    where
+        L: Layer<K, V, Q>,
+        Q: ?Sized,
+    {
+        let idx = self.depth_index(depth)?;
+        self.layer_mut(idx).get_mut(key)
+    }
+
+    /// Binds the key in the scope `depth` hops out from the innermost one,
+    /// returning the previous value in that scope.
+    pub fn assign_at_depth(&mut self, depth: usize, key: K, value: V) -> Result<Option<V>, crate::Error> {
+        let idx = self.depth_index(depth).ok_or(crate::Error::IndexOutOfRange)?;
+        self.insert_at(idx, key, value)
     }
 
     pub fn get_last_index<Q: ?Sized>(&self, key: &Q) -> Option<usize>
     where
-        K: Borrow<Q>,
-        Q: Hash + Eq,
+        L: Layer<K, V, Q>,
     {
-        for (i, map) in self.maps.iter().enumerate().rev() {
+        for (i, map) in self.layers().enumerate().rev() {
             if map.contains_key(key) {
                 return Some(i);
             }
         }
         None
     }
-}
-
-impl<K: Hash + Eq, V, S: BuildHasher + Default> ChainMap<K, V, S> {
-    pub fn new_child(&mut self) {
-        self.maps.push(HashMap::default());
-    }
 
-    pub fn remove_child(&mut self) -> Option<HashMap<K, V, S>> {
-        if self.maps.len() == 1 {
-            let ret = take(&mut self.maps[0]);
-            Some(ret)
-        } else {
-            self.maps.pop()
-        }
+    /// Calls `f` once for every visible key-value pair, innermost
+    /// scope first. Keys shadowed by an inner scope are skipped.
+    ///
+    /// The visited keys are tracked without allocating for the first
+    /// few; see [`for_each_with_capacity`](Self::for_each_with_capacity).
+    pub fn for_each<F>(&self, f: F)
+    where
+        F: FnMut(&K, &V),
+    {
+        self.for_each_with_capacity(0, f)
     }
 
-    pub fn split_off(&mut self, idx: usize) -> Self {
-        let maps = self.maps.split_off(idx);
-        Self {
-            maps,
+    /// Like [`for_each`](Self::for_each), with a hint of how many distinct
+    /// keys the chain holds. Up to 16 keys are tracked inline, so a chain
+    /// within that bound is walked without allocating; for a larger hint
+    /// the set of visited keys is allocated up front at that size.
+    pub fn for_each_with_capacity<F>(&self, capacity: usize, mut f: F)
+    where
+        F: FnMut(&K, &V),
+    {
+        let mut seen = Seen::with_capacity(capacity);
+        for layer in self.layers().rev() {
+            for (k, v) in layer.iter() {
+                if seen.insert(k) {
+                    f(k, v);
+                }
+            }
         }
     }
 
-    pub fn append(&mut self, other: &mut Self) {
-        self.maps.append(&mut other.maps);
+    /// Returns the set of keys visible through the chain, i.e. every key
+    /// bound in at least one scope, counted once regardless of shadowing.
+    pub fn effective_keys(&self) -> HashSet<&K> {
+        self.layers().flat_map(|layer| layer.iter().map(|(k, _)| k)).collect()
     }
 }
 
-impl<K, V> Default for ChainMap<K, V>
+impl<K, V, S, L> ChainMap<K, V, S, L>
 where
     K: Hash + Eq,
+    L: LayerStorage<K, V> + Default,
 {
-    fn default() -> Self {
-        Self {
-            maps: vec![HashMap::new()],
+    /// Pushes an empty child layer, reusing a layer previously released
+    /// with [`discard_child`](Self::discard_child) if one is available.
+    pub fn new_child(&mut self) {
+        let map = self.pool.pop().unwrap_or_else(|| self.empty_layer());
+        self.maps.push(map);
+    }
+
+    pub fn remove_child(&mut self) -> Option<L> {
+        if self.maps.len() == 1 {
+            self.forget_providers_from(0);
+            let empty = self.empty_layer();
+            let ret = std::mem::replace(self.layer_mut(0), empty);
+            Some(ret)
+        } else {
+            let top = self.maps.len().checked_sub(1)?;
+            self.layer_mut(top);
+            let ret = self.maps.pop();
+            self.forget_providers_from(self.maps.len());
+            ret
         }
     }
 }
 
-impl<K, Q: ?Sized, V, S> Index<&Q> for ChainMap<K, V, S>
+impl<K: Hash + Eq, V, S: BuildHasher> ChainMap<K, V, S>
 where
-    K: Eq + Hash + Borrow<Q>,
-    Q: Eq + Hash,
+    K: Hash + Eq,
     S: BuildHasher,
 {
-    type Output = V;
+    /// Creates a chain with `map` as its only layer. Every scope pushed
+    /// later uses a clone of the map's hasher.
+    pub fn new(map: HashMap<K, V, S>) -> Self
+    where
+        S: Clone,
+    {
+        let mut ret = Self::from_maps(vec![map]);
+        ret.new_layer = Some(|map| HashMap::with_hasher(map.hasher().clone()));
+        ret
+    }
 
-    /// Returns a reference to the value corresponding to the supplied key.
+    /// Creates a chain with a single empty layer using `hasher`, which is
+    /// cloned into every scope pushed later.
+    pub fn with_hasher(hasher: S) -> Self
+    where
+        S: Clone,
+    {
+        Self::new(HashMap::with_hasher(hasher))
+    }
+
+    /// Inserts a key-value pair into the current scope, explicitly allowing
+    /// it to shadow a binding in an outer scope regardless of the
+    /// shadowing policy.
+    pub fn insert_shadowing(&mut self, key: K, value: V) -> Option<V> {
+        self.insert(key, value)
+    }
+
+    /// Inserts a key-value pair into the current scope, honoring the
+    /// shadowing policy.
     ///
-    /// # Panics
+    /// Under [`ShadowPolicy::Deny`] this fails with `Error::Shadowing` if the
+    /// key is not bound in the current scope but is bound in an outer one,
+    /// leaving the chain untouched. Under [`ShadowPolicy::Warn`] the insert
+    /// succeeds and reports [`Insertion::Shadowed`].
     ///
-    /// Panics if the key is not present in the `HashMap`.
-    #[inline]
-    fn index(&self, key: &Q) -> &V {
-        self.get(key).expect("no entry found for key")
+    /// The pair is also checked by the validator, if one is set, failing
+    /// with `Error::Invalid` if it is rejected.
+    pub fn insert_checked(&mut self, key: K, value: V) -> Result<Insertion<V>, crate::Error> {
+        let top = self
+            .maps
+            .len()
+            .checked_sub(1)
+            .ok_or(crate::Error::IndexOutOfRange)?;
+        self.validate(&key, &value)?;
+        let shadows = !self.layer(top).contains_key(&key)
+            && self.layers().take(top).any(|m| m.contains_key(&key));
+        if shadows && self.shadow_policy == ShadowPolicy::Deny {
+            return Err(crate::Error::Shadowing);
+        }
+        Ok(match self.layer_mut(top).insert(key, value) {
+            Some(old) => Insertion::Replaced(old),
+            None if shadows && self.shadow_policy == ShadowPolicy::Warn => Insertion::Shadowed,
+            None => Insertion::Vacant,
+        })
     }
-}
 
-impl<K, V, S> PartialEq for ChainMap<K, V, S>
-where
-    K: Eq + Hash,
-    V: PartialEq,
-    S: std::hash::BuildHasher,
-{
-    fn eq(&self, other: &ChainMap<K, V, S>) -> bool {
-        self.maps == other.maps
+    /// Sets a check that every pair must pass before it is inserted by
+    /// [`insert_checked`](Self::insert_checked) or
+    /// [`insert_at_checked`](Self::insert_at_checked). Plain inserts are
+    /// not validated.
+    pub fn set_validator<F, E>(&mut self, f: F)
+    where
+        F: Fn(&K, &V) -> Result<(), E> + Send + Sync + 'static,
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        self.validator = Some(Arc::new(move |k, v| f(k, v).map_err(Into::into)));
     }
-}
 
-impl<K, V, S> Eq for ChainMap<K, V, S>
-where
-    K: Eq + Hash,
-    V: Eq,
-    S: BuildHasher,
-{
-}
+    pub fn clear_validator(&mut self) {
+        self.validator = None;
+    }
 
-impl<K, V, S> core::fmt::Debug for ChainMap<K, V, S>
-where
-    K: Eq + Hash + core::fmt::Debug,
-    V: core::fmt::Debug,
-    S: BuildHasher,
-{
-    fn fmt(&self, f: &mut core::fmt::Formatter) -> std::fmt::Result {
-        f.debug_struct("ChainMap")
-            .field("maps", &self.maps)
-            .finish()
+    /// Marks the keys matching `f` as sensitive, so the chain's `Debug`
+    /// output prints `«redacted»` in place of their values.
+    pub fn set_redaction<F>(&mut self, f: F)
+    where
+        F: Fn(&K) -> bool + Send + Sync + 'static,
+    {
+        self.redactor = Some(Arc::new(f));
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use std::default::Default;
+    pub fn clear_redaction(&mut self) {
+        self.redactor = None;
+    }
 
-    #[test]
-    fn initialization() {
-        let mut test_map = HashMap::new();
-        test_map.insert("test", 1);
-        let chain_map = ChainMap::new(test_map);
+    /// Starts recording the keys that lookups resolve from outside the
+    /// innermost `depth` scopes, e.g. the free variables a closure body
+    /// reads, see [`captured_keys`](Self::captured_keys).
+    ///
+    /// Only `get` and the bounded lookups built on `get_in` are recorded.
+    pub fn start_tracking(&mut self, depth: usize)
+    where
+        K: Clone,
+    {
+        self.tracker = Some(ReadTracker::new(depth));
+    }
 
-        assert!(chain_map.maps.len() > 0);
-        assert_eq!(chain_map.maps[0].get("test"), Some(&1));
+    /// The keys recorded since tracking started, empty if it isn't on.
+    pub fn captured_keys(&self) -> HashSet<K>
+    where
+        K: Clone,
+    {
+        match &self.tracker {
+            Some(tracker) => tracker.captured().clone(),
+            None => HashSet::default(),
+        }
     }
 
-    #[test]
-    fn initialization_default() {
-        let chain_map: ChainMap<(), ()> = ChainMap::default();
+    /// Stops recording reads, returning the keys recorded.
+    pub fn stop_tracking(&mut self) -> HashSet<K> {
+        match self.tracker.take() {
+            Some(tracker) => tracker.into_captured(),
+            None => HashSet::default(),
+        }
+    }
 
-        assert!(chain_map.maps.len() > 0);
-        assert!(chain_map.maps[0].is_empty());
+    fn validate(&self, key: &K, value: &V) -> Result<(), crate::Error> {
+        match &self.validator {
+            Some(validator) => validator(key, value).map_err(crate::Error::Invalid),
+            None => Ok(()),
+        }
     }
 
-    #[test]
-    fn insert() {
-        let mut chain_map = ChainMap::default();
-        assert!(chain_map.insert("test", 1).is_none());
+    pub fn shadow_policy(&self) -> ShadowPolicy {
+        self.shadow_policy
+    }
 
-        assert_eq!(chain_map.maps[0].get("test"), Some(&1));
+    pub fn set_shadow_policy(&mut self, policy: ShadowPolicy) {
+        self.shadow_policy = policy;
     }
 
-    #[test]
+    pub fn write_policy(&self) -> WritePolicy {
+        self.write_policy
+    }
+
+    /// Sets the policy used by [`get_mut_for_write`](Self::get_mut_for_write)
+    /// and [`assign`](Self::assign). Plain `get_mut` always writes through.
+    pub fn set_write_policy(&mut self, policy: WritePolicy) {
+        self.write_policy = policy;
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key,
+    /// following the chain's write policy.
+    pub fn get_mut_for_write<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q> + Clone,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.get_mut_with(self.write_policy, key)
+    }
+
+    /// Returns a mutable reference to the value corresponding to the key,
+    /// following `policy` rather than the chain's write policy.
+    ///
+    /// With [`WritePolicy::CopyUp`], a binding found in an outer scope is
+    /// first cloned into the current scope and a reference to the copy is
+    /// returned.
+    pub fn get_mut_with<Q>(&mut self, policy: WritePolicy, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q> + Clone,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        match policy {
+            WritePolicy::WriteThrough => self.get_mut(key),
+            WritePolicy::CopyUp => {
+                let top = self.maps.len().checked_sub(1)?;
+                let idx = self.get_last_index(key)?;
+                if idx != top {
+                    let (k, v) = self.layer(idx).get_key_value(key)?;
+                    let (k, v) = (k.clone(), v.clone());
+                    self.layer_mut(top).insert(k, v);
+                }
+                self.layer_mut(top).get_mut(key)
+            }
+        }
+    }
+
+    /// Copies the nearest binding of the key into the current scope and
+    /// returns a mutable reference to the copy, so a local override can
+    /// start from the inherited value. If the current scope already binds
+    /// the key, that binding is returned unchanged.
+    pub fn promote<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q> + Clone,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        self.get_mut_with(WritePolicy::CopyUp, key)
+    }
+
+    /// Moves the innermost binding of the key into the outermost scope,
+    /// returning the value it replaces there, if any. Bindings in the
+    /// scopes in between are left alone. Fails with `Error::KeyNotFound` if
+    /// no scope binds the key.
+    pub fn hoist<Q>(&mut self, key: &Q) -> Result<Option<V>, crate::Error>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = self.get_last_index(key).ok_or(crate::Error::KeyNotFound)?;
+        if idx == 0 {
+            return Ok(None);
+        }
+        let (k, v) = self
+            .layer_mut(idx)
+            .remove_entry(key)
+            .ok_or(crate::Error::KeyNotFound)?;
+        Ok(self.layer_mut(0).insert(k, v))
+    }
+
+    /// Renames `old` to `new` in every layer that binds `old`, returning
+    /// the number of layers changed. `on_collision` decides what happens in
+    /// layers that already bind `new`; with [`RenameCollision::Fail`] the
+    /// chain is left untouched if any layer would collide.
+    pub fn rename_key<Q>(
+        &mut self,
+        old: &Q,
+        new: K,
+        on_collision: RenameCollision,
+    ) -> Result<usize, crate::Error>
+    where
+        K: Borrow<Q> + Clone,
+        Q: Hash + Eq + ?Sized,
+    {
+        if on_collision == RenameCollision::Fail
+            && self
+                .layers()
+                .any(|m| m.contains_key(old) && m.contains_key::<K>(&new) && new.borrow() != old)
+        {
+            return Err(crate::Error::KeyCollision);
+        }
+        let mut renamed = 0;
+        for i in 0..self.maps.len() {
+            if !self.layer(i).contains_key(old) {
+                continue;
+            }
+            let map = self.layer_mut(i);
+            if map.contains_key::<K>(&new) && on_collision == RenameCollision::Skip {
+                continue;
+            }
+            if let Some(value) = map.remove(old) {
+                map.insert(new.clone(), value);
+                renamed += 1;
+            }
+        }
+        Ok(renamed)
+    }
+
+    /// Replaces the value of an existing binding following the chain's
+    /// write policy, returning the value that was visible before. Fails
+    /// with `Error::KeyNotFound` if no scope binds the key.
+    pub fn assign<Q>(&mut self, key: &Q, value: V) -> Result<V, crate::Error>
+    where
+        K: Borrow<Q> + Clone,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        let slot = self
+            .get_mut_for_write(key)
+            .ok_or(crate::Error::KeyNotFound)?;
+        Ok(std::mem::replace(slot, value))
+    }
+
+    /// Like [`insert_at`](Self::insert_at), but the pair must first pass
+    /// the validator, see [`set_validator`](Self::set_validator).
+    pub fn insert_at_checked(
+        &mut self,
+        idx: usize,
+        key: K,
+        value: V,
+    ) -> Result<Option<V>, crate::Error> {
+        if idx >= self.maps.len() {
+            return Err(crate::Error::IndexOutOfRange);
+        }
+        self.validate(&key, &value)?;
+        self.insert_at(idx, key, value)
+    }
+
+    /// Inserts every pair into the layer at `idx`, e.g. to reload a
+    /// defaults layer after the chain is built. Nothing is inserted if the
+    /// index is out of range.
+    pub fn extend_at<I>(&mut self, idx: usize, iter: I) -> Result<(), crate::Error>
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        if idx >= self.maps.len() {
+            return Err(crate::Error::IndexOutOfRange);
+        }
+        self.layer_mut(idx).extend(iter);
+        Ok(())
+    }
+
+    /// Inserts every pair into the current scope, reserving room for them
+    /// up front. Returns the number of existing bindings that were
+    /// replaced.
+    pub fn insert_many<I>(&mut self, iter: I) -> usize
+    where
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let top = match self.maps.len().checked_sub(1) {
+            Some(top) => top,
+            None => return 0,
+        };
+        let iter = iter.into_iter();
+        let map = self.layer_mut(top);
+        map.reserve(iter.size_hint().0);
+        let mut replaced = 0;
+        for (k, v) in iter {
+            if map.insert(k, v).is_some() {
+                replaced += 1;
+            }
+        }
+        replaced
+    }
+
+    /// Calls `update` on the nearest binding of the key, or binds it to
+    /// the result of `insert` in the innermost scope if it is unbound, in a
+    /// single walk of the chain. Only the layer holding the binding is
+    /// materialized.
+    ///
+    /// Fails with `Error::IndexOutOfRange`, without calling either
+    /// closure, if the chain has no layers.
+    pub fn update_or_insert<U, I>(&mut self, key: K, update: U, insert: I) -> Result<(), crate::Error>
+    where
+        U: FnOnce(&mut V),
+        I: FnOnce() -> V,
+    {
+        if let Some(value) = self.get_in_mut(LookupBounds::All, &key) {
+            update(value);
+            return Ok(());
+        }
+        let top = self.maps.len().checked_sub(1).ok_or(crate::Error::IndexOutOfRange)?;
+        self.layer_mut(top).insert(key, insert());
+        Ok(())
+    }
+
+    /// Inserts `map` as the new outermost layer, beneath every existing
+    /// scope. The index of every existing layer grows by one.
+    ///
+    /// This shifts every layer of the chain and renumbers its lazy and
+    /// provider-backed layers, so it costs O(layers) rather than the O(1)
+    /// of [`new_child_with`](Self::new_child_with). It is meant for
+    /// building a chain up from the outside, not for use in a hot loop.
+    pub fn prepend_parent(&mut self, map: HashMap<K, V, S>) {
+        self.maps.insert(0, map);
+        for layer in &mut self.lazy {
+            layer.idx += 1;
+        }
+        for layer in &mut self.providers {
+            layer.idx += 1;
+        }
+    }
+
+    /// Removes the outermost layer, the counterpart to
+    /// [`prepend_parent`](Self::prepend_parent). Returns `None` without
+    /// changing the chain if it only has one layer. The index of every
+    /// remaining layer shrinks by one.
+    pub fn pop_parent(&mut self) -> Option<HashMap<K, V, S>> {
+        if self.maps.len() < 2 {
+            return None;
+        }
+        self.layer_mut(0);
+        let ret = self.maps.remove(0);
+        self.providers.retain(|p| p.idx != 0);
+        for layer in &mut self.lazy {
+            layer.idx -= 1;
+        }
+        for layer in &mut self.providers {
+            layer.idx -= 1;
+        }
+        Some(ret)
+    }
+
+    /// Pushes an empty child layer using `hasher`, with room for at least
+    /// `n` entries.
+    pub fn new_child_with_capacity_and_hasher(&mut self, n: usize, hasher: S) {
+        self.new_child_with(HashMap::with_capacity_and_hasher(n, hasher));
+    }
+
+    /// Swaps out the whole layer at `idx` in a single step, returning the
+    /// old layer.
+    ///
+    /// If the layer was loaded from a provider, the provider is detached so
+    /// a later [`refresh`](Self::refresh) doesn't overwrite the new layer.
+    pub fn replace_layer(
+        &mut self,
+        idx: usize,
+        map: HashMap<K, V, S>,
+    ) -> Result<HashMap<K, V, S>, crate::Error> {
+        if idx >= self.maps.len() {
+            return Err(crate::Error::IndexOutOfRange);
+        }
+        self.providers.retain(|p| p.idx != idx);
+        Ok(std::mem::replace(self.layer_mut(idx), map))
+    }
+
+    /// Pushes a new child layer populated from `provider`, which is
+    /// re-read in place whenever [`refresh`](Self::refresh) is called.
+    pub fn push_provider<P>(&mut self, provider: P)
+    where
+        P: LayerProvider<K, V, S> + Send + Sync + 'static,
+    {
+        let token = provider.change_token();
+        self.maps.push(provider.load());
+        self.providers.push(ProvidedLayer {
+            idx: self.maps.len() - 1,
+            provider: Arc::new(provider),
+            token,
+        });
+    }
+
+    /// Reloads every provider-backed layer whose change token differs from
+    /// the one seen at the last load, replacing the layer's contents.
+    /// Returns the number of layers that were reloaded.
+    pub fn refresh(&mut self) -> usize {
+        let mut reloaded = 0;
+        for i in 0..self.providers.len() {
+            let layer = &self.providers[i];
+            let (idx, token) = (layer.idx, layer.provider.change_token());
+            if idx >= self.maps.len() || token.is_some() && token == layer.token {
+                continue;
+            }
+            let map = layer.provider.load();
+            *self.layer_mut(idx) = map;
+            self.providers[i].token = token;
+            reloaded += 1;
+        }
+        reloaded
+    }
+
+    /// Sets a handler that computes a value for keys no layer binds.
+    ///
+    /// Only [`get_or_fallback`](Self::get_or_fallback) consults it. `get`,
+    /// `get_mut` and `contains_key` return what the layers hold and never
+    /// call the handler.
+    pub fn set_fallback<F>(&mut self, f: F)
+    where
+        F: Fn(&K) -> Option<V> + Send + Sync + 'static,
+    {
+        self.fallback = Some(Fallback {
+            handler: Arc::new(f),
+            cache: false,
+        });
+    }
+
+    /// Like [`set_fallback`](Self::set_fallback), but values produced by the
+    /// handler are inserted into the outermost layer, so the handler runs
+    /// at most once per key.
+    pub fn set_fallback_cached<F>(&mut self, f: F)
+    where
+        F: Fn(&K) -> Option<V> + Send + Sync + 'static,
+    {
+        self.fallback = Some(Fallback {
+            handler: Arc::new(f),
+            cache: true,
+        });
+    }
+
+    pub fn clear_fallback(&mut self) {
+        self.fallback = None;
+    }
+
+    /// Returns the value corresponding to the key, or the result of the
+    /// fallback handler if no layer binds it. With
+    /// [`set_fallback_cached`](Self::set_fallback_cached) the result is
+    /// bound in the outermost layer before it is returned.
+    pub fn get_or_fallback<Q>(&mut self, key: &Q) -> Option<Cow<'_, V>>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ToOwned<Owned = K> + ?Sized,
+        V: Clone,
+    {
+        if let Some(idx) = self.get_last_index(key) {
+            let (k, v) = self.layer(idx).get_key_value(key)?;
+            self.record_read(idx, k);
+            return Some(Cow::Borrowed(v));
+        }
+        let fallback = self.fallback.clone()?;
+        let key = key.to_owned();
+        let value = (fallback.handler)(&key)?;
+        if !fallback.cache || self.maps.is_empty() {
+            return Some(Cow::Owned(value));
+        }
+        Some(Cow::Borrowed(self.layer_mut(0).entry(key).or_insert(value)))
+    }
+
+    /// Removes interior layers, i.e. every layer but the outermost and the
+    /// innermost, that hold no bindings. Lazy layers that have not been
+    /// built yet and provider backed layers are always kept.
+    ///
+    /// If `keep_indices` is `true`, returns a table mapping each old layer
+    /// index to its new index, or to `None` if the layer was removed.
+    pub fn prune_empty_layers(&mut self, keep_indices: bool) -> Option<Vec<Option<usize>>> {
+        let len = self.maps.len();
+        let mut remap = Vec::with_capacity(len);
+        let mut next = 0;
+        for i in 0..len {
+            let keep = i == 0
+                || i + 1 == len
+                || !self.is_materialized(i)
+                || self.providers.iter().any(|p| p.idx == i)
+                || !self.layer(i).is_empty();
+            if keep {
+                remap.push(Some(next));
+                next += 1;
+            } else {
+                remap.push(None);
+            }
+        }
+        self.remap_layers(&remap);
+        if keep_indices {
+            Some(remap)
+        } else {
+            None
+        }
+    }
+
+    /// Removes every layer that fails `f`, which is passed each layer's
+    /// index and contents, returning how many were removed. If every layer
+    /// fails, the innermost one is kept so the chain still has a scope.
+    ///
+    /// Lazy layers are built before being passed to `f`.
+    pub fn retain_layers<F>(&mut self, mut f: F) -> usize
+    where
+        F: FnMut(usize, &HashMap<K, V, S>) -> bool,
+    {
+        let len = self.maps.len();
+        let mut remap = Vec::with_capacity(len);
+        let mut next = 0;
+        for i in 0..len {
+            let keep = f(i, self.layer(i)) || (next == 0 && i + 1 == len);
+            if keep {
+                remap.push(Some(next));
+                next += 1;
+            } else {
+                remap.push(None);
+            }
+        }
+        self.providers.retain(|p| remap[p.idx].is_some());
+        self.remap_layers(&remap);
+        len - next
+    }
+
+    /// Drops the layers mapped to `None`, moving lazy and provider backed
+    /// layers to their new indices.
+    fn remap_layers(&mut self, remap: &[Option<usize>]) {
+        let mut idx = 0;
+        self.maps.retain(|_| {
+            idx += 1;
+            remap[idx - 1].is_some()
+        });
+        self.lazy.retain(|l| remap[l.idx].is_some());
+        for layer in &mut self.lazy {
+            layer.idx = remap[layer.idx].unwrap();
+        }
+        for layer in &mut self.providers {
+            layer.idx = remap[layer.idx].unwrap();
+        }
+    }
+
+    /// Reserves room for at least `additional` more entries in the current
+    /// scope, failing with `Error::Allocation` instead of aborting if the
+    /// memory can't be allocated.
+    pub fn try_reserve(&mut self, additional: usize) -> Result<(), crate::Error> {
+        let idx = self.maps.len().saturating_sub(1);
+        self.try_reserve_at(idx, additional)
+    }
+
+    /// Like [`try_reserve`](Self::try_reserve), for the layer at `idx`.
+    pub fn try_reserve_at(&mut self, idx: usize, additional: usize) -> Result<(), crate::Error> {
+        if idx >= self.maps.len() {
+            return Err(crate::Error::IndexOutOfRange);
+        }
+        self.layer_mut(idx)
+            .try_reserve(additional)
+            .map_err(crate::Error::Allocation)
+    }
+
+    /// Iterates over the keys bound in the layer at `idx`.
+    pub fn keys_at(&self, idx: usize) -> Result<impl Iterator<Item = &K>, crate::Error> {
+        if idx < self.maps.len() {
+            Ok(self.layer(idx).keys())
+        } else {
+            Err(crate::Error::IndexOutOfRange)
+        }
+    }
+
+    /// Iterates over the values bound in the layer at `idx`.
+    pub fn values_at(&self, idx: usize) -> Result<impl Iterator<Item = &V>, crate::Error> {
+        if idx < self.maps.len() {
+            Ok(self.layer(idx).values())
+        } else {
+            Err(crate::Error::IndexOutOfRange)
+        }
+    }
+
+    /// Iterates over every binding in the chain's layers, including
+    /// shadowed ones, starting with the outermost layer.
+    ///
+    /// Every binding of a layer is yielded before any binding of the next
+    /// layer, in the layer's own iteration order. Since inner layers come
+    /// last, collecting into a map keeps the visible binding of each key.
+    pub fn iter_outer_first(&self) -> IterAll<'_, K, V, S> {
+        IterAll::new(self, false)
+    }
+
+    /// Like [`iter_outer_first`](Self::iter_outer_first), starting with the
+    /// innermost layer, so the first binding seen for each key is the
+    /// visible one.
+    pub fn iter_inner_first(&self) -> IterAll<'_, K, V, S> {
+        IterAll::new(self, true)
+    }
+
+    /// Returns `true` if any visible binding has the value. Bindings
+    /// shadowed by an inner scope are not considered.
+    pub fn contains_value(&self, value: &V) -> bool
+    where
+        V: PartialEq,
+    {
+        self.visible().any(|(_, v)| v == value)
+    }
+
+    /// Returns every key whose visible binding has the value.
+    pub fn keys_with_value(&self, value: &V) -> Vec<&K>
+    where
+        V: PartialEq,
+    {
+        self.visible()
+            .filter(|(_, v)| *v == value)
+            .map(|(k, _)| k)
+            .collect()
+    }
+
+    /// The visible binding of every key, innermost scope first.
+    fn visible(&self) -> impl Iterator<Item = (&K, &V)> {
+        let mut seen = Seen::with_capacity(0);
+        self.layers()
+            .rev()
+            .flat_map(|map| map.iter())
+            .filter(move |(k, _)| seen.insert(*k))
+    }
+
+    /// Iterates over the visible binding of every key, in key order.
+    pub fn iter_sorted(&self) -> impl Iterator<Item = (&K, &V)>
+    where
+        K: Ord,
+    {
+        let mut sorted = BTreeMap::new();
+        for map in self.layers() {
+            sorted.extend(map.iter());
+        }
+        sorted.into_iter()
+    }
+
+    /// Returns the keys visible through the chain, in order.
+    pub fn keys_sorted(&self) -> Vec<&K>
+    where
+        K: Ord,
+    {
+        let mut keys: Vec<_> = self.effective_keys().into_iter().collect();
+        keys.sort_unstable();
+        keys
+    }
+
+    /// Returns every key that is bound in more than one scope, along with
+    /// the indices of the scopes whose binding is shadowed, in ascending
+    /// order. The innermost binding of a key is never listed.
+    pub fn shadowed_keys(&self) -> HashMap<&K, Vec<usize>> {
+        let mut seen = HashSet::new();
+        let mut ret: HashMap<&K, Vec<usize>> = HashMap::new();
+        for (i, map) in self.layers().enumerate().rev() {
+            for k in map.keys() {
+                if !seen.insert(k) {
+                    ret.entry(k).or_default().push(i);
+                }
+            }
+        }
+        for scopes in ret.values_mut() {
+            scopes.reverse();
+        }
+        ret
+    }
+
+    /// Removes every binding that is shadowed by an inner scope, returning
+    /// the number of bindings removed. Lookups are unaffected.
+    pub fn dedup(&mut self) -> usize {
+        self.dedup_before(self.maps.len())
+    }
+
+    /// Like [`dedup`](Self::dedup), but only removes shadowed bindings from
+    /// the layers strictly below `idx`.
+    pub fn dedup_before(&mut self, idx: usize) -> usize {
+        self.force_lazy();
+        let mut removed = 0;
+        for i in LookupBounds::Before(idx).range(self.maps.len()) {
+            let (map, inner) = self.maps[i..].split_first_mut().unwrap();
+            let before = map.len();
+            map.retain(|k, _| !inner.iter().any(|m| m.contains_key(k)));
+            removed += before - map.len();
+        }
+        removed
+    }
+
+    /// Calls `f` once for every visible key with a mutable reference to
+    /// its value, innermost scope first. Shadowed values are not visited.
+    pub fn for_each_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&K, &mut V),
+    {
+        self.force_lazy();
+        let mut seen = Seen::with_capacity(0);
+        for map in self.maps.iter_mut().rev() {
+            for (k, v) in map.iter_mut() {
+                if seen.insert(k) {
+                    f(k, v);
+                }
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default> ChainMap<K, V, S> {
+    /// Creates a single layer chain with every key in `keys` bound to a
+    /// clone of `value`.
+    pub fn from_keys<I>(keys: I, value: V) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        V: Clone,
+    {
+        Self::from_keys_with(keys, || value.clone())
+    }
+
+    /// Creates a single layer chain with every key in `keys` bound to the
+    /// result of calling `f`.
+    pub fn from_keys_with<I, F>(keys: I, mut f: F) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        F: FnMut() -> V,
+    {
+        Self::from_maps(vec![keys.into_iter().map(|k| (k, f())).collect()])
+    }
+
+    /// Creates a chain with `depth` empty layers, so the scope indices up
+    /// to `depth - 1` are valid before anything is inserted. A depth of
+    /// zero is treated as one, since a chain always has a layer.
+    pub fn with_depth(depth: usize) -> Self {
+        Self::from_maps((0..depth.max(1)).map(|_| HashMap::default()).collect())
+    }
+
+    /// Like [`with_depth`](Self::with_depth), with one layer per entry of
+    /// `capacities`, each with room for that many entries.
+    pub fn with_capacities<I>(capacities: I) -> Self
+    where
+        I: IntoIterator<Item = usize>,
+    {
+        let mut maps: Vec<_> = capacities
+            .into_iter()
+            .map(|n| HashMap::with_capacity_and_hasher(n, S::default()))
+            .collect();
+        if maps.is_empty() {
+            maps.push(HashMap::default());
+        }
+        Self::from_maps(maps)
+    }
+
+    /// Creates a chain with one layer per map, outermost first, e.g. from
+    /// deserialized config files. An empty `layers` gives a chain with a
+    /// single empty layer.
+    pub fn with_layers<I>(layers: I) -> Self
+    where
+        I: IntoIterator<Item = HashMap<K, V, S>>,
+    {
+        let mut maps: Vec<_> = layers.into_iter().collect();
+        if maps.is_empty() {
+            maps.push(HashMap::default());
+        }
+        Self::from_maps(maps)
+    }
+
+    /// Inserts an empty layer beneath every existing scope, see
+    /// [`prepend_parent`](Self::prepend_parent).
+    pub fn new_parent(&mut self) {
+        let map = self.empty_layer();
+        self.prepend_parent(map);
+    }
+
+    /// Like [`new_child`](Self::new_child), with room for at least `n`
+    /// entries in the new layer.
+    pub fn new_child_with_capacity(&mut self, n: usize) {
+        let mut map = self.pool.pop().unwrap_or_else(|| self.empty_layer());
+        map.reserve(n);
+        self.maps.push(map);
+    }
+
+    /// Removes the innermost layer like [`remove_child`](Self::remove_child),
+    /// but instead of returning it, clears it and keeps it for reuse by
+    /// the next [`new_child`](Self::new_child), preserving its capacity.
+    /// Returns `false` if there was no layer to remove.
+    pub fn discard_child(&mut self) -> bool {
+        match self.remove_child() {
+            Some(mut map) => {
+                map.clear();
+                self.pool.push(map);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the innermost layer like [`remove_child`](Self::remove_child),
+    /// moving its contents into `buf` instead of returning a new map. `buf`
+    /// is cleared first, and its old allocation is kept for reuse by the
+    /// next [`new_child`](Self::new_child).
+    pub fn remove_child_into(&mut self, buf: &mut HashMap<K, V, S>) {
+        buf.clear();
+        if let Some(top) = self.maps.len().checked_sub(1) {
+            std::mem::swap(self.layer_mut(top), buf);
+            self.discard_child();
+        }
+    }
+
+    /// Declares `key` in the current scope, failing with the existing
+    /// binding if the current scope already declares it. Bindings in outer
+    /// scopes are shadowed as with [`insert`](Self::insert). A chain left
+    /// without any scope, e.g. by [`split_off(0)`](Self::split_off), gets
+    /// an empty one first.
+    pub fn declare(&mut self, key: K, value: V) -> Result<(), AlreadyDeclared<'_, V>> {
+        self.ensure_depth(1);
+        let layer = self.maps.len() - 1;
+        match self.layer_mut(layer).entry(key) {
+            Entry::Occupied(entry) => Err(AlreadyDeclared {
+                layer,
+                existing: entry.into_mut(),
+                value,
+            }),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns the value bound to the key, first binding it to
+    /// `V::default()` in the innermost scope if it is unbound anywhere.
+    pub fn get_or_default(&mut self, key: K) -> &V
+    where
+        V: Default,
+    {
+        self.get_or_default_mut(key)
+    }
+
+    /// Returns a mutable reference to the value bound to the key, first
+    /// binding it to `V::default()` in the innermost scope if no scope of
+    /// the chain binds it. A chain left without any scope gets an empty
+    /// one first.
+    pub fn get_or_default_mut(&mut self, key: K) -> &mut V
+    where
+        V: Default,
+    {
+        self.ensure_depth(1);
+        let idx = self.get_last_index(&key).unwrap_or(self.maps.len() - 1);
+        self.layer_mut(idx).entry(key).or_default()
+    }
+
+    /// Drops every layer kept for reuse by [`discard_child`](Self::discard_child).
+    pub fn clear_pool(&mut self) {
+        self.pool.clear();
+    }
+
+    /// Pushes empty children until the chain has at least `depth` layers,
+    /// returning the number of layers added.
+    pub fn ensure_depth(&mut self, depth: usize) -> usize {
+        let added = depth.saturating_sub(self.maps.len());
+        for _ in 0..added {
+            self.new_child();
+        }
+        added
+    }
+
+    /// Pushes a child layer whose contents are built by `f` only once a
+    /// lookup reaches it. Lookups that are satisfied by an inner layer
+    /// never call `f`.
+    pub fn new_child_lazy<F>(&mut self, f: F)
+    where
+        F: Fn() -> HashMap<K, V, S> + Send + Sync + 'static,
+    {
+        self.lazy.push(LazyLayer::new(self.maps.len(), Arc::new(f)));
+        let placeholder = self.empty_layer();
+        self.maps.push(placeholder);
+    }
+
+    pub fn split_off(&mut self, idx: usize) -> Self {
+        let maps = self.maps.split_off(idx);
+        let mut ret = Self::from_maps(maps);
+        ret.shadow_policy = self.shadow_policy;
+        ret.validator = self.validator.clone();
+        ret.redactor = self.redactor.clone();
+        ret.write_policy = self.write_policy;
+        ret.fallback = self.fallback.clone();
+        ret.tracker = self.tracker.as_ref().map(ReadTracker::restart);
+        ret.new_layer = self.new_layer;
+        for layer in self.providers.iter().filter(|p| p.idx >= idx) {
+            let mut layer = layer.clone();
+            layer.idx -= idx;
+            ret.providers.push(layer);
+        }
+        self.forget_providers_from(idx);
+        let (lazy, kept) = take(&mut self.lazy).into_iter().partition(|l| l.idx >= idx);
+        self.lazy = kept;
+        ret.lazy = lazy;
+        for layer in &mut ret.lazy {
+            layer.idx -= idx;
+        }
+        ret
+    }
+
+    pub fn append(&mut self, other: &mut Self) {
+        let offset = self.maps.len();
+        for mut layer in other.providers.drain(..) {
+            layer.idx += offset;
+            self.providers.push(layer);
+        }
+        for mut layer in other.lazy.drain(..) {
+            layer.idx += offset;
+            self.lazy.push(layer);
+        }
+        self.maps.append(&mut other.maps);
+    }
+
+    /// Pushes every layer of `other` on top of this chain, like
+    /// [`append`](Self::append). Only the layers are adopted, not the
+    /// policies or validator of `other`.
+    pub fn adopt(&mut self, mut other: Self) {
+        self.append(&mut other);
+    }
+
+    /// Inserts every layer of `other` at `idx`, so its outermost layer
+    /// ends up at `idx` and the layers previously at or above `idx` move
+    /// up past the adopted ones.
+    pub fn adopt_at(&mut self, idx: usize, mut other: Self) -> Result<(), crate::Error> {
+        if idx > self.maps.len() {
+            return Err(crate::Error::IndexOutOfRange);
+        }
+        let count = other.maps.len();
+        for layer in &mut self.lazy {
+            if layer.idx >= idx {
+                layer.idx += count;
+            }
+        }
+        for layer in &mut self.providers {
+            if layer.idx >= idx {
+                layer.idx += count;
+            }
+        }
+        for mut layer in other.providers.drain(..) {
+            layer.idx += idx;
+            self.providers.push(layer);
+        }
+        for mut layer in other.lazy.drain(..) {
+            layer.idx += idx;
+            self.lazy.push(layer);
+        }
+        self.maps.splice(idx..idx, other.maps);
+        Ok(())
+    }
+}
+
+impl<K, V, S> ChainMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    /// Takes a snapshot of every scope in the chain that can be cheaply
+    /// cloned and later re-entered with [`from_captured`](Self::from_captured).
+    ///
+    /// No scope is copied: the chain and the snapshot share its scopes
+    /// until the chain next writes to one of them, at which point that
+    /// scope alone is copied if the snapshot is still alive.
+    pub fn capture(&mut self) -> CapturedEnv<K, V, S> {
+        CapturedEnv {
+            maps: (0..self.maps.len()).map(|idx| self.share_layer(idx)).collect(),
+        }
+    }
+
+    /// Creates a chain whose outer scopes are the captured ones, with
+    /// `map` as its innermost scope.
+    ///
+    /// The captured scopes are shared with `env` rather than copied. A
+    /// write to one of them copies it into the chain first, so the
+    /// environment itself never changes.
+    pub fn from_captured(env: CapturedEnv<K, V, S>, map: HashMap<K, V, S>) -> Self {
+        let mut maps = Vec::with_capacity(env.maps.len() + 1);
+        let mut lazy = Vec::with_capacity(env.maps.len());
+        for (idx, shared) in env.maps.iter().enumerate() {
+            maps.push(HashMap::with_hasher(shared.hasher().clone()));
+            lazy.push(LazyLayer::shared(idx, Arc::clone(shared)));
+        }
+        maps.push(map);
+        let mut ret = Self::from_maps(maps);
+        ret.lazy = lazy;
+        ret.new_layer = Some(|map| HashMap::with_hasher(map.hasher().clone()));
+        ret
+    }
+
+    /// Moves the layer at `idx` behind an `Arc` shared with the chain,
+    /// unless it already is.
+    fn share_layer(&mut self, idx: usize) -> Arc<HashMap<K, V, S>> {
+        if let Some(shared) = self
+            .lazy
+            .iter()
+            .find(|l| l.idx == idx)
+            .and_then(LazyLayer::as_shared)
+        {
+            return Arc::clone(shared);
+        }
+        let empty = HashMap::with_hasher(self.maps[idx].hasher().clone());
+        let map = Arc::new(std::mem::replace(self.layer_mut(idx), empty));
+        self.lazy.push(LazyLayer::shared(idx, Arc::clone(&map)));
+        map
+    }
+}
+
+impl<K, V, S> Default for ChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: Default,
+{
+    fn default() -> Self {
+        Self::from_maps(vec![HashMap::default()])
+    }
+}
+
+impl<K, Q: ?Sized, V, S> Index<&Q> for ChainMap<K, V, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `HashMap`.
+    #[inline]
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K, Q: ?Sized, V, S> Index<(usize, &Q)> for ChainMap<K, V, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash + std::fmt::Debug,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Returns a reference to the value the layer at `idx` binds to the
+    /// supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the layer doesn't exist or doesn't bind the key.
+    #[inline]
+    fn index(&self, (idx, key): (usize, &Q)) -> &V {
+        match self.get_at(idx, key) {
+            Some(value) => value,
+            None => panic!("no entry found for key {:?} in layer {}", key, idx),
+        }
+    }
+}
+
+impl<K, V, S> PartialEq for ChainMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: std::hash::BuildHasher,
+{
+    fn eq(&self, other: &ChainMap<K, V, S>) -> bool {
+        self.maps.len() == other.maps.len() && self.layers().eq(other.layers())
+    }
+}
+
+impl<K, V, S> Eq for ChainMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+impl<K, V, S> core::fmt::Debug for ChainMap<K, V, S>
+where
+    K: Eq + Hash + core::fmt::Debug,
+    V: core::fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> std::fmt::Result {
+        let mut s = f.debug_struct("ChainMap");
+        match &self.redactor {
+            Some(redactor) => s.field(
+                "maps",
+                &self
+                    .layers()
+                    .map(|map| RedactedLayer { map, redactor })
+                    .collect::<Vec<_>>(),
+            ),
+            None => s.field("maps", &self.layers().collect::<Vec<_>>()),
+        };
+        s.finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::default::Default;
+    use std::sync::atomic::{AtomicI32, Ordering};
+
+    #[test]
+    fn send_sync() {
+        fn _assert_send_sync<T: Send + Sync>() {}
+        _assert_send_sync::<ChainMap<String, i32>>();
+        _assert_send_sync::<CapturedEnv<String, i32>>();
+    }
+
+    #[test]
+    fn initialization() {
+        let mut test_map = HashMap::new();
+        test_map.insert("test", 1);
+        let chain_map = ChainMap::new(test_map);
+
+        assert!(chain_map.maps.len() > 0);
+        assert_eq!(chain_map.maps[0].get("test"), Some(&1));
+    }
+
+    #[test]
+    fn initialization_default() {
+        let chain_map: ChainMap<(), ()> = ChainMap::default();
+
+        assert!(chain_map.maps.len() > 0);
+        assert!(chain_map.maps[0].is_empty());
+    }
+
+    #[test]
+    fn insert() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        assert!(chain_map.insert("test", 1).is_none());
+
+        assert_eq!(chain_map.maps[0].get("test"), Some(&1));
+    }
+
+    #[test]
     fn insert_at() {
-        let mut chain_map = ChainMap::default();
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("banana", "milk");
+        chain_map.new_child();
+
+        chain_map.insert_at(0, "strawberry", "soda").unwrap();
+        assert_eq!(chain_map.maps[0].get("strawberry"), Some(&"soda"));
+        assert_eq!(chain_map.maps[1].get("strawberry"), None);
+    }
+
+    #[test]
+    #[should_panic = "IndexOutOfRange"]
+    fn insert_at_out_of_bounds() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
         chain_map.insert("banana", "milk");
         chain_map.new_child();
 
-        chain_map.insert_at(0, "strawberry", "soda").unwrap();
-        assert_eq!(chain_map.maps[0].get("strawberry"), Some(&"soda"));
-        assert_eq!(chain_map.maps[1].get("strawberry"), None);
+        chain_map.insert_at(37, "strawberry", "soda").unwrap();
+    }
+
+    #[test]
+    fn get() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("test", 1);
+
+        assert_eq!(chain_map.get(&"test"), Some(&1));
+    }
+
+    #[test]
+    fn get_none() {
+        let chain_map: ChainMap<&str, ()> = ChainMap::default();
+        assert_eq!(chain_map.get(&"test"), None);
+    }
+
+    #[test]
+    fn get_mut() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("test", 1);
+
+        let test_value = chain_map.get_mut(&"test");
+        assert_eq!(test_value, Some(&mut 1));
+        *test_value.unwrap() += 1;
+        let changed = chain_map.get(&"test");
+        assert_eq!(changed, Some(&2));
+    }
+
+    #[test]
+    fn get_mut_outer() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("outer", 1);
+        chain_map.new_child();
+        chain_map.insert("inner", 2);
+        let ret = chain_map.get_mut("outer").unwrap();
+        *ret += 9000;
+
+        let changed = chain_map.get(&"outer");
+        assert_eq!(changed, Some(&9001));
+    }
+
+    #[test]
+    fn index() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("test", 1);
+
+        assert_eq!(chain_map[&"test"], 1);
+    }
+
+    #[test]
+    fn new_child() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("test", 1);
+        chain_map.new_child();
+        assert!(chain_map.maps.len() > 1);
+    }
+
+    #[test]
+    fn scopes() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.insert("y", 2);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        assert_eq!(chain_map.get("x"), Some(&1));
+        assert_eq!(chain_map.get("y"), Some(&2));
+    }
+
+    #[test]
+    fn remove_child() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.insert("y", 2);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        let ret = chain_map.remove_child().unwrap();
+        assert_eq!(ret.get("x"), Some(&1));
+        assert_eq!(chain_map.get("x"), Some(&0));
+    }
+
+    #[test]
+    fn remove_child_length_1() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        let _ = chain_map.remove_child();
+        assert_eq!(chain_map.get("x"), None);
+        assert!(chain_map.maps.len() == 1);
+    }
+
+    #[test]
+    fn has_at_exists() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+
+        assert!(chain_map.has_at(0, &"x"));
+    }
+
+    #[test]
+    fn has_at_doesnt_exist() {
+        let chain_map: ChainMap<&str, ()> = ChainMap::default();
+
+        assert!(!chain_map.has_at(11, &"x"));
+    }
+
+    #[test]
+    fn last_has_true() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.new_child();
+        chain_map.insert("y", 1);
+
+        assert!(chain_map.last_has(&"y"));
+    }
+
+    #[test]
+    fn last_has_false() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.new_child();
+        chain_map.insert("y", 1);
+
+        assert!(!chain_map.last_has(&"x"));
+    }
+
+    #[test]
+    fn child_len() {
+        let mut chain_map: ChainMap<&str, ()> = ChainMap::default();
+        assert_eq!(chain_map.child_len(), 1);
+
+        for i in 2..100 {
+            chain_map.new_child();
+            assert_eq!(chain_map.child_len(), i);
+        }
+    }
+
+    #[test]
+    fn get_before_exists() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("test", 1);
+        chain_map.new_child();
+        chain_map.insert("test", 2);
+
+        assert_eq!(chain_map.get_before(1, &"test"), Some(&1));
+    }
+
+    #[test]
+    fn get_before_mut_exists() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("test", 1);
+        chain_map.new_child();
+        chain_map.insert("test", 2);
+
+        let test_value = chain_map.get_before_mut(1, &"test");
+        assert_eq!(test_value, Some(&mut 1));
+        *test_value.unwrap() += 2;
+        let changed = chain_map.get_before(1, &"test");
+        assert_eq!(changed, Some(&3));
+        let child = chain_map.get("test");
+        assert_eq!(child, Some(&2));
+    }
+
+    #[test]
+    fn get_last_index_exists() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("test1", 1);
+        chain_map.new_child();
+        chain_map.insert("test2", 2);
+
+        assert_eq!(chain_map.get_last_index("test1"), Some(0));
+        assert_eq!(chain_map.get_last_index("test2"), Some(1));
+    }
+
+    #[test]
+    fn get_last_index_doesnt_exist() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("test1", 1);
+        chain_map.new_child();
+        chain_map.insert("test2", 2);
+
+        assert_eq!(chain_map.get_last_index("shmee"), None);
+    }
+
+    #[test]
+    fn custom_hasher() {
+        // note: this test is about type checking
+        // rather than actual assertions
+        use std::hash::BuildHasherDefault;
+        use hashers::oz::DJB2Hasher; 
+        let hm = HashMap::with_hasher(BuildHasherDefault::<DJB2Hasher>::default());
+        let mut cm = ChainMap::new(hm);
+        cm.insert("test1", 1);
+        cm.new_child();
+        cm.insert("test1", 1);
+        cm.remove_child();
+        cm["test1"];
+    }
+
+    #[test]
+    fn for_each_skips_shadowed() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.insert("y", 2);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        let mut seen = Vec::new();
+        chain_map.for_each(|k, v| seen.push((*k, *v)));
+        seen.sort();
+        assert_eq!(seen, vec![("x", 1), ("y", 2)]);
+
+        let mut seen = Vec::new();
+        chain_map.for_each_with_capacity(2, |k, v| seen.push((*k, *v)));
+        seen.sort();
+        assert_eq!(seen, vec![("x", 1), ("y", 2)]);
+    }
+
+    #[test]
+    fn for_each_past_inline_keys() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        for i in 0..40 {
+            chain_map.insert(i, 0);
+        }
+        chain_map.new_child();
+        for i in 20..60 {
+            chain_map.insert(i, 1);
+        }
+        for capacity in [0, 64] {
+            let mut seen = Vec::new();
+            chain_map.for_each_with_capacity(capacity, |k, v| seen.push((*k, *v)));
+            seen.sort();
+            let expected: Vec<_> = (0..60).map(|i| (i, u8::from(i >= 20))).collect();
+            assert_eq!(seen, expected);
+        }
+    }
+
+    #[test]
+    fn for_each_mut_skips_shadowed() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.insert("y", 2);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        chain_map.for_each_mut(|_, v| *v += 10);
+        assert_eq!(chain_map.maps[0].get("x"), Some(&0));
+        assert_eq!(chain_map.get("x"), Some(&11));
+        assert_eq!(chain_map.get("y"), Some(&12));
+    }
+
+    #[test]
+    fn effective_keys() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.insert("y", 2);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        chain_map.insert("z", 3);
+        let keys = chain_map.effective_keys();
+        assert_eq!(keys.len(), 3);
+        assert!(keys.contains(&"x"));
+        assert!(keys.contains(&"y"));
+        assert!(keys.contains(&"z"));
+    }
+
+    #[test]
+    fn shadowed_keys() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.insert("y", 0);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        chain_map.new_child();
+        chain_map.insert("x", 2);
+        chain_map.insert("z", 2);
+        let shadowed = chain_map.shadowed_keys();
+        assert_eq!(shadowed.len(), 1);
+        assert_eq!(shadowed.get(&"x"), Some(&vec![0, 1]));
+    }
+
+    #[test]
+    fn insert_checked_allow() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.new_child();
+        assert_eq!(chain_map.insert_checked("x", 1).unwrap(), Insertion::Vacant);
+        assert_eq!(chain_map.insert_checked("x", 2).unwrap(), Insertion::Replaced(1));
+    }
+
+    #[test]
+    fn insert_checked_warn() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.set_shadow_policy(ShadowPolicy::Warn);
+        chain_map.insert("x", 0);
+        chain_map.new_child();
+        assert_eq!(chain_map.insert_checked("x", 1).unwrap(), Insertion::Shadowed);
+        assert_eq!(chain_map.get("x"), Some(&1));
+    }
+
+    #[test]
+    fn insert_checked_deny() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.set_shadow_policy(ShadowPolicy::Deny);
+        chain_map.insert("x", 0);
+        chain_map.new_child();
+        assert!(chain_map.insert_checked("x", 1).is_err());
+        assert_eq!(chain_map.insert_checked("y", 1).unwrap(), Insertion::Vacant);
+        assert_eq!(chain_map.get("x"), Some(&0));
+        chain_map.insert_shadowing("x", 2);
+        assert_eq!(chain_map.get("x"), Some(&2));
+    }
+
+    #[test]
+    fn get_after() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("test", 1);
+        chain_map.insert("outer", 1);
+        chain_map.new_child();
+        chain_map.insert("test", 2);
+
+        assert_eq!(chain_map.get_after(1, &"test"), Some(&2));
+        assert_eq!(chain_map.get_after(1, &"outer"), None);
+        assert_eq!(chain_map.get_after(0, &"outer"), Some(&1));
+        assert_eq!(chain_map.get_after(5, &"test"), None);
+    }
+
+    #[test]
+    fn get_before_mut_excludes_idx() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("test", 1);
+        chain_map.new_child();
+        chain_map.insert("test", 2);
+
+        assert_eq!(chain_map.get_before_mut(0, &"test"), None);
+        assert_eq!(chain_map.get_before_mut(1, &"test"), Some(&mut 1));
+        assert_eq!(chain_map.get_in_mut(LookupBounds::All, &"test"), Some(&mut 2));
+    }
+
+    #[test]
+    fn capture() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.new_child();
+        chain_map.insert("y", 1);
+        let env = chain_map.capture();
+        chain_map.insert("y", 2);
+
+        assert_eq!(env.get("y"), Some(&1));
+
+        let mut closure = ChainMap::from_captured(env.clone(), HashMap::new());
+        closure.insert("z", 3);
+        assert_eq!(closure.child_len(), 3);
+        assert_eq!(closure.get("x"), Some(&0));
+        assert_eq!(closure.get("y"), Some(&1));
+        assert_eq!(closure.get("z"), Some(&3));
+        assert_eq!(closure.get_last_index("x"), Some(0));
+        assert_eq!(closure.len(), 3);
+        assert_eq!(closure.keys_sorted(), vec![&"x", &"y", &"z"]);
+        *closure.get_mut("x").unwrap() = 10;
+        assert_eq!(closure.get("x"), Some(&10));
+        assert_eq!(env.get("x"), Some(&0));
+
+        let nested = closure.capture();
+        assert_eq!(nested.child_len(), 3);
+        assert_eq!(nested.get("x"), Some(&10));
+        assert_eq!(env.get("z"), None);
+    }
+
+    #[test]
+    fn capture_shares_layers() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.new_child();
+        let env = chain_map.capture();
+        let again = chain_map.capture();
+        assert!(Arc::ptr_eq(&env.maps[0], &again.maps[0]));
+        let closure = ChainMap::from_captured(env.clone(), HashMap::default());
+        let nested = ChainMap::from_captured(closure.clone().capture(), HashMap::default());
+        assert_eq!(nested.child_len(), 4);
+        chain_map.insert("y", 1);
+        let after_write = chain_map.capture();
+        assert!(Arc::ptr_eq(&env.maps[0], &after_write.maps[0]));
+        assert!(!Arc::ptr_eq(&env.maps[1], &after_write.maps[1]));
+        assert_eq!(env.get("y"), None);
+    }
+
+    #[test]
+    fn get_at_depth() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        chain_map.new_child();
+
+        assert_eq!(chain_map.get_at_depth(0, "x"), None);
+        assert_eq!(chain_map.get_at_depth(1, "x"), Some(&1));
+        assert_eq!(chain_map.get_at_depth(2, "x"), Some(&0));
+        assert_eq!(chain_map.get_at_depth(3, "x"), None);
+    }
+
+    #[test]
+    fn assign_at_depth() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+
+        assert_eq!(chain_map.assign_at_depth(1, "x", 5).unwrap(), Some(0));
+        assert_eq!(chain_map.maps[0].get("x"), Some(&5));
+        assert_eq!(chain_map.get("x"), Some(&1));
+        assert!(chain_map.assign_at_depth(2, "x", 5).is_err());
+    }
+
+    #[test]
+    fn provider_refresh() {
+        struct Counter(Arc<AtomicI32>);
+        impl LayerProvider<&'static str, i32> for Counter {
+            fn load(&self) -> HashMap<&'static str, i32> {
+                let mut map = HashMap::new();
+                map.insert("count", self.0.load(Ordering::Relaxed));
+                map
+            }
+            fn change_token(&self) -> Option<u64> {
+                Some(self.0.load(Ordering::Relaxed) as u64)
+            }
+        }
+        let count = Arc::new(AtomicI32::new(0));
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("count", -1);
+        chain_map.push_provider(Counter(count.clone()));
+        chain_map.new_child();
+        assert_eq!(chain_map.get("count"), Some(&0));
+        assert_eq!(chain_map.refresh(), 0);
+        count.store(5, Ordering::Relaxed);
+        assert_eq!(chain_map.refresh(), 1);
+        assert_eq!(chain_map.get("count"), Some(&5));
+        chain_map.remove_child();
+        chain_map.remove_child();
+        count.store(6, Ordering::Relaxed);
+        assert_eq!(chain_map.refresh(), 0);
+        assert_eq!(chain_map.get("count"), Some(&-1));
+    }
+
+    #[test]
+    fn provider_closure() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.push_provider(|| {
+            let mut map = HashMap::new();
+            map.insert("x", 1);
+            map
+        });
+        chain_map.insert("x", 2);
+        assert_eq!(chain_map.refresh(), 1);
+        assert_eq!(chain_map.get("x"), Some(&1));
+    }
+
+    #[test]
+    fn refresh_after_capture() {
+        let source = Arc::new(AtomicI32::new(0));
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        let provider = Arc::clone(&source);
+        chain_map.push_provider(move || {
+            let mut map = HashMap::new();
+            map.insert("x", provider.load(Ordering::Relaxed));
+            map
+        });
+        let env = chain_map.capture();
+        source.store(1, Ordering::Relaxed);
+        assert_eq!(chain_map.refresh(), 1);
+        assert_eq!(chain_map.get("x"), Some(&1));
+        assert_eq!(env.get("x"), Some(&0));
+    }
+
+    #[test]
+    fn from_keys() {
+        let chain_map: ChainMap<_, _> = ChainMap::from_keys(vec!["a", "b"], None::<i32>);
+        assert_eq!(chain_map.child_len(), 1);
+        assert_eq!(chain_map.get("a"), Some(&None));
+        assert_eq!(chain_map.get("b"), Some(&None));
+
+        let mut next = 0;
+        let chain_map: ChainMap<_, _> = ChainMap::from_keys_with(vec!["a", "b"], || {
+            next += 1;
+            next
+        });
+        assert_eq!(chain_map.get("a").unwrap() + chain_map.get("b").unwrap(), 3);
     }
 
     #[test]
-    #[should_panic = "IndexOutOfRange"]
-    fn insert_at_out_of_bounds() {
-        let mut chain_map = ChainMap::default();
-        chain_map.insert("banana", "milk");
+    fn fallback() {
+        let mut chain_map: ChainMap<String, usize> = ChainMap::default();
+        chain_map.insert("x".to_string(), 0);
         chain_map.new_child();
-
-        chain_map.insert_at(37, "strawberry", "soda").unwrap();
+        assert_eq!(chain_map.get_or_fallback("y"), None);
+        chain_map.set_fallback(|k: &String| Some(k.len()));
+        assert_eq!(chain_map.get_or_fallback("x").unwrap().into_owned(), 0);
+        assert_eq!(chain_map.get_or_fallback("abc").unwrap().into_owned(), 3);
+        assert_eq!(chain_map.get("abc"), None);
+        assert!(!chain_map.contains_key("abc"));
+        let mut split = chain_map.split_off(1);
+        assert_eq!(split.get_or_fallback("abcd").unwrap().into_owned(), 4);
     }
 
     #[test]
-    fn get() {
-        let mut chain_map = ChainMap::default();
-        chain_map.insert("test", 1);
+    fn fallback_cached() {
+        let mut chain_map: ChainMap<String, usize> = ChainMap::default();
+        chain_map.new_child();
+        chain_map.set_fallback_cached(|k: &String| Some(k.len()));
+        assert_eq!(chain_map.get_or_fallback("abc").unwrap().into_owned(), 3);
+        assert_eq!(chain_map.maps[0].get("abc"), Some(&3));
+        assert!(chain_map.contains_key("abc"));
+        chain_map.clear_fallback();
+        assert_eq!(chain_map.get_or_fallback("abcd"), None);
+    }
 
-        assert_eq!(chain_map.get(&"test"), Some(&1));
+    #[test]
+    fn lazy_layer_untouched() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.new_child_lazy(|| panic!("lazy layer built"));
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        assert_eq!(chain_map.get("x"), Some(&1));
+        assert!(!chain_map.is_materialized(1));
+        chain_map.remove_child();
+        assert_eq!(chain_map.child_len(), 2);
     }
 
     #[test]
-    fn get_none() {
-        let chain_map: ChainMap<&str, ()> = ChainMap::default();
-        assert_eq!(chain_map.get(&"test"), None);
+    fn lazy_layer_materialized() {
+        let calls = Arc::new(AtomicI32::new(0));
+        let counter = calls.clone();
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("y", 0);
+        chain_map.new_child_lazy(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+            let mut map = HashMap::new();
+            map.insert("x", 1);
+            map
+        });
+        chain_map.new_child();
+        assert_eq!(chain_map.get("y"), Some(&0));
+        assert!(chain_map.is_materialized(1));
+        assert_eq!(chain_map.get("x"), Some(&1));
+        *chain_map.get_mut("x").unwrap() += 1;
+        assert_eq!(chain_map.get("x"), Some(&2));
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
     }
 
     #[test]
-    fn get_mut() {
-        let mut chain_map = ChainMap::default();
-        chain_map.insert("test", 1);
+    fn total_len() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        assert!(chain_map.is_empty());
+        chain_map.insert("x", 0);
+        chain_map.insert("y", 0);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        assert_eq!(chain_map.len(), 2);
+        assert_eq!(chain_map.total_len(), 3);
+        assert!(!chain_map.is_empty());
+    }
 
-        let test_value = chain_map.get_mut(&"test");
-        assert_eq!(test_value, Some(&mut 1));
-        *test_value.unwrap() += 1;
-        let changed = chain_map.get(&"test");
-        assert_eq!(changed, Some(&2));
+    #[test]
+    fn dedup() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.insert("y", 0);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        chain_map.insert("y", 1);
+        chain_map.new_child();
+        chain_map.insert("x", 2);
+
+        assert_eq!(chain_map.dedup_before(1), 2);
+        assert_eq!(chain_map.total_len(), 3);
+        assert_eq!(chain_map.dedup(), 1);
+        assert_eq!(chain_map.total_len(), 2);
+        assert_eq!(chain_map.get("x"), Some(&2));
+        assert_eq!(chain_map.get("y"), Some(&1));
     }
 
     #[test]
-    fn get_mut_outer() {
-        let mut chain_map = ChainMap::default();
-        chain_map.insert("outer", 1);
+    fn prune_empty_layers() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.new_child();
+        chain_map.new_child();
+        chain_map.insert("x", 2);
+        chain_map.new_child();
         chain_map.new_child();
-        chain_map.insert("inner", 2);
-        let ret = chain_map.get_mut("outer").unwrap();
-        *ret += 9000;
 
-        let changed = chain_map.get(&"outer");
-        assert_eq!(changed, Some(&9001));
+        let remap = chain_map.prune_empty_layers(true).unwrap();
+        assert_eq!(remap, vec![Some(0), None, Some(1), None, Some(2)]);
+        assert_eq!(chain_map.child_len(), 3);
+        assert_eq!(chain_map.get_last_index("x"), Some(1));
+        assert!(chain_map.prune_empty_layers(false).is_none());
     }
 
     #[test]
-    fn index() {
-        let mut chain_map = ChainMap::default();
-        chain_map.insert("test", 1);
-
-        assert_eq!(chain_map[&"test"], 1);
+    fn ensure_depth() {
+        let mut chain_map: ChainMap<&str, ()> = ChainMap::default();
+        assert_eq!(chain_map.ensure_depth(4), 3);
+        assert_eq!(chain_map.child_len(), 4);
+        assert_eq!(chain_map.ensure_depth(2), 0);
+        assert_eq!(chain_map.child_len(), 4);
     }
 
     #[test]
-    fn new_child() {
-        let mut chain_map = ChainMap::default();
-        chain_map.insert("test", 1);
+    fn discard_child_reuses_layer() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
         chain_map.new_child();
-        assert!(chain_map.maps.len() > 1);
+        for i in 0..100 {
+            chain_map.insert(i, i);
+        }
+        let capacity = chain_map.maps[1].capacity();
+        assert!(chain_map.discard_child());
+        assert_eq!(chain_map.child_len(), 1);
+        chain_map.new_child();
+        assert!(chain_map.maps[1].is_empty());
+        assert_eq!(chain_map.maps[1].capacity(), capacity);
+        chain_map.discard_child();
+        chain_map.clear_pool();
+        chain_map.new_child();
+        assert_eq!(chain_map.maps[1].capacity(), 0);
     }
 
     #[test]
-    fn scopes() {
-        let mut chain_map = ChainMap::default();
+    fn write_through() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
         chain_map.insert("x", 0);
-        chain_map.insert("y", 2);
         chain_map.new_child();
+        assert_eq!(chain_map.assign("x", 1).unwrap(), 0);
+        assert_eq!(chain_map.maps[0].get("x"), Some(&1));
+        assert!(!chain_map.last_has("x"));
+        assert!(chain_map.assign("y", 1).is_err());
+    }
+
+    #[test]
+    fn copy_up() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.set_write_policy(WritePolicy::CopyUp);
+        chain_map.insert("x", vec![0]);
+        chain_map.new_child();
+        chain_map.get_mut_for_write("x").unwrap().push(1);
+        assert_eq!(chain_map.get("x"), Some(&vec![0, 1]));
+        assert_eq!(chain_map.maps[0].get("x"), Some(&vec![0]));
+        assert_eq!(chain_map.assign("x", vec![2]).unwrap(), vec![0, 1]);
+        chain_map.remove_child();
+        assert_eq!(chain_map.get("x"), Some(&vec![0]));
+    }
+
+    #[test]
+    fn promote() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
         chain_map.insert("x", 1);
-        assert_eq!(chain_map.get("x"), Some(&1));
-        assert_eq!(chain_map.get("y"), Some(&2));
+        chain_map.new_child();
+        chain_map.new_child();
+        *chain_map.promote("x").unwrap() += 1;
+        assert_eq!(chain_map.get("x"), Some(&2));
+        assert_eq!(chain_map.get_last_index("x"), Some(2));
+        assert_eq!(chain_map.maps[0].get("x"), Some(&1));
+        assert!(chain_map.promote("y").is_none());
+        assert!(!chain_map.last_has("y"));
     }
 
     #[test]
-    fn remove_child() {
-        let mut chain_map = ChainMap::default();
+    fn hoist() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
         chain_map.insert("x", 0);
-        chain_map.insert("y", 2);
         chain_map.new_child();
         chain_map.insert("x", 1);
-        let ret = chain_map.remove_child().unwrap();
-        assert_eq!(ret.get("x"), Some(&1));
-        assert_eq!(chain_map.get("x"), Some(&0));
+        chain_map.insert("y", 1);
+        chain_map.new_child();
+        chain_map.insert("x", 2);
+
+        assert_eq!(chain_map.hoist("x").unwrap(), Some(0));
+        assert_eq!(chain_map.maps[0].get("x"), Some(&2));
+        assert_eq!(chain_map.get("x"), Some(&1));
+        assert_eq!(chain_map.hoist("y").unwrap(), None);
+        assert_eq!(chain_map.get_last_index("y"), Some(0));
+        assert_eq!(chain_map.hoist("y").unwrap(), None);
+        assert!(chain_map.hoist("z").is_err());
     }
 
     #[test]
-    fn remove_child_length_1() {
-        let mut chain_map = ChainMap::default();
+    fn rename_key() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
         chain_map.insert("x", 0);
-        let _ = chain_map.remove_child();
+        chain_map.new_child();
+        chain_map.insert("y", 1);
+        chain_map.new_child();
+        chain_map.insert("x", 2);
+
+        assert_eq!(chain_map.rename_key("x", "z", RenameCollision::Fail).unwrap(), 2);
         assert_eq!(chain_map.get("x"), None);
-        assert!(chain_map.maps.len() == 1);
+        assert_eq!(chain_map.get("z"), Some(&2));
+        assert_eq!(chain_map.maps[0].get("z"), Some(&0));
     }
 
     #[test]
-    fn has_at_exists() {
-        let mut chain_map = ChainMap::default();
+    fn rename_key_collision() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
         chain_map.insert("x", 0);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        chain_map.insert("y", 1);
 
-        assert!(chain_map.has_at(0, &"x"));
+        assert!(chain_map.rename_key("x", "y", RenameCollision::Fail).is_err());
+        assert_eq!(chain_map.maps[0].get("x"), Some(&0));
+        assert_eq!(chain_map.rename_key("x", "y", RenameCollision::Skip).unwrap(), 1);
+        assert_eq!(chain_map.get("x"), Some(&1));
+        assert_eq!(chain_map.maps[0].get("y"), Some(&0));
+        assert_eq!(chain_map.rename_key("x", "y", RenameCollision::Overwrite).unwrap(), 1);
+        assert_eq!(chain_map.get("x"), None);
+        assert_eq!(chain_map.get("y"), Some(&1));
     }
 
     #[test]
-    fn has_at_doesnt_exist() {
-        let chain_map: ChainMap<&str, ()> = ChainMap::default();
+    fn insert_many() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert(0, 0);
+        chain_map.new_child();
+        chain_map.insert(1, 0);
+        assert_eq!(chain_map.insert_many((0..10).map(|i| (i, i))), 1);
+        assert_eq!(chain_map.maps[1].len(), 10);
+        assert_eq!(chain_map.get(&0), Some(&0));
+        assert_eq!(chain_map.get(&1), Some(&1));
+    }
 
-        assert!(!chain_map.has_at(11, &"x"));
+    #[test]
+    fn extend_at() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.new_child();
+        chain_map.extend_at(0, vec![("x", 0), ("y", 0)]).unwrap();
+        assert_eq!(chain_map.maps[0].len(), 2);
+        assert!(chain_map.maps[1].is_empty());
+        assert!(chain_map.extend_at(2, vec![("z", 0)]).is_err());
     }
 
     #[test]
-    fn last_has_true() {
-        let mut chain_map = ChainMap::default();
+    fn sorted_iteration() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("b", 1);
+        chain_map.insert("c", 1);
+        chain_map.new_child();
+        chain_map.insert("a", 2);
+        chain_map.insert("b", 2);
+        assert_eq!(
+            chain_map.iter_sorted().collect::<Vec<_>>(),
+            vec![(&"a", &2), (&"b", &2), (&"c", &1)]
+        );
+        assert_eq!(chain_map.keys_sorted(), vec![&"a", &"b", &"c"]);
+    }
+
+    #[test]
+    fn replace_layer() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("port", 80);
+        chain_map.new_child();
+        chain_map.insert("host", 1);
+        let mut reloaded = HashMap::new();
+        reloaded.insert("port", 8080);
+        let old = chain_map.replace_layer(0, reloaded).unwrap();
+        assert_eq!(old.get("port"), Some(&80));
+        assert_eq!(chain_map.get("port"), Some(&8080));
+        assert_eq!(chain_map.get("host"), Some(&1));
+        assert!(chain_map.replace_layer(2, HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn get_or_default() {
+        let mut chain_map: ChainMap<&str, u32> = ChainMap::default();
+        chain_map.insert("outer", 1);
+        chain_map.new_child();
+        *chain_map.get_or_default_mut("outer") += 1;
+        *chain_map.get_or_default_mut("count") += 1;
+        assert_eq!(*chain_map.get_or_default("missing"), 0);
+        assert!(chain_map.last_has("count"));
+        assert!(chain_map.last_has("missing"));
+        let outer = chain_map.remove_child().unwrap();
+        assert!(!outer.contains_key("outer"));
+        assert_eq!(chain_map.get("outer"), Some(&2));
+        chain_map.split_off(0);
+        assert_eq!(*chain_map.get_or_default("outer"), 0);
+    }
+
+    #[test]
+    fn validator() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.set_validator(|_: &&str, v: &u16| {
+            if *v == 0 {
+                Err("port must not be 0")
+            } else {
+                Ok(())
+            }
+        });
+        chain_map.insert_checked("port", 80).unwrap();
+        let err = chain_map.insert_checked("port", 0).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid value: port must not be 0");
+        assert!(chain_map.insert_at_checked(0, "port", 0).is_err());
+        assert_eq!(chain_map.get("port"), Some(&80));
+        // plain inserts are not validated
+        chain_map.insert("port", 0);
+        chain_map.clear_validator();
+        assert!(chain_map.insert_checked("port", 0).is_ok());
+    }
+
+    #[test]
+    fn redaction() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("user", "admin");
+        chain_map.new_child();
+        chain_map.insert("password", "hunter2");
+        chain_map.set_redaction(|k: &&str| *k == "password");
+        let out = format!("{:?}", chain_map);
+        assert!(!out.contains("hunter2"));
+        assert!(out.contains("\"password\": «redacted»"));
+        assert!(out.contains("\"admin\""));
+        chain_map.clear_redaction();
+        assert!(format!("{:?}", chain_map).contains("hunter2"));
+    }
+
+    #[test]
+    fn with_depth() {
+        let mut chain_map: ChainMap<&str, i32> = ChainMap::with_depth(3);
+        assert_eq!(chain_map.child_len(), 3);
+        assert!(chain_map.insert_at(2, "x", 0).is_ok());
+        assert_eq!(ChainMap::<u8, u8>::with_depth(0).child_len(), 1);
+        let chain_map: ChainMap<u8, u8> = ChainMap::with_capacities([4, 0, 16]);
+        assert_eq!(chain_map.child_len(), 3);
+        assert!(chain_map.maps[2].capacity() >= 16);
+    }
+
+    #[test]
+    fn new_child_with_capacity() {
+        let mut chain_map: ChainMap<&str, i32> = ChainMap::default();
+        chain_map.new_child_with_capacity(32);
+        assert!(chain_map.maps[1].capacity() >= 32);
+        chain_map.new_child_with_capacity_and_hasher(8, RandomState::default());
+        assert!(chain_map.maps[2].capacity() >= 8);
+        assert_eq!(chain_map.child_len(), 3);
+    }
+
+    #[test]
+    fn last_has_without_scopes() {
+        let mut chain_map: ChainMap<&str, i32> = ChainMap::default();
         chain_map.insert("x", 0);
+        chain_map.split_off(0);
+        assert!(!chain_map.last_has("x"));
+    }
+
+    #[test]
+    fn declare() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        assert!(chain_map.declare("f", 0).is_ok());
         chain_map.new_child();
-        chain_map.insert("y", 1);
+        assert!(chain_map.declare("f", 1).is_ok());
+        let err = chain_map.declare("f", 2).unwrap_err();
+        assert_eq!((err.layer, *err.existing, err.value), (1, 1, 2));
+        assert_eq!(chain_map.get("f"), Some(&1));
+        chain_map.split_off(0);
+        assert!(chain_map.declare("f", 3).is_ok());
+        assert_eq!(chain_map.child_len(), 1);
+    }
 
-        assert!(chain_map.last_has(&"y"));
+    #[test]
+    fn remove_at() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        assert_eq!(chain_map.get_at(0, "x"), Some(&0));
+        assert_eq!(chain_map.remove("x"), Some(1));
+        assert!(chain_map.contains_key("x"));
+        assert_eq!(chain_map.remove_at(0, "x").unwrap(), Some(0));
+        assert!(chain_map.remove_at(2, "x").is_err());
+        assert!(!chain_map.contains_key("x"));
     }
 
     #[test]
-    fn last_has_false() {
-        let mut chain_map = ChainMap::default();
+    fn retain_layers() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
         chain_map.insert("x", 0);
         chain_map.new_child();
-        chain_map.insert("y", 1);
+        chain_map.insert("plugin", 1);
+        chain_map.new_child();
+        chain_map.insert("x", 2);
+        let removed = chain_map.retain_layers(|_, layer| !layer.contains_key("plugin"));
+        assert_eq!(removed, 1);
+        assert_eq!(chain_map.child_len(), 2);
+        assert_eq!(chain_map.get("plugin"), None);
+        assert_eq!(chain_map.get_before(1, "x"), Some(&0));
+        assert_eq!(chain_map.retain_layers(|_, _| false), 1);
+        assert_eq!(chain_map.child_len(), 1);
+        assert_eq!(chain_map.get("x"), Some(&2));
+    }
 
-        assert!(!chain_map.last_has(&"x"));
+    #[test]
+    fn update_or_insert() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("count", 1);
+        chain_map.new_child();
+        chain_map.update_or_insert("count", |v| *v += 1, || 0).unwrap();
+        chain_map.update_or_insert("other", |v| *v += 1, || 10).unwrap();
+        assert_eq!(chain_map.get_before(1, "count"), Some(&2));
+        assert!(!chain_map.last_has("count"));
+        assert!(chain_map.last_has("other"));
+        assert_eq!(chain_map.get("other"), Some(&10));
+
+        let _env = chain_map.capture();
+        chain_map.new_child();
+        chain_map.update_or_insert("missing", |v| *v += 1, || 0).unwrap();
+        assert_eq!(chain_map.lazy.len(), 2);
+        assert!(chain_map.lazy.iter().all(|l| l.as_shared().is_some()));
+        chain_map.split_off(0);
+        assert!(matches!(
+            chain_map.update_or_insert("count", |_| (), || 0),
+            Err(crate::Error::IndexOutOfRange)
+        ));
     }
 
     #[test]
-    fn child_len() {
-        let mut chain_map: ChainMap<&str, ()> = ChainMap::default();
+    fn occurrences() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.new_child();
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        assert_eq!(chain_map.occurrences("x"), 2);
+        assert_eq!(chain_map.occurrences("y"), 0);
+    }
+
+    #[test]
+    fn contains_value() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("home", "/root");
+        chain_map.insert("cache", "/tmp");
+        chain_map.new_child();
+        chain_map.insert("home", "/home/user");
+        chain_map.insert("tmp", "/tmp");
+        assert!(chain_map.contains_value(&"/tmp"));
+        assert!(!chain_map.contains_value(&"/root"));
+        let mut keys = chain_map.keys_with_value(&"/tmp");
+        keys.sort();
+        assert_eq!(keys, vec![&"cache", &"tmp"]);
+
+        let mut lazy: ChainMap<_, _> = ChainMap::default();
+        lazy.new_child_lazy(|| panic!("lazy layer built"));
+        lazy.new_child();
+        lazy.insert("tmp", "/tmp");
+        assert!(lazy.contains_value(&"/tmp"));
+    }
+
+    #[test]
+    fn prepend_parent() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 1);
+        chain_map.new_child_lazy(|| {
+            let mut map = HashMap::new();
+            map.insert("y", 2);
+            map
+        });
+        let mut defaults = HashMap::new();
+        defaults.insert("x", 0);
+        defaults.insert("z", 0);
+        chain_map.prepend_parent(defaults);
+        chain_map.new_parent();
+        assert_eq!(chain_map.child_len(), 4);
+        assert_eq!(chain_map.get("x"), Some(&1));
+        assert_eq!(chain_map.get("z"), Some(&0));
+        assert_eq!(chain_map.get_last_index("y"), Some(3));
+        assert!(chain_map.layer(0).is_empty());
+    }
+
+    #[test]
+    fn prepend_parent_lazy() {
+        struct Fixed;
+        impl LayerProvider<&'static str, i32> for Fixed {
+            fn load(&self) -> HashMap<&'static str, i32> {
+                let mut map = HashMap::new();
+                map.insert("provided", 1);
+                map
+            }
+            fn change_token(&self) -> Option<u64> {
+                None
+            }
+        }
+        let builds = Arc::new(AtomicI32::new(0));
+        let counter = Arc::clone(&builds);
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.new_child_lazy(move || {
+            counter.fetch_add(1, Ordering::Relaxed);
+            let mut map = HashMap::new();
+            map.insert("lazy", 1);
+            map
+        });
+        chain_map.push_provider(Fixed);
+        chain_map.new_child();
+        chain_map.insert("local", 1);
+        chain_map.new_parent();
+        chain_map.new_parent();
+        assert_eq!(builds.load(Ordering::Relaxed), 0);
+        assert_eq!(chain_map.get_last_index("local"), Some(5));
+        assert_eq!(builds.load(Ordering::Relaxed), 0);
+        assert_eq!(chain_map.get_last_index("lazy"), Some(3));
+        assert_eq!(builds.load(Ordering::Relaxed), 1);
+        assert_eq!(chain_map.refresh(), 1);
+        assert_eq!(chain_map.get_last_index("provided"), Some(4));
+
+        let captured = chain_map.capture();
+        chain_map.new_parent();
+        assert_eq!(chain_map.remove_at(4, "lazy").unwrap(), Some(1));
+        assert_eq!(captured.get("lazy"), Some(&1));
+        assert_eq!(chain_map.pop_parent().map(|map| map.len()), Some(0));
+        assert_eq!(chain_map.get_last_index("provided"), Some(4));
+        assert_eq!(builds.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn pop_parent() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("print", 0);
+        chain_map.new_child_lazy(|| {
+            let mut map = HashMap::new();
+            map.insert("x", 1);
+            map
+        });
+        let stdlib = chain_map.pop_parent().unwrap();
+        assert_eq!(stdlib.get("print"), Some(&0));
         assert_eq!(chain_map.child_len(), 1);
+        assert_eq!(chain_map.get_last_index("x"), Some(0));
+        assert!(chain_map.pop_parent().is_none());
+        assert_eq!(chain_map.get("x"), Some(&1));
+    }
 
-        for i in 2..100 {
+    #[test]
+    fn adopt() {
+        let mut importer: ChainMap<_, _> = ChainMap::default();
+        importer.insert("main", 0);
+        importer.new_child_lazy(|| {
+            let mut map = HashMap::new();
+            map.insert("local", 0);
+            map
+        });
+        let mut module: ChainMap<_, _> = ChainMap::default();
+        module.insert("export", 1);
+        module.new_child();
+        module.insert("main", 1);
+        importer.adopt_at(1, module.clone()).unwrap();
+        assert_eq!(importer.child_len(), 4);
+        assert_eq!(importer.get_last_index("main"), Some(2));
+        assert_eq!(importer.get_last_index("local"), Some(3));
+        assert!(importer.adopt_at(5, module.clone()).is_err());
+        importer.adopt(module);
+        assert_eq!(importer.child_len(), 6);
+        assert_eq!(importer.get_last_index("export"), Some(4));
+    }
+
+    #[test]
+    fn iter_outer_and_inner_first() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.insert("y", 0);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        let outer: Vec<_> = chain_map.iter_outer_first().collect();
+        assert_eq!(outer.len(), 3);
+        assert_eq!(outer[2], (&"x", &1));
+        let flat: HashMap<_, _> = chain_map.iter_outer_first().collect();
+        assert_eq!(flat.get(&"x"), Some(&&1));
+        let inner: Vec<_> = chain_map.iter_inner_first().collect();
+        assert_eq!(inner[0], (&"x", &1));
+        assert_eq!(
+            inner[1..].iter().copied().collect::<HashSet<_>>(),
+            outer[..2].iter().copied().collect::<HashSet<_>>()
+        );
+    }
+
+    #[test]
+    fn with_hasher() {
+        use hashers::oz::DJB2Hasher;
+        use std::hash::BuildHasherDefault;
+        fn build<S: BuildHasher + Default + Clone>() -> ChainMap<&'static str, i32, S> {
+            let mut chain_map = ChainMap::with_hasher(S::default());
+            chain_map.insert("x", 0);
             chain_map.new_child();
-            assert_eq!(chain_map.child_len(), i);
+            chain_map
         }
+        let chain_map = build::<BuildHasherDefault<DJB2Hasher>>();
+        assert_eq!(chain_map.get("x"), Some(&0));
+        assert_eq!(chain_map.child_len(), 2);
+
+        let chain_map: ChainMap<&str, i32, BuildHasherDefault<DJB2Hasher>> = Default::default();
+        assert!(chain_map.is_empty());
     }
 
     #[test]
-    fn get_before_exists() {
-        let mut chain_map = ChainMap::default();
-        chain_map.insert("test", 1);
+    fn remove_child_into() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
         chain_map.new_child();
-        chain_map.insert("test", 2);
-
-        assert_eq!(chain_map.get_before(1, &"test"), Some(&1));
+        chain_map.insert("x", 1);
+        let mut buf = HashMap::with_capacity(64);
+        buf.insert("stale", 2);
+        chain_map.remove_child_into(&mut buf);
+        assert_eq!(buf.len(), 1);
+        assert_eq!(buf.get("x"), Some(&1));
+        assert_eq!(chain_map.child_len(), 1);
+        assert!(chain_map.pool[0].capacity() >= 64);
+        chain_map.remove_child_into(&mut buf);
+        assert_eq!(buf.get("x"), Some(&0));
+        assert!(chain_map.is_empty());
+        assert_eq!(chain_map.child_len(), 1);
+        chain_map.split_off(0);
+        chain_map.remove_child_into(&mut buf);
+        assert!(buf.is_empty());
     }
 
     #[test]
-    fn get_before_mut_exists() {
-        let mut chain_map = ChainMap::default();
-        chain_map.insert("test", 1);
+    fn tracking() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("global", 0);
+        chain_map.insert("shadowed", 0);
         chain_map.new_child();
-        chain_map.insert("test", 2);
+        chain_map.insert("outer", 1);
+        chain_map.new_child();
+        chain_map.insert("local", 2);
+        chain_map.insert("shadowed", 2);
+        chain_map.get("global");
+        assert!(chain_map.captured_keys().is_empty());
+        chain_map.start_tracking(1);
+        for key in ["global", "outer", "local", "shadowed", "missing"] {
+            chain_map.get(key);
+        }
+        chain_map.get_before(2, "outer");
+        let mut captured: Vec<_> = chain_map.stop_tracking().into_iter().collect();
+        captured.sort();
+        assert_eq!(captured, vec!["global", "outer"]);
+        chain_map.get("global");
+        assert!(chain_map.captured_keys().is_empty());
+        chain_map.start_tracking(1);
+        chain_map.get("global");
+        let split = chain_map.split_off(1);
+        assert!(split.captured_keys().is_empty());
+        split.get("local");
+        split.get("outer");
+        assert_eq!(split.captured_keys().into_iter().collect::<Vec<_>>(), vec!["outer"]);
+    }
 
-        let test_value = chain_map.get_before_mut(1, &"test");
-        assert_eq!(test_value, Some(&mut 1));
-        *test_value.unwrap() += 2;
-        let changed = chain_map.get_before(1, &"test");
-        assert_eq!(changed, Some(&3));
-        let child = chain_map.get("test");
-        assert_eq!(child, Some(&2));
+    #[test]
+    fn with_layers() {
+        let outer: HashMap<_, _, RandomState> = vec![("x", 0), ("y", 0)].into_iter().collect();
+        let inner: HashMap<_, _, RandomState> = vec![("x", 1)].into_iter().collect();
+        let chain_map = ChainMap::with_layers(vec![outer, inner]);
+        assert_eq!(chain_map.child_len(), 2);
+        assert_eq!(chain_map.get("x"), Some(&1));
+        assert_eq!(chain_map.get("y"), Some(&0));
+        let empty: ChainMap<&str, i32> = ChainMap::with_layers(Vec::new());
+        assert_eq!(empty.child_len(), 1);
     }
 
     #[test]
-    fn get_last_index_exists() {
-        let mut chain_map = ChainMap::default();
-        chain_map.insert("test1", 1);
+    fn keys_and_values_at() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x", 0);
         chain_map.new_child();
-        chain_map.insert("test2", 2);
+        chain_map.insert("y", 1);
+        chain_map.insert("z", 2);
+        let mut keys: Vec<_> = chain_map.keys_at(1).unwrap().collect();
+        keys.sort();
+        assert_eq!(keys, vec![&"y", &"z"]);
+        assert_eq!(chain_map.values_at(0).unwrap().collect::<Vec<_>>(), vec![&0]);
+        assert!(chain_map.keys_at(2).is_err());
+        assert!(chain_map.values_at(2).is_err());
+    }
 
-        assert_eq!(chain_map.get_last_index("test1"), Some(0));
-        assert_eq!(chain_map.get_last_index("test2"), Some(1));
+    #[test]
+    fn index_layer() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("port", 80);
+        chain_map.new_child();
+        chain_map.insert("port", 8080);
+        assert_eq!(chain_map[(0, "port")], 80);
+        assert_eq!(chain_map[(1, "port")], 8080);
     }
 
     #[test]
-    fn get_last_index_doesnt_exist() {
-        let mut chain_map = ChainMap::default();
-        chain_map.insert("test1", 1);
+    #[should_panic(expected = "no entry found for key \"host\" in layer 1")]
+    fn index_layer_missing() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("host", "localhost");
         chain_map.new_child();
-        chain_map.insert("test2", 2);
+        let _ = chain_map[(1, "host")];
+    }
 
-        assert_eq!(chain_map.get_last_index("shmee"), None);
+    #[test]
+    fn try_reserve() {
+        let mut chain_map: ChainMap<u8, u8> = ChainMap::default();
+        chain_map.new_child();
+        chain_map.try_reserve(64).unwrap();
+        assert!(chain_map.maps[1].capacity() >= 64);
+        chain_map.try_reserve_at(0, 32).unwrap();
+        assert!(chain_map.maps[0].capacity() >= 32);
+        assert!(matches!(
+            chain_map.try_reserve(usize::MAX),
+            Err(crate::Error::Allocation(_))
+        ));
+        assert!(matches!(
+            chain_map.try_reserve_at(2, 1),
+            Err(crate::Error::IndexOutOfRange)
+        ));
     }
 
     #[test]
-    fn custom_hasher() {
-        // note: this test is about type checking
-        // rather than actual assertions
-        use std::hash::BuildHasherDefault;
-        use hashers::oz::DJB2Hasher; 
-        let hm = HashMap::with_hasher(BuildHasherDefault::<DJB2Hasher>::default());
-        let mut cm = ChainMap::new(hm);
-        cm.insert("test1", 1);
-        cm.new_child();
-        cm.insert("test1", 1);
-        cm.remove_child();
-        cm["test1"];
+    fn get_owned() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("host", "localhost".to_string());
+        chain_map.new_child();
+        chain_map.insert("port", "8080".to_string());
+        let host: Option<String> = chain_map.get_owned("host");
+        assert_eq!(host.as_deref(), Some("localhost"));
+        assert_eq!(
+            chain_map.resolve_owned(vec!["port", "user", "host"]),
+            vec![Some("8080".to_string()), None, Some("localhost".to_string())]
+        );
     }
 }
@@ -0,0 +1,567 @@
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hash},
+    iter::FromIterator,
+    ops::Index,
+    rc::Rc,
+};
+
+/// The number of hash bits consumed at each level of the trie.
+const BITS: u32 = 5;
+const MASK: u64 = (1 << BITS) - 1;
+
+/// A persistent hash map, the default layer type of
+/// [`LockedChainMap`](crate::LockedChainMap).
+///
+/// The map is a hash array mapped trie whose nodes are shared by reference
+/// counting, so cloning it is O(1) and an update only copies the nodes on
+/// the path to the changed entry.
+pub struct PersistentMap<K, V, S = RandomState> {
+    root: Rc<Node<K, V>>,
+    len: usize,
+    hasher: S,
+}
+
+struct Node<K, V> {
+    /// Which of the 32 slots of this node are occupied
+    bitmap: u32,
+    /// The occupied slots, in slot order
+    entries: Vec<Entry<K, V>>,
+}
+
+enum Entry<K, V> {
+    Leaf(u64, K, V),
+    /// Several keys whose full hashes are equal
+    Collision(u64, Vec<(K, V)>),
+    Branch(Rc<Node<K, V>>),
+}
+
+impl<K: Clone, V: Clone> Clone for Node<K, V> {
+    fn clone(&self) -> Self {
+        Self {
+            bitmap: self.bitmap,
+            entries: self.entries.clone(),
+        }
+    }
+}
+
+impl<K: Clone, V: Clone> Clone for Entry<K, V> {
+    fn clone(&self) -> Self {
+        match self {
+            Entry::Leaf(h, k, v) => Entry::Leaf(*h, k.clone(), v.clone()),
+            Entry::Collision(h, list) => Entry::Collision(*h, list.clone()),
+            Entry::Branch(node) => Entry::Branch(Rc::clone(node)),
+        }
+    }
+}
+
+impl<K, V> Node<K, V> {
+    fn empty() -> Self {
+        Self {
+            bitmap: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// The slot `hash` falls into at `shift` and its position in `entries`.
+    fn slot(&self, hash: u64, shift: u32) -> (u32, usize) {
+        let bit = 1 << ((hash >> shift) & MASK);
+        (bit, (self.bitmap & (bit - 1)).count_ones() as usize)
+    }
+
+    fn hash_of(entry: &Entry<K, V>) -> u64 {
+        match entry {
+            Entry::Leaf(h, _, _) | Entry::Collision(h, _) => *h,
+            Entry::Branch(_) => unreachable!("branches have no single hash"),
+        }
+    }
+
+    /// Builds a node holding two entries whose hashes differ.
+    fn pair(a: Entry<K, V>, b: Entry<K, V>, shift: u32) -> Self {
+        let (ha, hb) = (Self::hash_of(&a), Self::hash_of(&b));
+        let (ia, ib) = ((ha >> shift) & MASK, (hb >> shift) & MASK);
+        if ia == ib {
+            Self {
+                bitmap: 1 << ia,
+                entries: vec![Entry::Branch(Rc::new(Self::pair(a, b, shift + BITS)))],
+            }
+        } else {
+            let entries = if ia < ib { vec![a, b] } else { vec![b, a] };
+            Self {
+                bitmap: (1 << ia) | (1 << ib),
+                entries,
+            }
+        }
+    }
+
+    fn get<Q>(&self, hash: u64, shift: u32, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let (bit, pos) = self.slot(hash, shift);
+        if self.bitmap & bit == 0 {
+            return None;
+        }
+        match &self.entries[pos] {
+            Entry::Leaf(h, k, v) if *h == hash && k.borrow() == key => Some((k, v)),
+            Entry::Leaf(..) => None,
+            Entry::Collision(h, list) if *h == hash => list
+                .iter()
+                .find(|(k, _)| k.borrow() == key)
+                .map(|(k, v)| (k, v)),
+            Entry::Collision(..) => None,
+            Entry::Branch(node) => node.get(hash, shift + BITS, key),
+        }
+    }
+}
+
+impl<K: Clone + Eq, V: Clone> Node<K, V> {
+    fn get_mut<Q>(&mut self, hash: u64, shift: u32, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let (bit, pos) = self.slot(hash, shift);
+        if self.bitmap & bit == 0 {
+            return None;
+        }
+        match &mut self.entries[pos] {
+            Entry::Leaf(h, k, v) if *h == hash && (*k).borrow() == key => Some(v),
+            Entry::Leaf(..) => None,
+            Entry::Collision(h, list) if *h == hash => list
+                .iter_mut()
+                .find(|(k, _)| (*k).borrow() == key)
+                .map(|(_, v)| v),
+            Entry::Collision(..) => None,
+            Entry::Branch(node) => Rc::make_mut(node).get_mut(hash, shift + BITS, key),
+        }
+    }
+
+    fn insert(&mut self, hash: u64, shift: u32, key: K, value: V) -> Option<V> {
+        let (bit, pos) = self.slot(hash, shift);
+        if self.bitmap & bit == 0 {
+            self.bitmap |= bit;
+            self.entries.insert(pos, Entry::Leaf(hash, key, value));
+            return None;
+        }
+        let entry = &mut self.entries[pos];
+        match entry {
+            Entry::Leaf(h, k, v) if *h == hash && *k == key => {
+                return Some(std::mem::replace(v, value));
+            }
+            Entry::Collision(h, list) if *h == hash => {
+                if let Some((_, v)) = list.iter_mut().find(|(k, _)| *k == key) {
+                    return Some(std::mem::replace(v, value));
+                }
+                list.push((key, value));
+                return None;
+            }
+            Entry::Branch(node) => {
+                return Rc::make_mut(node).insert(hash, shift + BITS, key, value);
+            }
+            _ => {}
+        }
+        // the slot holds a different key, move both into a deeper node, or a
+        // collision list when the full hashes are equal
+        let existing = std::mem::replace(entry, Entry::Collision(hash, Vec::new()));
+        *entry = match existing {
+            Entry::Leaf(h, k, v) if h == hash => Entry::Collision(h, vec![(k, v), (key, value)]),
+            existing => Entry::Branch(Rc::new(Self::pair(
+                existing,
+                Entry::Leaf(hash, key, value),
+                shift + BITS,
+            ))),
+        };
+        None
+    }
+
+    fn remove<Q>(&mut self, hash: u64, shift: u32, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        let (bit, pos) = self.slot(hash, shift);
+        if self.bitmap & bit == 0 {
+            return None;
+        }
+        let (removed, replacement) = match &mut self.entries[pos] {
+            Entry::Leaf(h, k, _) if *h == hash && (*k).borrow() == key => {
+                self.bitmap &= !bit;
+                return match self.entries.remove(pos) {
+                    Entry::Leaf(_, k, v) => Some((k, v)),
+                    _ => unreachable!(),
+                };
+            }
+            Entry::Collision(h, list) if *h == hash => {
+                let idx = list.iter().position(|(k, _)| k.borrow() == key)?;
+                let removed = list.swap_remove(idx);
+                let replacement = match list.len() {
+                    1 => list.pop().map(|(k, v)| Entry::Leaf(hash, k, v)),
+                    _ => None,
+                };
+                (removed, replacement)
+            }
+            Entry::Branch(node) => {
+                let node = Rc::make_mut(node);
+                let removed = node.remove(hash, shift + BITS, key)?;
+                // pull a lone leaf or collision list back up into this node
+                let replacement = match node.entries.as_slice() {
+                    [Entry::Leaf(..)] | [Entry::Collision(..)] => node.entries.pop(),
+                    _ => None,
+                };
+                (removed, replacement)
+            }
+            _ => return None,
+        };
+        if let Some(entry) = replacement {
+            self.entries[pos] = entry;
+        }
+        Some(removed)
+    }
+}
+
+impl<K, V, S> PersistentMap<K, V, S> {
+    pub fn with_hasher(hasher: S) -> Self {
+        Self {
+            root: Rc::new(Node::empty()),
+            len: 0,
+            hasher,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn hasher(&self) -> &S {
+        &self.hasher
+    }
+
+    /// Removes every entry. Clones of the map are unaffected.
+    pub fn clear(&mut self) {
+        self.root = Rc::new(Node::empty());
+        self.len = 0;
+    }
+
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            stack: vec![self.root.entries.iter()],
+            collision: [].iter(),
+            remaining: self.len,
+        }
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(k, _)| k)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, v)| v)
+    }
+
+    /// Returns `true` if both maps share the same root, in which case they
+    /// are known to be equal without comparing any entries.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.root, &other.root)
+    }
+}
+
+impl<K, V> PersistentMap<K, V> {
+    pub fn new() -> Self {
+        Self::with_hasher(RandomState::new())
+    }
+}
+
+impl<K, V, S> PersistentMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.root.get(self.hasher.hash_one(key), 0, key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+}
+
+impl<K, V, S> PersistentMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher,
+{
+    /// Returns a mutable reference to the value, first copying any nodes
+    /// on its path that are shared with another map.
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.hasher.hash_one(key);
+        // check first so a miss doesn't copy any shared nodes
+        self.root.get(hash, 0, key)?;
+        Rc::make_mut(&mut self.root).get_mut(hash, 0, key)
+    }
+
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        let hash = self.hasher.hash_one(&key);
+        let ret = Rc::make_mut(&mut self.root).insert(hash, 0, key, value);
+        if ret.is_none() {
+            self.len += 1;
+        }
+        ret
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.remove_entry(key).map(|(_, v)| v)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = self.hasher.hash_one(key);
+        // check first so a miss doesn't copy any shared nodes
+        self.root.get(hash, 0, key)?;
+        let ret = Rc::make_mut(&mut self.root).remove(hash, 0, key);
+        if ret.is_some() {
+            self.len -= 1;
+        }
+        ret
+    }
+}
+
+/// An iterator over the entries of a [`PersistentMap`], in no particular
+/// order.
+pub struct Iter<'a, K, V> {
+    stack: Vec<std::slice::Iter<'a, Entry<K, V>>>,
+    collision: std::slice::Iter<'a, (K, V)>,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some((k, v)) = self.collision.next() {
+                self.remaining -= 1;
+                return Some((k, v));
+            }
+            match self.stack.last_mut()?.next() {
+                Some(Entry::Leaf(_, k, v)) => {
+                    self.remaining -= 1;
+                    return Some((k, v));
+                }
+                Some(Entry::Collision(_, list)) => self.collision = list.iter(),
+                Some(Entry::Branch(node)) => self.stack.push(node.entries.iter()),
+                None => {
+                    self.stack.pop();
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+
+impl<'a, K, V, S> IntoIterator for &'a PersistentMap<K, V, S> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<K, V, S: Clone> Clone for PersistentMap<K, V, S> {
+    fn clone(&self) -> Self {
+        Self {
+            root: Rc::clone(&self.root),
+            len: self.len,
+            hasher: self.hasher.clone(),
+        }
+    }
+}
+
+impl<K, V, S: Default> Default for PersistentMap<K, V, S> {
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> FromIterator<(K, V)> for PersistentMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Default,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut ret = Self::default();
+        ret.extend(iter);
+        ret
+    }
+}
+
+impl<K, V, S> Extend<(K, V)> for PersistentMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher,
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl<K, Q: ?Sized, V, S> Index<&Q> for PersistentMap<K, V, S>
+where
+    K: Eq + Hash + Borrow<Q>,
+    Q: Eq + Hash,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `PersistentMap`.
+    #[inline]
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K, V, S> PartialEq for PersistentMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: PartialEq,
+    S: BuildHasher,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len
+            && (self.ptr_eq(other) || self.iter().all(|(k, v)| other.get(k) == Some(v)))
+    }
+}
+
+impl<K, V, S> Eq for PersistentMap<K, V, S>
+where
+    K: Eq + Hash,
+    V: Eq,
+    S: BuildHasher,
+{
+}
+
+impl<K, V, S> core::fmt::Debug for PersistentMap<K, V, S>
+where
+    K: core::fmt::Debug,
+    V: core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> std::fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{
+        collections::HashMap,
+        hash::{BuildHasherDefault, Hasher},
+    };
+
+    /// Sends every key to the same hash, to exercise collision lists.
+    #[derive(Default)]
+    struct ConstHasher;
+
+    impl Hasher for ConstHasher {
+        fn finish(&self) -> u64 {
+            7
+        }
+        fn write(&mut self, _: &[u8]) {}
+    }
+
+    #[test]
+    fn matches_std_map() {
+        let mut map = PersistentMap::new();
+        let mut expected = HashMap::new();
+        for i in 0..2000u32 {
+            assert_eq!(map.insert(i, i * 2), expected.insert(i, i * 2));
+        }
+        for i in (0..2000u32).step_by(3) {
+            assert_eq!(map.remove(&i), expected.remove(&i));
+        }
+        *map.get_mut(&1).unwrap() += 1;
+        *expected.get_mut(&1).unwrap() += 1;
+        assert_eq!(map.len(), expected.len());
+        assert_eq!(map.iter().len(), expected.len());
+        for (k, v) in &expected {
+            assert_eq!(map.get(k), Some(v));
+        }
+        assert_eq!(map.get(&3), None);
+    }
+
+    #[test]
+    fn clones_are_independent() {
+        let mut map = PersistentMap::new();
+        map.insert("x", 0);
+        let snapshot = map.clone();
+        assert!(map.ptr_eq(&snapshot));
+        map.insert("x", 1);
+        map.insert("y", 2);
+        assert_eq!(snapshot.get("x"), Some(&0));
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(map["x"], 1);
+        assert_ne!(map, snapshot);
+    }
+
+    #[test]
+    fn hash_collisions() {
+        let mut map: PersistentMap<u8, u8, BuildHasherDefault<ConstHasher>> =
+            PersistentMap::default();
+        map.insert(1, 1);
+        map.insert(2, 2);
+        map.insert(3, 3);
+        assert_eq!(map.insert(2, 4), Some(2));
+        assert_eq!(map.remove(&1), Some(1));
+        assert_eq!(map.remove(&3), Some(3));
+        assert_eq!(map.get(&2), Some(&4));
+        assert_eq!(map.iter().collect::<Vec<_>>(), vec![(&2, &4)]);
+    }
+}
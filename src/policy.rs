@@ -0,0 +1,64 @@
+/// Controls how a chain reacts when a key inserted into the current scope
+/// would shadow a binding in an outer scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum ShadowPolicy {
+    /// Shadowing is permitted and not reported
+    #[default]
+    Allow,
+    /// Shadowing is permitted but reported via [`Insertion::Shadowed`]
+    Warn,
+    /// Shadowing is rejected with [`Error::Shadowing`](crate::Error::Shadowing)
+    Deny,
+}
+
+/// The outcome of a policy-checked insert.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Insertion<V> {
+    /// The key was not bound in the current scope
+    Vacant,
+    /// The key was already bound in the current scope, the old value is returned
+    Replaced(V),
+    /// The key was not bound in the current scope but now shadows an
+    /// outer binding
+    Shadowed,
+}
+
+/// Controls what happens when a binding that lives in an outer scope is
+/// written to through the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum WritePolicy {
+    /// The outer binding is modified in place
+    #[default]
+    WriteThrough,
+    /// The binding is copied into the current scope and the copy is
+    /// modified, leaving the outer binding untouched
+    CopyUp,
+}
+
+/// What [`ChainMap::rename_key`](crate::ChainMap::rename_key) does in a
+/// layer that already binds the new key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum RenameCollision {
+    /// The existing binding of the new key is replaced
+    Overwrite,
+    /// The layer is left untouched, keeping both bindings
+    Skip,
+    /// The rename is rejected and no layer is modified
+    #[default]
+    Fail,
+}
+
+/// How [`ChainMap::load_into`](crate::ChainMap::load_into) combines a saved
+/// chain with the one it is loaded into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum MergeOnLoad {
+    /// The current layers are dropped in favour of the saved ones
+    #[default]
+    Replace,
+    /// Saved layers are merged into the current layer at the same index,
+    /// keeping the current value of keys bound by both
+    KeepCurrent,
+    /// Saved layers are merged into the current layer at the same index,
+    /// taking the saved value of keys bound by both
+    PreferSaved,
+}
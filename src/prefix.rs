@@ -0,0 +1,116 @@
+use crate::ChainMap;
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hash},
+};
+
+/// A read-only view of the bindings of a string keyed chain whose keys
+/// start with a common prefix, created by [`ChainMap::scoped`].
+///
+/// Keys passed to and returned from the view have the prefix removed.
+pub struct PrefixView<'a, K, V, S = RandomState> {
+    chain: &'a ChainMap<K, V, S>,
+    prefix: String,
+}
+
+impl<K, V, S> ChainMap<K, V, S>
+where
+    K: Hash + Eq + Borrow<str>,
+    S: BuildHasher,
+{
+    /// Returns a view of the bindings whose keys start with `prefix`, so
+    /// that `scoped("db.").get("host")` looks up `"db.host"`.
+    pub fn scoped(&self, prefix: &str) -> PrefixView<'_, K, V, S> {
+        PrefixView {
+            chain: self,
+            prefix: prefix.to_string(),
+        }
+    }
+}
+
+impl<'a, K, V, S> PrefixView<'a, K, V, S>
+where
+    K: Hash + Eq + Borrow<str>,
+    S: BuildHasher,
+{
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Looks up `prefix + key` through the whole chain.
+    pub fn get(&self, key: &str) -> Option<&'a V> {
+        let full = format!("{}{}", self.prefix, key);
+        self.chain.get::<str>(&full)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Narrows the view further, so `scoped("db.").scoped("pool.")` is
+    /// the same as `scoped("db.pool.")`.
+    pub fn scoped(&self, prefix: &str) -> PrefixView<'a, K, V, S> {
+        PrefixView {
+            chain: self.chain,
+            prefix: format!("{}{}", self.prefix, prefix),
+        }
+    }
+
+    /// Iterates over the visible bindings in the namespace, innermost
+    /// scope first, with the prefix stripped from each key.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a V)> + '_ {
+        let chain = self.chain;
+        chain.layers().enumerate().rev().flat_map(move |(i, map)| {
+            map.iter().filter_map(move |(k, v)| {
+                let key: &str = k.borrow();
+                let rest = key.strip_prefix(self.prefix.as_str())?;
+                let shadowed = chain.layers().skip(i + 1).any(|m| m.contains_key(key));
+                if shadowed {
+                    None
+                } else {
+                    Some((rest, v))
+                }
+            })
+        })
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.iter().map(|(k, _)| k)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scoped_get() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("db.host", "localhost");
+        chain_map.insert("db.pool.size", "4");
+        chain_map.insert("http.port", "80");
+        chain_map.new_child();
+        chain_map.insert("db.host", "db.internal");
+
+        let db = chain_map.scoped("db.");
+        assert_eq!(db.get("host"), Some(&"db.internal"));
+        assert_eq!(db.get("port"), None);
+        assert_eq!(db.scoped("pool.").get("size"), Some(&"4"));
+    }
+
+    #[test]
+    fn scoped_iter() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("db.host", "localhost");
+        chain_map.insert("db.user", "admin");
+        chain_map.insert("http.port", "80");
+        chain_map.new_child();
+        chain_map.insert("db.host", "db.internal");
+
+        let db = chain_map.scoped("db.");
+        let mut bindings: Vec<_> = db.iter().collect();
+        bindings.sort();
+        assert_eq!(bindings, vec![("host", &"db.internal"), ("user", &"admin")]);
+    }
+}
@@ -0,0 +1,44 @@
+use std::collections::{hash_map::RandomState, HashMap};
+use std::sync::Arc;
+
+/// An external source that can materialize one layer of a chain.
+///
+/// Providers are attached with
+/// [`ChainMap::push_provider`](crate::ChainMap::push_provider) and re-read
+/// with [`ChainMap::refresh`](crate::ChainMap::refresh).
+pub trait LayerProvider<K, V, S = RandomState> {
+    /// Reads the current contents of the source.
+    fn load(&self) -> HashMap<K, V, S>;
+
+    /// An opaque value that changes whenever the source does, e.g. a file's
+    /// modification time or a revision number. When this returns `None` the
+    /// layer is reloaded on every refresh.
+    fn change_token(&self) -> Option<u64> {
+        None
+    }
+}
+
+impl<K, V, S, F> LayerProvider<K, V, S> for F
+where
+    F: Fn() -> HashMap<K, V, S>,
+{
+    fn load(&self) -> HashMap<K, V, S> {
+        self()
+    }
+}
+
+pub(crate) struct ProvidedLayer<K, V, S> {
+    pub(crate) idx: usize,
+    pub(crate) provider: Arc<dyn LayerProvider<K, V, S> + Send + Sync>,
+    pub(crate) token: Option<u64>,
+}
+
+impl<K, V, S> Clone for ProvidedLayer<K, V, S> {
+    fn clone(&self) -> Self {
+        Self {
+            idx: self.idx,
+            provider: Arc::clone(&self.provider),
+            token: self.token,
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use crate::LookupBounds;
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+};
+
+/// The scopes of a lock guarded chain, as seen through one of its read
+/// guards, e.g. from `AsyncChainMap::read`.
+///
+/// Every lookup made through the same guard observes the same state, since
+/// writers are locked out until the guard is dropped.
+pub struct ReadView<K, V, S = RandomState> {
+    pub(crate) maps: Vec<HashMap<K, V, S>>,
+}
+
+impl<K, V, S> ReadView<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_in(LookupBounds::All, key)
+    }
+
+    /// Returns the value corresponding to the key, searching only the
+    /// layers selected by `bounds`, innermost first.
+    pub fn get_in<Q>(&self, bounds: LookupBounds, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps[bounds.range(self.maps.len())]
+            .iter()
+            .rev()
+            .find_map(|map| map.get(key))
+    }
+
+    pub fn get_before<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_in(LookupBounds::Before(idx), key)
+    }
+
+    pub fn get_after<Q>(&self, idx: usize, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_in(LookupBounds::After(idx), key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn has_at<Q>(&self, idx: usize, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.get(idx).is_some_and(|map| map.contains_key(key))
+    }
+
+    pub fn get_last_index<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.iter().rposition(|map| map.contains_key(key))
+    }
+
+    pub fn last_has<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.last().is_some_and(|map| map.contains_key(key))
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.maps.len()
+    }
+
+    /// The number of bindings across every scope, including ones that are
+    /// shadowed by an inner scope.
+    pub fn total_len(&self) -> usize {
+        self.maps.iter().map(HashMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.maps.iter().all(HashMap::is_empty)
+    }
+}
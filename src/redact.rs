@@ -0,0 +1,39 @@
+use std::collections::HashMap;
+use std::{fmt, sync::Arc};
+
+/// Decides which keys have their values hidden from `Debug` output, see
+/// [`ChainMap::set_redaction`](crate::ChainMap::set_redaction).
+pub(crate) type Redactor<K> = Arc<dyn Fn(&K) -> bool + Send + Sync>;
+
+/// Formats a layer like `HashMap`'s `Debug`, replacing the values of
+/// sensitive keys.
+pub(crate) struct RedactedLayer<'a, K, V, S> {
+    pub(crate) map: &'a HashMap<K, V, S>,
+    pub(crate) redactor: &'a Redactor<K>,
+}
+
+struct Placeholder;
+
+impl fmt::Debug for Placeholder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("«redacted»")
+    }
+}
+
+impl<K, V, S> fmt::Debug for RedactedLayer<'_, K, V, S>
+where
+    K: fmt::Debug,
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut map = f.debug_map();
+        for (k, v) in self.map {
+            if (self.redactor)(k) {
+                map.entry(k, &Placeholder);
+            } else {
+                map.entry(k, v);
+            }
+        }
+        map.finish()
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{ChainMap, ChainSet};
+use rkyv::{
+    bytecheck::CheckBytes,
+    collections::swiss_table::{
+        ArchivedHashMap, ArchivedHashSet, HashMapResolver, HashSetResolver,
+    },
+    munge::munge,
+    rancor::{Fallible, Source},
+    ser::{Allocator, Writer},
+    vec::{ArchivedVec, VecResolver},
+    Archive, Deserialize, Place, Portable, Serialize,
+};
+use std::{
+    borrow::Borrow,
+    collections::{HashMap, HashSet},
+    hash::{BuildHasher, Hash},
+};
+
+const LOAD_FACTOR: (usize, usize) = (7, 8);
+
+/// An archived [`ChainMap`], which can be queried in place, e.g. straight
+/// out of a memory mapped file, without deserializing it.
+#[derive(Portable, CheckBytes)]
+#[bytecheck(crate = rkyv::bytecheck)]
+#[repr(transparent)]
+pub struct ArchivedChainMap<K, V> {
+    maps: ArchivedVec<ArchivedHashMap<K, V>>,
+}
+
+/// An archived [`ChainSet`], which can be queried in place without
+/// deserializing it.
+#[derive(Portable, CheckBytes)]
+#[bytecheck(crate = rkyv::bytecheck)]
+#[repr(transparent)]
+pub struct ArchivedChainSet<T> {
+    sets: ArchivedVec<ArchivedHashSet<T>>,
+}
+
+impl<K, V> ArchivedChainMap<K, V> {
+    /// Returns the value corresponding to the key, searching the archived
+    /// layers from the innermost outward.
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q> + Hash + Eq,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.iter().rev().find_map(|map| map.get(key))
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q> + Hash + Eq,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.maps.len()
+    }
+}
+
+impl<T> ArchivedChainSet<T> {
+    pub fn get<Q>(&self, value: &Q) -> Option<&T>
+    where
+        T: Borrow<Q> + Hash + Eq,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.sets.iter().rev().find_map(|set| set.get(value))
+    }
+
+    pub fn contains<Q>(&self, value: &Q) -> bool
+    where
+        T: Borrow<Q> + Hash + Eq,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(value).is_some()
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.sets.len()
+    }
+}
+
+/// Archives a single layer through its entries, so any map type can be
+/// used for the layers.
+struct MapLayer<'a, K, V, S>(&'a HashMap<K, V, S>);
+
+impl<K, V, S> Archive for MapLayer<'_, K, V, S>
+where
+    K: Archive,
+    V: Archive,
+{
+    type Archived = ArchivedHashMap<K::Archived, V::Archived>;
+    type Resolver = HashMapResolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        ArchivedHashMap::resolve_from_len(self.0.len(), LOAD_FACTOR, resolver, out);
+    }
+}
+
+impl<K, V, S, Ser> Serialize<Ser> for MapLayer<'_, K, V, S>
+where
+    K: Serialize<Ser> + Hash + Eq,
+    V: Serialize<Ser>,
+    Ser: Fallible + Writer + Allocator + ?Sized,
+    Ser::Error: Source,
+{
+    fn serialize(&self, serializer: &mut Ser) -> Result<Self::Resolver, Ser::Error> {
+        ArchivedHashMap::<K::Archived, V::Archived>::serialize_from_iter::<_, _, _, K, V, _>(
+            self.0.iter(),
+            LOAD_FACTOR,
+            serializer,
+        )
+    }
+}
+
+struct SetLayer<'a, T>(&'a HashSet<T>);
+
+impl<T: Archive> Archive for SetLayer<'_, T> {
+    type Archived = ArchivedHashSet<T::Archived>;
+    type Resolver = HashSetResolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        ArchivedHashSet::resolve_from_len(self.0.len(), LOAD_FACTOR, resolver, out);
+    }
+}
+
+impl<T, Ser> Serialize<Ser> for SetLayer<'_, T>
+where
+    T: Serialize<Ser> + Hash + Eq,
+    Ser: Fallible + Writer + Allocator + ?Sized,
+    Ser::Error: Source,
+{
+    fn serialize(&self, serializer: &mut Ser) -> Result<Self::Resolver, Ser::Error> {
+        ArchivedHashSet::<T::Archived>::serialize_from_iter::<_, T, _>(
+            self.0.iter(),
+            LOAD_FACTOR,
+            serializer,
+        )
+    }
+}
+
+impl<K, V, S> Archive for ChainMap<K, V, S>
+where
+    K: Archive,
+    V: Archive,
+{
+    type Archived = ArchivedChainMap<K::Archived, V::Archived>;
+    type Resolver = VecResolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        munge!(let ArchivedChainMap { maps } = out);
+        ArchivedVec::resolve_from_len(self.maps.len(), resolver, maps);
+    }
+}
+
+impl<K, V, S, Ser> Serialize<Ser> for ChainMap<K, V, S>
+where
+    K: Serialize<Ser> + Hash + Eq,
+    V: Serialize<Ser>,
+    S: BuildHasher,
+    Ser: Fallible + Writer + Allocator + ?Sized,
+    Ser::Error: Source,
+{
+    fn serialize(&self, serializer: &mut Ser) -> Result<Self::Resolver, Ser::Error> {
+        let layers: Vec<_> = self.layers().map(MapLayer).collect();
+        ArchivedVec::serialize_from_slice(&layers, serializer)
+    }
+}
+
+impl<K, V, S, D> Deserialize<ChainMap<K, V, S>, D> for ArchivedChainMap<K::Archived, V::Archived>
+where
+    K: Archive + Hash + Eq,
+    K::Archived: Deserialize<K, D>,
+    V: Archive,
+    V::Archived: Deserialize<V, D>,
+    S: BuildHasher + Default,
+    D: Fallible + ?Sized,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<ChainMap<K, V, S>, D::Error> {
+        let mut maps = Vec::with_capacity(self.maps.len());
+        for archived in self.maps.iter() {
+            let mut map = HashMap::with_capacity_and_hasher(archived.len(), S::default());
+            for (k, v) in archived.iter() {
+                map.insert(k.deserialize(deserializer)?, v.deserialize(deserializer)?);
+            }
+            maps.push(map);
+        }
+        if maps.is_empty() {
+            maps.push(HashMap::default());
+        }
+        Ok(ChainMap::from_maps(maps))
+    }
+}
+
+impl<T: Archive> Archive for ChainSet<T> {
+    type Archived = ArchivedChainSet<T::Archived>;
+    type Resolver = VecResolver;
+
+    fn resolve(&self, resolver: Self::Resolver, out: Place<Self::Archived>) {
+        munge!(let ArchivedChainSet { sets } = out);
+        ArchivedVec::resolve_from_len(self.sets.len(), resolver, sets);
+    }
+}
+
+impl<T, Ser> Serialize<Ser> for ChainSet<T>
+where
+    T: Serialize<Ser> + Hash + Eq,
+    Ser: Fallible + Writer + Allocator + ?Sized,
+    Ser::Error: Source,
+{
+    fn serialize(&self, serializer: &mut Ser) -> Result<Self::Resolver, Ser::Error> {
+        let layers: Vec<_> = self.sets.iter().map(SetLayer).collect();
+        ArchivedVec::serialize_from_slice(&layers, serializer)
+    }
+}
+
+impl<T, D> Deserialize<ChainSet<T>, D> for ArchivedChainSet<T::Archived>
+where
+    T: Archive + Hash + Eq,
+    T::Archived: Deserialize<T, D>,
+    D: Fallible + ?Sized,
+{
+    fn deserialize(&self, deserializer: &mut D) -> Result<ChainSet<T>, D::Error> {
+        let mut sets = Vec::with_capacity(self.sets.len());
+        for archived in self.sets.iter() {
+            let mut set = HashSet::with_capacity(archived.len());
+            for value in archived.iter() {
+                set.insert(value.deserialize(deserializer)?);
+            }
+            sets.push(set);
+        }
+        if sets.is_empty() {
+            sets.push(HashSet::new());
+        }
+        Ok(ChainSet { sets })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use rkyv::rancor::Error;
+
+    #[test]
+    fn archived_chain_map() {
+        let mut chain_map: ChainMap<String, u32> = ChainMap::default();
+        chain_map.insert("x".to_string(), 0);
+        chain_map.new_child();
+        chain_map.insert("x".to_string(), 1);
+        chain_map.insert("y".to_string(), 2);
+        let bytes = rkyv::to_bytes::<Error>(&chain_map).unwrap();
+        let archived = rkyv::access::<
+            ArchivedChainMap<rkyv::string::ArchivedString, rkyv::Archived<u32>>,
+            Error,
+        >(&bytes)
+        .unwrap();
+        assert_eq!(archived.get("x").map(|v| v.to_native()), Some(1));
+        assert_eq!(archived.child_len(), 2);
+        let round_trip: ChainMap<String, u32> = rkyv::deserialize::<_, Error>(archived).unwrap();
+        assert_eq!(round_trip, chain_map);
+    }
+
+    #[test]
+    fn archived_chain_set() {
+        let mut chain_set = ChainSet::default();
+        chain_set.insert(1u8);
+        chain_set.new_child();
+        chain_set.insert(2u8);
+        let bytes = rkyv::to_bytes::<Error>(&chain_set).unwrap();
+        let archived = rkyv::access::<ArchivedChainSet<u8>, Error>(&bytes).unwrap();
+        assert!(archived.contains(&1));
+        let round_trip: ChainSet<u8> = rkyv::deserialize::<_, Error>(archived).unwrap();
+        assert_eq!(round_trip.get(&2), Some(&2));
+    }
+}
@@ -0,0 +1,150 @@
+use crate::ChainMap;
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap, HashSet},
+    hash::{BuildHasher, Hash},
+};
+
+/// A read-only view of a [`ChainMap`]'s layers, created by
+/// [`ChainMap::scoped_view`].
+///
+/// Lazy layers are built when the view is taken, so lookups through the
+/// view never run user code. The view only borrows the layers, so it is
+/// `Send` and `Sync` whenever the keys, values and hasher are `Sync`, and
+/// the chain can't be changed while a view of it is alive.
+///
+/// ```
+/// # use hash_chain::ChainMap;
+/// let mut chain_map: ChainMap<_, _> = ChainMap::default();
+/// chain_map.insert("x", 1);
+/// let view = chain_map.scoped_view();
+/// std::thread::scope(|s| {
+///     s.spawn(|| assert_eq!(view.get("x"), Some(&1)));
+///     s.spawn(|| assert!(!view.contains_key("y")));
+/// });
+/// ```
+#[derive(Clone)]
+pub struct ScopedView<'a, K, V, S = RandomState> {
+    maps: Vec<&'a HashMap<K, V, S>>,
+}
+
+impl<K, V, S> ChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Returns a view of the current layers, building any lazy layers
+    /// first.
+    pub fn scoped_view(&self) -> ScopedView<'_, K, V, S> {
+        ScopedView {
+            maps: self.layers().collect(),
+        }
+    }
+}
+
+impl<'a, K, V, S> ScopedView<'a, K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn get<Q>(&self, key: &Q) -> Option<&'a V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.iter().rev().find_map(|map| map.get(key))
+    }
+
+    pub fn get_at<Q>(&self, idx: usize, key: &Q) -> Option<&'a V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.get(idx)?.get(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn last_has<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.last().is_some_and(|map| map.contains_key(key))
+    }
+
+    pub fn get_last_index<Q>(&self, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.iter().rposition(|map| map.contains_key(key))
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.maps.len()
+    }
+
+    /// The number of visible keys, counting each key once no matter how
+    /// many layers bind it.
+    pub fn len(&self) -> usize {
+        self.maps
+            .iter()
+            .flat_map(|map| map.keys())
+            .collect::<HashSet<_>>()
+            .len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.maps.iter().all(|map| map.is_empty())
+    }
+
+    /// Iterates over the visible bindings, innermost layer first.
+    pub fn iter(&self) -> impl Iterator<Item = (&'a K, &'a V)> + '_ {
+        self.maps
+            .iter()
+            .enumerate()
+            .rev()
+            .flat_map(move |(idx, map)| {
+                map.iter()
+                    .filter(move |(k, _)| !self.maps[idx + 1..].iter().any(|m| m.contains_key(*k)))
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+    #[test]
+    fn shared_with_scoped_threads() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("x".to_string(), 0);
+        chain_map.insert("y".to_string(), 0);
+        chain_map.new_child();
+        chain_map.insert("x".to_string(), 1);
+        let view = chain_map.scoped_view();
+        assert_send_sync(&view);
+        let view = &view;
+        let found: Vec<_> = std::thread::scope(|s| {
+            let handles: Vec<_> = ["x", "y", "z"]
+                .iter()
+                .map(|key| s.spawn(move || view.get(*key).copied()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert_eq!(found, vec![Some(1), Some(0), None]);
+        assert_eq!(view.get_last_index("y"), Some(0));
+        assert_eq!(view.get_at(0, "x"), Some(&0));
+        assert_eq!(view.len(), 2);
+        assert_eq!(view.iter().count(), 2);
+    }
+}
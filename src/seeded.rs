@@ -0,0 +1,183 @@
+//! A reproducible hasher for the `deterministic` feature, for chains whose
+//! iteration and debug output must be the same on every run.
+
+use crate::{ChainMap, LockedChainMap};
+use std::hash::{BuildHasher, Hash, Hasher};
+
+const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// Builds seeded FNV-1a hashers, so hashes, and with them iteration order,
+/// are the same across runs and platforms. Needs no entropy, so it also
+/// works on targets like wasm.
+///
+/// Integers are hashed as their little endian bytes, with `usize` and
+/// `isize` widened to 64 bits, so a hash doesn't depend on the platform.
+///
+/// The hash is not resistant to collision attacks; only use it where the
+/// keys are trusted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SeededState {
+    seed: u64,
+}
+
+impl SeededState {
+    pub const fn with_seed(seed: u64) -> Self {
+        Self { seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl BuildHasher for SeededState {
+    type Hasher = SeededHasher;
+
+    fn build_hasher(&self) -> SeededHasher {
+        SeededHasher {
+            state: OFFSET_BASIS ^ self.seed.wrapping_mul(PRIME),
+        }
+    }
+}
+
+impl<K, V> ChainMap<K, V, SeededState>
+where
+    K: Hash + Eq,
+{
+    /// Creates a chain with a single empty layer hashed with `seed`. Every
+    /// scope pushed later is hashed with the same seed.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_hasher(SeededState::with_seed(seed))
+    }
+}
+
+impl<K, V> LockedChainMap<K, V, SeededState>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+{
+    /// Creates a chain with a single empty layer hashed with `seed`.
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_hasher(SeededState::with_seed(seed))
+    }
+}
+
+/// The hasher built by [`SeededState`]
+#[derive(Debug, Clone)]
+pub struct SeededHasher {
+    state: u64,
+}
+
+impl Hasher for SeededHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.state ^= u64::from(*byte);
+            self.state = self.state.wrapping_mul(PRIME);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i8(&mut self, i: i8) {
+        self.write_u8(i as u8);
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16);
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32);
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write_u128(i as u128);
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_i64(i as i64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.state
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn reproducible_order() {
+        let build = || {
+            let mut chain_map = ChainMap::with_seed(7);
+            chain_map.new_child();
+            for i in 0..32 {
+                chain_map.insert(i, i);
+            }
+            chain_map.remove_child().unwrap().into_iter().collect::<Vec<_>>()
+        };
+        assert_eq!(build(), build());
+        let a = SeededState::with_seed(1).hash_one("x");
+        assert_eq!(a, SeededState::with_seed(1).hash_one("x"));
+        assert_ne!(a, SeededState::with_seed(2).hash_one("x"));
+    }
+
+    #[test]
+    fn children_keep_seed() {
+        let mut chain_map: ChainMap<u8, u8, _> = ChainMap::with_seed(7);
+        chain_map.new_child();
+        chain_map.new_child_with_capacity(4);
+        chain_map.new_parent();
+        assert!(chain_map.layers().all(|map| map.hasher().seed() == 7));
+        chain_map.remove_child();
+        chain_map.remove_child();
+        chain_map.remove_child();
+        assert_eq!(chain_map.remove_child().unwrap().hasher().seed(), 7);
+        assert_eq!(chain_map.layers().next().unwrap().hasher().seed(), 7);
+
+        let mut locked: LockedChainMap<u8, u8, _> = LockedChainMap::with_seed(7);
+        locked.new_child();
+        assert!(locked.maps.iter().all(|map| map.hasher().seed() == 7));
+    }
+
+    #[test]
+    fn integers_are_platform_independent() {
+        let state = SeededState::default();
+        assert_eq!(state.hash_one(1u64), 0x89cd_3129_1d2a_efa4);
+        assert_eq!(state.hash_one(1usize), state.hash_one(1u64));
+        assert_eq!(state.hash_one(-1isize), 0x8cf5_1a8b_fca3_883d);
+        assert_eq!(state.hash_one(-1isize), state.hash_one(u64::MAX));
+        assert_eq!(
+            SeededState::with_seed(7).hash_one(0x1234u32),
+            0xdf31_6fec_1b40_74da
+        );
+    }
+}
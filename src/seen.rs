@@ -0,0 +1,45 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+
+/// The number of keys [`Seen`] tracks before it allocates.
+pub(crate) const INLINE_KEYS: usize = 16;
+
+/// The keys already visited while walking a chain from the innermost
+/// layer out. The first [`INLINE_KEYS`] keys are kept inline and found by
+/// a linear scan; past that they are moved into a set.
+pub(crate) struct Seen<'a, K> {
+    inline: [Option<&'a K>; INLINE_KEYS],
+    len: usize,
+    set: Option<HashSet<&'a K>>,
+}
+
+impl<'a, K: Hash + Eq> Seen<'a, K> {
+    /// Expects about `capacity` distinct keys, going straight to a set
+    /// sized for them if they won't fit inline.
+    pub(crate) fn with_capacity(capacity: usize) -> Self {
+        Self {
+            inline: [None; INLINE_KEYS],
+            len: 0,
+            set: (capacity > INLINE_KEYS).then(|| HashSet::with_capacity(capacity)),
+        }
+    }
+
+    /// Returns `true` if `key` had not been seen yet.
+    pub(crate) fn insert(&mut self, key: &'a K) -> bool {
+        if let Some(set) = &mut self.set {
+            return set.insert(key);
+        }
+        if self.inline[..self.len].contains(&Some(key)) {
+            return false;
+        }
+        if self.len < INLINE_KEYS {
+            self.inline[self.len] = Some(key);
+            self.len += 1;
+        } else {
+            let mut set: HashSet<_> = self.inline.iter().flatten().copied().collect();
+            set.insert(key);
+            self.set = Some(set);
+        }
+        true
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{ChainMap, ChainSet};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::{HashMap, HashSet};
+use std::hash::{BuildHasher, Hash};
+
+// Chains are written as a sequence of layers, outermost first. The length
+// of every sequence is known up front so formats that are not
+// self-describing, like bincode, can read them back.
+
+impl<K, V, S> Serialize for ChainMap<K, V, S>
+where
+    K: Serialize + Hash + Eq,
+    V: Serialize,
+    S: BuildHasher,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serializer.collect_seq(self.layers())
+    }
+}
+
+impl<'de, K, V, S> Deserialize<'de> for ChainMap<K, V, S>
+where
+    K: Deserialize<'de> + Hash + Eq,
+    V: Deserialize<'de>,
+    S: BuildHasher + Default,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut maps: Vec<HashMap<K, V, S>> = Vec::deserialize(deserializer)?;
+        if maps.is_empty() {
+            maps.push(HashMap::default());
+        }
+        Ok(Self::from_maps(maps))
+    }
+}
+
+impl<T> Serialize for ChainSet<T>
+where
+    T: Serialize + Hash + Eq,
+{
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        serializer.collect_seq(&self.sets)
+    }
+}
+
+impl<'de, T> Deserialize<'de> for ChainSet<T>
+where
+    T: Deserialize<'de> + Hash + Eq,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let mut sets: Vec<HashSet<T>> = Vec::deserialize(deserializer)?;
+        if sets.is_empty() {
+            sets.push(HashSet::new());
+        }
+        Ok(Self { sets })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn serde_json_round_trip() {
+        let mut chain_map: ChainMap<String, u8> = ChainMap::default();
+        chain_map.insert("x".to_string(), 0);
+        chain_map.new_child();
+        chain_map.insert("x".to_string(), 1);
+        let json = serde_json::to_string(&chain_map).unwrap();
+        assert_eq!(json, r#"[{"x":0},{"x":1}]"#);
+        let round_trip: ChainMap<String, u8> = serde_json::from_str(&json).unwrap();
+        assert_eq!(round_trip, chain_map);
+    }
+}
@@ -33,6 +33,109 @@ impl<T: Hash + Eq> ChainSet<T> {
         None
     }
 
+    /// Returns the value equal to `value` from the nearest layer holding
+    /// one, first inserting `value` into the current layer if no layer
+    /// does.
+    ///
+    /// `value` is only cloned when it is inserted, to find it again
+    /// afterwards; [`get_or_insert_with`](Self::get_or_insert_with) avoids
+    /// the clone.
+    pub fn get_or_insert(&mut self, value: T) -> &T
+    where
+        T: Clone,
+    {
+        let (top, outer) = self.split_top();
+        if !top.contains(&value) {
+            if let Some(found) = outer.iter().rev().find_map(|set| set.get(&value)) {
+                return found;
+            }
+            top.insert(value.clone());
+        }
+        top.get(&value).expect("value was found or inserted")
+    }
+
+    /// Returns the value equal to `value` from the nearest layer holding
+    /// one, first inserting the result of `f` into the current layer if no
+    /// layer does. This is the interning primitive: `f` is only called,
+    /// e.g. to allocate an owned copy of `value`, when it is missing.
+    pub fn get_or_insert_with<Q, F>(&mut self, value: &Q, f: F) -> &T
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        F: FnOnce(&Q) -> T,
+    {
+        let (top, outer) = self.split_top();
+        if !top.contains(value) {
+            if let Some(found) = outer.iter().rev().find_map(|set| set.get(value)) {
+                return found;
+            }
+            top.insert(f(value));
+        }
+        top.get(value).expect("value was found or inserted")
+    }
+
+    /// The current layer and the layers outside it, for lookups that
+    /// return from an outer layer but insert into the current one.
+    fn split_top(&mut self) -> (&mut HashSet<T>, &[HashSet<T>]) {
+        if self.sets.is_empty() {
+            self.sets.push(HashSet::new());
+        }
+        let (top, outer) = self.sets.split_last_mut().expect("a layer was just pushed");
+        (top, outer)
+    }
+
+    /// Keeps only the values of the current layer for which `f` returns
+    /// `true`.
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        if let Some(set) = self.sets.last_mut() {
+            set.retain(f);
+        }
+    }
+
+    /// Keeps only the values, in every layer, for which `f` returns
+    /// `true`.
+    pub fn retain_all<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&T) -> bool,
+    {
+        for set in &mut self.sets {
+            set.retain(&mut f);
+        }
+    }
+
+    /// Removes every value from every layer, keeping the layers.
+    pub fn clear(&mut self) {
+        self.sets.iter_mut().for_each(HashSet::clear);
+    }
+
+    /// Removes every value from the layer at `idx`.
+    pub fn clear_at(&mut self, idx: usize) -> Result<(), crate::Error> {
+        self.sets
+            .get_mut(idx)
+            .ok_or(crate::Error::IndexOutOfRange)?
+            .clear();
+        Ok(())
+    }
+
+    /// Removes every value from the current layer, keeping its capacity,
+    /// and iterates over them.
+    pub fn drain_top(&mut self) -> impl Iterator<Item = T> + '_ {
+        self.sets.last_mut().into_iter().flat_map(HashSet::drain)
+    }
+
+    /// Removes every value from the layer at `idx`, keeping its capacity,
+    /// and iterates over them.
+    pub fn drain_at(&mut self, idx: usize) -> Result<impl Iterator<Item = T> + '_, crate::Error> {
+        Ok(self
+            .sets
+            .get_mut(idx)
+            .ok_or(crate::Error::IndexOutOfRange)?
+            .drain())
+    }
+
     pub fn new_child(&mut self) {
         self.sets.push(HashSet::new());
     }
@@ -59,6 +162,17 @@ impl<T: Hash + Eq> Default for ChainSet<T> {
     }
 }
 
+impl<T> core::fmt::Debug for ChainSet<T>
+where
+    T: Eq + Hash + core::fmt::Debug,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ChainSet")
+            .field("sets", &self.sets)
+            .finish()
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -142,4 +256,57 @@ mod test {
         assert_eq!(chain_set.get("x"), None);
         assert!(chain_set.sets.len() == 1);
     }
+
+    #[test]
+    fn get_or_insert() {
+        let mut chain_set: ChainSet<String> = ChainSet::default();
+        chain_set.insert("outer".to_string());
+        chain_set.new_child();
+        assert_eq!(chain_set.get_or_insert("outer".to_string()), "outer");
+        assert!(chain_set.sets[1].is_empty());
+        assert_eq!(chain_set.get_or_insert_with("inner", str::to_string), "inner");
+        assert!(chain_set.sets[1].contains("inner"));
+        chain_set.get_or_insert_with("outer", |_| unreachable!());
+        assert_eq!(chain_set.sets[1].len(), 1);
+    }
+
+    #[test]
+    fn clear_and_drain() {
+        let mut chain_set = ChainSet::default();
+        chain_set.insert(0);
+        chain_set.new_child();
+        chain_set.insert(1);
+        chain_set.insert(2);
+        let mut drained: Vec<_> = chain_set.drain_top().collect();
+        drained.sort();
+        assert_eq!(drained, vec![1, 2]);
+        assert_eq!(chain_set.sets.len(), 2);
+        assert_eq!(chain_set.drain_at(0).unwrap().collect::<Vec<_>>(), vec![0]);
+        assert!(chain_set.drain_at(2).is_err());
+        chain_set.insert(3);
+        chain_set.clear_at(1).unwrap();
+        assert!(chain_set.clear_at(2).is_err());
+        assert_eq!(chain_set.get(&3), None);
+        chain_set.insert(4);
+        chain_set.clear();
+        assert_eq!(chain_set.sets.len(), 2);
+        assert_eq!(chain_set.get(&4), None);
+    }
+
+    #[test]
+    fn retain() {
+        let mut chain_set = ChainSet::default();
+        chain_set.insert(1);
+        chain_set.insert(2);
+        chain_set.new_child();
+        chain_set.insert(3);
+        chain_set.insert(4);
+        chain_set.retain(|v| v % 2 == 0);
+        assert_eq!(chain_set.get(&3), None);
+        assert_eq!(chain_set.get(&1), Some(&1));
+        chain_set.retain_all(|v| *v > 1);
+        assert_eq!(chain_set.get(&1), None);
+        assert_eq!(chain_set.get(&2), Some(&2));
+        assert_eq!(chain_set.get(&4), Some(&4));
+    }
 }
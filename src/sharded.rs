@@ -0,0 +1,291 @@
+use crate::ChainMap;
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    mem::{replace, take},
+    sync::{PoisonError, RwLock},
+};
+
+const DEFAULT_SHARDS: usize = 16;
+
+/// A layer split into shards that are locked independently.
+type Shards<K, V, S> = Box<[RwLock<HashMap<K, V, S>>]>;
+
+/// A shared chain whose layers are each split into a fixed number of
+/// shards with their own lock, so threads inserting different keys into
+/// the current scope rarely wait on each other.
+///
+/// A key is hashed once and goes to the same shard in every layer, so a
+/// lookup locks one shard per layer. Pushing or removing a scope locks the
+/// whole chain.
+pub struct ShardedChainMap<K, V, S = RandomState> {
+    hasher: S,
+    layers: RwLock<Vec<Shards<K, V, S>>>,
+}
+
+impl<K, V, S> ShardedChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Clone,
+{
+    /// Creates a chain whose layers have `shards` shards each, at least
+    /// one.
+    pub fn with_shards_and_hasher(shards: usize, hasher: S) -> Self {
+        let layer = Self::empty_layer(shards.max(1), &hasher);
+        Self {
+            hasher,
+            layers: RwLock::new(vec![layer]),
+        }
+    }
+
+    fn empty_layer(shards: usize, hasher: &S) -> Shards<K, V, S> {
+        (0..shards)
+            .map(|_| RwLock::new(HashMap::with_hasher(hasher.clone())))
+            .collect()
+    }
+
+    /// The shard of `key`. Uses the high bits of the hash, since the maps
+    /// inside the shards pick their buckets with the low ones.
+    fn shard<Q>(&self, key: &Q, shards: usize) -> usize
+    where
+        Q: Hash + ?Sized,
+    {
+        (self.hasher.hash_one(key) >> 32) as usize % shards
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.read_layers()[0].len()
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.read_layers().len()
+    }
+
+    /// Returns a copy of the value corresponding to the key.
+    pub fn get<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+        V: Clone,
+    {
+        let layers = self.read_layers();
+        let shard = self.shard(key, layers[0].len());
+        layers.iter().rev().find_map(|layer| {
+            layer[shard]
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get(key)
+                .cloned()
+        })
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let layers = self.read_layers();
+        let shard = self.shard(key, layers[0].len());
+        layers.iter().rev().any(|layer| {
+            layer[shard]
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .contains_key(key)
+        })
+    }
+
+    /// Inserts a key-value pair into the current scope, locking only the
+    /// key's shard of that scope.
+    pub fn insert(&self, key: K, value: V) -> Option<V> {
+        let layers = self.read_layers();
+        let top = layers.last()?;
+        let shard = self.shard(&key, top.len());
+        let old = top[shard]
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(key, value);
+        old
+    }
+
+    /// Removes a key from the current scope, returning its value.
+    pub fn remove<Q>(&self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let layers = self.read_layers();
+        let top = layers.last()?;
+        let shard = self.shard(key, top.len());
+        let old = top[shard]
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(key);
+        old
+    }
+
+    pub fn new_child(&self) {
+        let mut layers = self.write_layers();
+        let layer = Self::empty_layer(layers[0].len(), &self.hasher);
+        layers.push(layer);
+    }
+
+    /// Removes the current scope, merging its shards into one map. As with
+    /// [`ChainMap::remove_child`], the last scope is cleared rather than
+    /// removed.
+    pub fn remove_child(&self) -> Option<HashMap<K, V, S>> {
+        let mut layers = self.write_layers();
+        let layer = if layers.len() == 1 {
+            let empty = Self::empty_layer(layers[0].len(), &self.hasher);
+            replace(&mut layers[0], empty)
+        } else {
+            layers.pop()?
+        };
+        drop(layers);
+        let mut map = HashMap::with_hasher(self.hasher.clone());
+        for shard in layer.into_vec() {
+            map.extend(shard.into_inner().unwrap_or_else(PoisonError::into_inner));
+        }
+        Some(map)
+    }
+
+    /// Copies the current state of the chain into a regular [`ChainMap`].
+    pub fn to_chain_map(&self) -> ChainMap<K, V, S>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let maps = self
+            .read_layers()
+            .iter()
+            .map(|layer| {
+                let mut map = HashMap::with_hasher(self.hasher.clone());
+                for shard in layer.iter() {
+                    let shard = shard.read().unwrap_or_else(PoisonError::into_inner);
+                    map.extend(shard.iter().map(|(k, v)| (k.clone(), v.clone())));
+                }
+                map
+            })
+            .collect();
+        ChainMap::from_maps(maps)
+    }
+
+    /// Moves the chain's entries into a regular [`ChainMap`].
+    pub fn into_chain_map(self) -> ChainMap<K, V, S> {
+        let Self { hasher, layers } = self;
+        let maps = layers
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+            .into_iter()
+            .map(|layer| {
+                let mut map = HashMap::with_hasher(hasher.clone());
+                for shard in layer.into_vec() {
+                    map.extend(shard.into_inner().unwrap_or_else(PoisonError::into_inner));
+                }
+                map
+            })
+            .collect();
+        ChainMap::from_maps(maps)
+    }
+
+    fn read_layers(&self) -> std::sync::RwLockReadGuard<'_, Vec<Shards<K, V, S>>> {
+        self.layers.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_layers(&self) -> std::sync::RwLockWriteGuard<'_, Vec<Shards<K, V, S>>> {
+        self.layers.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<K, V> ShardedChainMap<K, V>
+where
+    K: Hash + Eq,
+{
+    pub fn new() -> Self {
+        Self::with_shards(DEFAULT_SHARDS)
+    }
+
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_shards_and_hasher(shards, RandomState::default())
+    }
+}
+
+impl<K, V> Default for ShardedChainMap<K, V>
+where
+    K: Hash + Eq,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, S> From<ChainMap<K, V, S>> for ShardedChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Clone,
+{
+    fn from(mut chain: ChainMap<K, V, S>) -> Self {
+        chain.force_lazy();
+        let hasher = chain.maps[0].hasher().clone();
+        let sharded = Self::with_shards_and_hasher(DEFAULT_SHARDS, hasher);
+        for (idx, map) in take(&mut chain.maps).into_iter().enumerate() {
+            if idx > 0 {
+                sharded.new_child();
+            }
+            for (key, value) in map {
+                sharded.insert(key, value);
+            }
+        }
+        sharded
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::{sync::Arc, thread};
+
+    #[test]
+    fn scopes() {
+        let chain_map = ShardedChainMap::with_shards(4);
+        chain_map.insert("x", 0);
+        chain_map.insert("y", 0);
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        assert_eq!(chain_map.get("x"), Some(1));
+        assert_eq!(chain_map.get("y"), Some(0));
+        assert_eq!(chain_map.to_chain_map().get("x"), Some(&1));
+        let top = chain_map.remove_child().unwrap();
+        assert_eq!(top.len(), 1);
+        assert_eq!(chain_map.get("x"), Some(0));
+        assert_eq!(chain_map.remove("x"), Some(0));
+        assert!(!chain_map.contains_key("x"));
+        assert_eq!(chain_map.child_len(), 1);
+        assert_eq!(chain_map.shard_count(), 4);
+    }
+
+    #[test]
+    fn concurrent_inserts() {
+        let chain_map = Arc::new(ShardedChainMap::<u32, u32>::new());
+        chain_map.insert(0, 0);
+        chain_map.new_child();
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let chain_map = Arc::clone(&chain_map);
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        chain_map.insert(t * 100 + i, t);
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(chain_map.get(&0), Some(0));
+        assert_eq!(chain_map.get(&799), Some(7));
+        let chain_map = Arc::try_unwrap(chain_map).ok().unwrap().into_chain_map();
+        assert_eq!(chain_map.child_len(), 2);
+        assert_eq!(chain_map.len(), 800);
+    }
+}
@@ -0,0 +1,218 @@
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    ops::Index,
+};
+
+/// A chain whose values live in a slab, so each binding can be reached
+/// through a [`ValueHandle`] without hashing or searching the scopes.
+///
+/// Names can be resolved once, e.g. by a compiler, with
+/// [`handle`](Self::handle) and then accessed by handle at runtime.
+pub struct SlotChainMap<K, V, S = RandomState> {
+    maps: Vec<HashMap<K, usize, S>>,
+    slots: Vec<Slot<V>>,
+    free: Vec<usize>,
+}
+
+struct Slot<V> {
+    generation: u32,
+    value: Option<V>,
+}
+
+/// A stable reference to a binding in a [`SlotChainMap`].
+///
+/// A handle stays valid, whatever else is inserted or looked up, until its
+/// binding is removed along with its scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ValueHandle {
+    slot: usize,
+    generation: u32,
+}
+
+impl<K, V, S> SlotChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    pub fn with_hasher(hasher: S) -> Self {
+        Self {
+            maps: vec![HashMap::with_hasher(hasher)],
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+
+    /// Inserts a key-value pair into the current scope, returning the
+    /// binding's handle. If the scope already binds the key, its value is
+    /// replaced and its existing handle is returned.
+    pub fn insert(&mut self, key: K, value: V) -> ValueHandle {
+        let top = self.maps.len() - 1;
+        if let Some(&slot) = self.maps[top].get(&key) {
+            self.slots[slot].value = Some(value);
+            return self.handle_of(slot);
+        }
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.slots[slot].value = Some(value);
+                slot
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: Some(value),
+                });
+                self.slots.len() - 1
+            }
+        };
+        self.maps[top].insert(key, slot);
+        self.handle_of(slot)
+    }
+
+    fn handle_of(&self, slot: usize) -> ValueHandle {
+        ValueHandle {
+            slot,
+            generation: self.slots[slot].generation,
+        }
+    }
+
+    /// Returns the handle of the nearest binding of the key.
+    pub fn handle<Q>(&self, key: &Q) -> Option<ValueHandle>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let slot = self.maps.iter().rev().find_map(|map| map.get(key))?;
+        Some(self.handle_of(*slot))
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.resolve(self.handle(key)?)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let handle = self.handle(key)?;
+        self.resolve_mut(handle)
+    }
+
+    /// Returns the value of the binding, or `None` if it has been removed.
+    pub fn resolve(&self, handle: ValueHandle) -> Option<&V> {
+        let slot = self.slots.get(handle.slot)?;
+        if slot.generation == handle.generation {
+            slot.value.as_ref()
+        } else {
+            None
+        }
+    }
+
+    pub fn resolve_mut(&mut self, handle: ValueHandle) -> Option<&mut V> {
+        let slot = self.slots.get_mut(handle.slot)?;
+        if slot.generation == handle.generation {
+            slot.value.as_mut()
+        } else {
+            None
+        }
+    }
+
+    pub fn new_child_with_hasher(&mut self, hasher: S) {
+        self.maps.push(HashMap::with_hasher(hasher));
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.maps.len()
+    }
+
+    /// Removes the current scope, invalidating the handles of its
+    /// bindings, and returns its pairs. As with
+    /// [`ChainMap::remove_child`](crate::ChainMap::remove_child), the last
+    /// scope is emptied rather than removed.
+    pub fn remove_child(&mut self) -> Vec<(K, V)> {
+        let map = if self.maps.len() == 1 {
+            self.maps[0].drain().collect::<Vec<_>>()
+        } else {
+            self.maps.pop().into_iter().flatten().collect()
+        };
+        map.into_iter()
+            .filter_map(|(key, slot)| {
+                let entry = &mut self.slots[slot];
+                entry.generation = entry.generation.wrapping_add(1);
+                self.free.push(slot);
+                entry.value.take().map(|value| (key, value))
+            })
+            .collect()
+    }
+}
+
+impl<K, V, S> SlotChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    pub fn new() -> Self {
+        Self::with_hasher(S::default())
+    }
+
+    pub fn new_child(&mut self) {
+        self.new_child_with_hasher(S::default());
+    }
+}
+
+impl<K, V, S> Default for SlotChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, V, S> Index<ValueHandle> for SlotChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Returns the value of the binding.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the binding has been removed.
+    fn index(&self, handle: ValueHandle) -> &V {
+        self.resolve(handle).expect("stale value handle")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn handles() {
+        let mut chain_map: SlotChainMap<&str, i32> = SlotChainMap::new();
+        let outer = chain_map.insert("x", 0);
+        chain_map.new_child();
+        let inner = chain_map.insert("x", 1);
+        assert_eq!(chain_map.handle("x"), Some(inner));
+        assert_eq!(chain_map[outer], 0);
+        *chain_map.resolve_mut(inner).unwrap() += 1;
+        assert_eq!(chain_map.insert("x", 5), inner);
+        assert_eq!(chain_map.remove_child(), vec![("x", 5)]);
+        assert_eq!(chain_map.resolve(inner), None);
+        let reused = chain_map.insert("y", 2);
+        assert_ne!(reused, inner);
+        assert_eq!(chain_map.resolve(inner), None);
+        assert_eq!(chain_map.get("x"), Some(&0));
+        assert_eq!(chain_map.get("y"), Some(&2));
+    }
+}
@@ -0,0 +1,216 @@
+use crate::undo::Undo;
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    ops::Index,
+};
+
+/// A map whose changes can be speculatively made and then either kept or
+/// rolled back, without creating a layer per speculation.
+///
+/// While a [`Snapshot`] is open every change is recorded in an undo log.
+/// Committing the outermost snapshot simply drops the log, so committed
+/// changes cost nothing to merge, and changes made with no snapshot open
+/// are not logged at all.
+pub struct SnapshotChainMap<K, V, S = RandomState> {
+    table: HashMap<K, V, S>,
+    log: Vec<Undo<K, V>>,
+    open: usize,
+}
+
+/// A point a [`SnapshotChainMap`] can be rolled back to, created with
+/// [`SnapshotChainMap::snapshot`].
+///
+/// Snapshots must be committed or rolled back in the reverse of the
+/// order they were taken in.
+#[must_use]
+#[derive(Debug)]
+pub struct Snapshot {
+    len: usize,
+}
+
+impl<K, V, S> SnapshotChainMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    S: BuildHasher,
+{
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            table: HashMap::with_hasher(hash_builder),
+            log: Vec::new(),
+            open: 0,
+        }
+    }
+
+    /// Inserts a key-value pair, returning `true` if the key was already
+    /// bound.
+    pub fn insert(&mut self, key: K, value: V) -> bool {
+        if !self.in_snapshot() {
+            return self.table.insert(key, value).is_some();
+        }
+        let undo = match self.table.insert(key.clone(), value) {
+            Some(old) => Undo::Restore(key, old),
+            None => Undo::Remove(key),
+        };
+        let replaced = matches!(undo, Undo::Restore(..));
+        self.log.push(undo);
+        replaced
+    }
+
+    /// Removes a key, returning `true` if it was bound.
+    pub fn remove<Q>(&mut self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.table.remove_entry(key) {
+            Some((key, old)) => {
+                if self.in_snapshot() {
+                    self.log.push(Undo::Restore(key, old));
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.table.get(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.table.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// Returns `true` if any snapshot is still open.
+    pub fn in_snapshot(&self) -> bool {
+        self.open > 0
+    }
+
+    /// Starts recording changes so they can be rolled back. Snapshots may
+    /// be nested.
+    pub fn snapshot(&mut self) -> Snapshot {
+        self.open += 1;
+        Snapshot {
+            len: self.log.len(),
+        }
+    }
+
+    /// Keeps every change made since `snapshot` was taken.
+    pub fn commit(&mut self, snapshot: Snapshot) {
+        self.close(&snapshot);
+        if self.open == 0 {
+            self.log.clear();
+        }
+    }
+
+    /// Reverts every change made since `snapshot` was taken.
+    pub fn rollback_to(&mut self, snapshot: Snapshot) {
+        self.close(&snapshot);
+        for undo in self.log.drain(snapshot.len..).rev() {
+            undo.revert(&mut self.table);
+        }
+    }
+
+    fn close(&mut self, snapshot: &Snapshot) {
+        assert!(
+            self.open > 0 && snapshot.len <= self.log.len(),
+            "snapshot closed out of order"
+        );
+        self.open -= 1;
+    }
+}
+
+impl<K, V, S> Default for SnapshotChainMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    S: BuildHasher + Default,
+{
+    fn default() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, Q: ?Sized, V, S> Index<&Q> for SnapshotChainMap<K, V, S>
+where
+    K: Eq + Hash + Clone + Borrow<Q>,
+    Q: Eq + Hash,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `SnapshotChainMap`.
+    #[inline]
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K, V, S> core::fmt::Debug for SnapshotChainMap<K, V, S>
+where
+    K: Eq + Hash + core::fmt::Debug,
+    V: core::fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SnapshotChainMap")
+            .field("table", &self.table)
+            .field("open", &self.open)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nested_snapshots() {
+        let mut map: SnapshotChainMap<_, _, RandomState> = SnapshotChainMap::default();
+        map.insert("x", 0);
+        let outer = map.snapshot();
+        map.insert("x", 1);
+        let inner = map.snapshot();
+        map.insert("y", 2);
+        map.remove("x");
+        map.rollback_to(inner);
+        assert_eq!(map["x"], 1);
+        assert!(!map.contains_key("y"));
+        map.commit(outer);
+        assert!(!map.in_snapshot());
+        assert_eq!(map["x"], 1);
+    }
+
+    #[test]
+    fn rollback_outer_after_commit_inner() {
+        let mut map: SnapshotChainMap<_, _, RandomState> = SnapshotChainMap::default();
+        let outer = map.snapshot();
+        map.insert("x", 0);
+        let inner = map.snapshot();
+        map.insert("y", 1);
+        map.commit(inner);
+        map.rollback_to(outer);
+        assert!(map.is_empty());
+    }
+}
@@ -0,0 +1,75 @@
+use crate::ChainMap;
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+};
+
+/// Helpers for chains binding each key to a stack of values, so a key can
+/// be overridden and restored within a single scope, the way template
+/// engines handle `{% push %}` and `{% pop %}` blocks.
+impl<K, V, S> ChainMap<K, Vec<V>, S>
+where
+    K: Hash + Eq,
+    S: BuildHasher,
+{
+    /// Pushes a value onto the key's stack in the current scope, starting
+    /// a new stack if the scope doesn't bind the key yet.
+    pub fn push_value(&mut self, key: K, value: V) {
+        if let Some(idx) = self.maps.len().checked_sub(1) {
+            self.layer_mut(idx).entry(key).or_default().push(value);
+        }
+    }
+
+    /// Pops the value on top of the innermost non-empty stack bound to the
+    /// key, unbinding the key from that scope once its stack is empty.
+    pub fn pop_value<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let idx = (0..self.maps.len())
+            .rev()
+            .find(|idx| self.layer(*idx).get(key).is_some_and(|s| !s.is_empty()))?;
+        let layer = self.layer_mut(idx);
+        let stack = layer.get_mut(key)?;
+        let value = stack.pop();
+        if stack.is_empty() {
+            layer.remove(key);
+        }
+        value
+    }
+
+    /// Returns the value on top of the innermost non-empty stack bound to
+    /// the key.
+    pub fn top_value<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.layers().rev().find_map(|map| map.get(key)?.last())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn push_and_pop() {
+        let mut chain_map: ChainMap<&str, Vec<&str>> = ChainMap::default();
+        chain_map.push_value("title", "Home");
+        chain_map.new_child();
+        chain_map.insert("title", Vec::new());
+        assert_eq!(chain_map.top_value("title"), Some(&"Home"));
+        chain_map.push_value("title", "Blog");
+        chain_map.push_value("title", "Post");
+        assert_eq!(chain_map.top_value("title"), Some(&"Post"));
+        assert_eq!(chain_map.pop_value("title"), Some("Post"));
+        assert_eq!(chain_map.pop_value("title"), Some("Blog"));
+        assert!(!chain_map.last_has("title"));
+        assert_eq!(chain_map.top_value("title"), Some(&"Home"));
+        assert_eq!(chain_map.pop_value("title"), Some("Home"));
+        assert_eq!(chain_map.pop_value("title"), None);
+        assert_eq!(chain_map.top_value("title"), None);
+    }
+}
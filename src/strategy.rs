@@ -0,0 +1,100 @@
+//! [`proptest`] strategies for generating chains.
+//!
+//! Chains are generated as a vector of layers, so shrinking removes whole
+//! layers before it starts removing or simplifying the entries inside them.
+
+use crate::{ChainMap, ChainSet};
+use proptest::{
+    collection::{hash_map, hash_set, vec, SizeRange},
+    strategy::Strategy,
+};
+use std::{hash::Hash, ops::Range};
+
+const LAYER_SIZE: Range<usize> = 0..16;
+
+/// Generates a [`ChainMap`] with a number of layers drawn from `depth`.
+///
+/// A depth of zero is treated as one, since a chain always has a layer.
+pub fn chain_map_strategy<K, V>(
+    key: K,
+    value: V,
+    depth: impl Into<SizeRange>,
+) -> impl Strategy<Value = ChainMap<K::Value, V::Value>>
+where
+    K: Strategy + Clone,
+    K::Value: Hash + Eq,
+    V: Strategy + Clone,
+{
+    chain_map_strategy_with_layer_size(key, value, depth, LAYER_SIZE)
+}
+
+/// Like [`chain_map_strategy`], with the number of entries in each layer
+/// drawn from `layer_size`.
+pub fn chain_map_strategy_with_layer_size<K, V>(
+    key: K,
+    value: V,
+    depth: impl Into<SizeRange>,
+    layer_size: impl Into<SizeRange>,
+) -> impl Strategy<Value = ChainMap<K::Value, V::Value>>
+where
+    K: Strategy + Clone,
+    K::Value: Hash + Eq,
+    V: Strategy + Clone,
+{
+    vec(hash_map(key, value, layer_size), depth).prop_map(|layers| {
+        let mut maps: Vec<_> = layers
+            .into_iter()
+            .map(|layer| layer.into_iter().collect())
+            .collect();
+        if maps.is_empty() {
+            maps.push(Default::default());
+        }
+        ChainMap::from_maps(maps)
+    })
+}
+
+/// Generates a [`ChainSet`] with a number of layers drawn from `depth`.
+pub fn chain_set_strategy<T>(
+    element: T,
+    depth: impl Into<SizeRange>,
+) -> impl Strategy<Value = ChainSet<T::Value>>
+where
+    T: Strategy + Clone,
+    T::Value: Hash + Eq,
+{
+    vec(hash_set(element, LAYER_SIZE), depth).prop_map(|layers| {
+        let mut sets: Vec<_> = layers
+            .into_iter()
+            .map(|layer| layer.into_iter().collect())
+            .collect();
+        if sets.is_empty() {
+            sets.push(Default::default());
+        }
+        ChainSet { sets }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use proptest::prelude::*;
+    use std::collections::HashMap;
+
+    proptest! {
+        #[test]
+        fn get_matches_flattened(chain_map in chain_map_strategy(0u8..16, any::<u8>(), 1..6)) {
+            let mut flat = HashMap::new();
+            for map in &chain_map.maps {
+                flat.extend(map.iter().map(|(k, v)| (*k, *v)));
+            }
+            for k in 0u8..16 {
+                prop_assert_eq!(chain_map.get(&k), flat.get(&k));
+            }
+        }
+
+        #[test]
+        fn depth_in_range(chain_set in chain_set_strategy(any::<u8>(), 2..4)) {
+            prop_assert!((2..4).contains(&chain_set.sets.len()));
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::ChainMap;
+use std::{
+    borrow::Borrow,
+    hash::{BuildHasher, Hash},
+};
+
+impl<K, V, S> ChainMap<K, V, S>
+where
+    K: Hash + Eq + Borrow<str>,
+    S: BuildHasher,
+{
+    /// Like [`try_get`](Self::try_get), failing with
+    /// [`Error::UnknownKey`](crate::Error::UnknownKey), which carries the
+    /// closest visible key as a suggestion, e.g. for "unknown setting"
+    /// messages.
+    pub fn get_or_err(&self, key: &str) -> Result<&V, crate::Error> {
+        self.get(key).ok_or_else(|| crate::Error::UnknownKey {
+            key: key.to_string(),
+            suggestion: self.suggest(key).map(|k| k.borrow().to_string()),
+        })
+    }
+
+    /// Returns the visible key closest to `key` by edit distance, if it is
+    /// close enough to be a likely typo: one edit for each three chars of
+    /// `key`, but always at least one. Ties go to the key that sorts
+    /// first.
+    pub fn suggest(&self, key: &str) -> Option<&K> {
+        let max = (key.chars().count() / 3).max(1);
+        self.effective_keys()
+            .into_iter()
+            .map(|k| (edit_distance(key, k.borrow()), k.borrow(), k))
+            .filter(|(distance, _, _)| *distance <= max)
+            .min_by_key(|(distance, name, _): &(usize, &str, &K)| (*distance, *name))
+            .map(|(_, _, k)| k)
+    }
+}
+
+/// The Levenshtein distance between `a` and `b`, counted in chars.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn distance() {
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("colour", "color"), 1);
+    }
+
+    #[test]
+    fn did_you_mean() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("color", 0);
+        chain_map.insert("verbose", 0);
+        chain_map.new_child();
+        chain_map.insert("colors", 1);
+        assert_eq!(chain_map.get_or_err("color").unwrap(), &0);
+        match chain_map.get_or_err("colr") {
+            Err(e @ Error::UnknownKey { .. }) => {
+                assert_eq!(e.to_string(), "Unknown key `colr`, did you mean `color`?")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(
+            chain_map.get_or_err("quiet"),
+            Err(Error::UnknownKey {
+                suggestion: None,
+                ..
+            })
+        ));
+        assert!(matches!(
+            chain_map.try_get("quiet"),
+            Err(Error::KeyNotFound)
+        ));
+    }
+}
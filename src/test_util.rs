@@ -0,0 +1,122 @@
+//! Assertions and fixtures for testing code built on the chains.
+//!
+//! The assertions are exported at the crate root as macros:
+//! [`assert_resolves!`](crate::assert_resolves) and
+//! [`assert_shadowed!`](crate::assert_shadowed).
+
+use crate::ChainMap;
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    hash::{BuildHasher, Hash},
+};
+
+/// Builds a chain with one layer per item of `layers`, outermost first.
+/// An empty `layers` gives a chain with a single empty layer.
+///
+/// ```
+/// # use hash_chain::test_util::chain_from_layers;
+/// let chain_map = chain_from_layers(vec![vec![("x", 1)], vec![("x", 2), ("y", 3)]]);
+/// assert_eq!(chain_map.child_len(), 2);
+/// ```
+pub fn chain_from_layers<K, V, L, I>(layers: L) -> ChainMap<K, V>
+where
+    K: Hash + Eq,
+    L: IntoIterator<Item = I>,
+    I: IntoIterator<Item = (K, V)>,
+{
+    let mut maps: Vec<HashMap<K, V>> = layers
+        .into_iter()
+        .map(|layer| layer.into_iter().collect())
+        .collect();
+    if maps.is_empty() {
+        maps.push(HashMap::default());
+    }
+    ChainMap::from_maps(maps)
+}
+
+/// The indices of the layers binding `key`, outermost first.
+pub fn bound_layers<K, V, S, Q>(chain: &ChainMap<K, V, S>, key: &Q) -> Vec<usize>
+where
+    K: Hash + Eq + Borrow<Q>,
+    S: BuildHasher,
+    Q: Hash + Eq + ?Sized,
+{
+    chain
+        .layers()
+        .enumerate()
+        .filter(|(_, map)| map.contains_key(key))
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+/// Asserts that each key resolves to the given value through the chain.
+///
+/// ```
+/// # use hash_chain::{assert_resolves, test_util::chain_from_layers};
+/// let chain_map = chain_from_layers(vec![vec![("x", 1)], vec![("x", 2), ("y", 3)]]);
+/// assert_resolves!(chain_map, "x" => 2, "y" => 3);
+/// ```
+#[macro_export]
+macro_rules! assert_resolves {
+    ($chain:expr, $($key:expr => $value:expr),+ $(,)?) => {{
+        let chain = &$chain;
+        $(
+            match chain.get($key) {
+                Some(found) if *found == $value => {}
+                found => panic!(
+                    "expected {:?} to resolve to {:?}, found {:?}",
+                    $key, $value, found
+                ),
+            }
+        )+
+    }};
+}
+
+/// Asserts that a key is bound in exactly the given layers, outermost
+/// first.
+///
+/// ```
+/// # use hash_chain::{assert_shadowed, test_util::chain_from_layers};
+/// let chain_map = chain_from_layers(vec![vec![("x", 1)], vec![], vec![("x", 2)]]);
+/// assert_shadowed!(chain_map, "x", layers = [0, 2]);
+/// ```
+#[macro_export]
+macro_rules! assert_shadowed {
+    ($chain:expr, $key:expr, layers = [$($layer:expr),* $(,)?] $(,)?) => {{
+        let found = $crate::test_util::bound_layers(&$chain, $key);
+        let expected: ::std::vec::Vec<usize> = ::std::vec![$($layer),*];
+        if found != expected {
+            panic!(
+                "expected {:?} to be bound in layers {:?}, found it in {:?}",
+                $key, expected, found
+            );
+        }
+    }};
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn assertions() {
+        let chain_map = chain_from_layers(vec![
+            vec![("x".to_string(), "outer".to_string())],
+            vec![("y".to_string(), "middle".to_string())],
+            vec![("x".to_string(), "inner".to_string())],
+        ]);
+        assert_resolves!(chain_map, "x" => "inner", "y" => "middle");
+        assert_shadowed!(chain_map, "x", layers = [0, 2]);
+        assert_shadowed!(chain_map, "z", layers = []);
+        let empty: ChainMap<&str, i32> = chain_from_layers(Vec::<Vec<_>>::new());
+        assert_eq!(empty.child_len(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected \"x\" to resolve to 1, found Some(2)")]
+    fn resolves_failure() {
+        let chain_map = chain_from_layers(vec![vec![("x", 1)], vec![("x", 2)]]);
+        assert_resolves!(chain_map, "x" => 1);
+    }
+}
@@ -0,0 +1,71 @@
+use std::collections::HashSet;
+use std::{
+    hash::Hash,
+    sync::{Mutex, MutexGuard, PoisonError},
+};
+
+/// Records the keys a chain resolved from outside its innermost scopes,
+/// see [`ChainMap::start_tracking`](crate::ChainMap::start_tracking).
+pub(crate) struct ReadTracker<K> {
+    /// The number of innermost scopes whose reads aren't recorded
+    pub(crate) depth: usize,
+    clone_key: fn(&K) -> K,
+    captured: Mutex<HashSet<K>>,
+}
+
+impl<K: Hash + Eq> ReadTracker<K> {
+    pub(crate) fn new(depth: usize) -> Self
+    where
+        K: Clone,
+    {
+        Self {
+            depth,
+            clone_key: K::clone,
+            captured: Mutex::new(HashSet::default()),
+        }
+    }
+
+    /// A tracker with the same settings that hasn't recorded anything.
+    pub(crate) fn restart(&self) -> Self {
+        Self {
+            depth: self.depth,
+            clone_key: self.clone_key,
+            captured: Mutex::new(HashSet::default()),
+        }
+    }
+
+    /// Records a read of `key` from the layer at `idx` of a chain with
+    /// `len` layers.
+    pub(crate) fn record(&self, idx: usize, len: usize, key: &K) {
+        if idx + self.depth < len {
+            let mut captured = self.captured();
+            if !captured.contains(key) {
+                captured.insert((self.clone_key)(key));
+            }
+        }
+    }
+}
+
+impl<K> ReadTracker<K> {
+    /// The keys recorded so far. A panic while the set was locked can't
+    /// leave it half updated, so a poisoned lock is used as is.
+    pub(crate) fn captured(&self) -> MutexGuard<'_, HashSet<K>> {
+        self.captured.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn into_captured(self) -> HashSet<K> {
+        self.captured
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<K: Clone> Clone for ReadTracker<K> {
+    fn clone(&self) -> Self {
+        Self {
+            depth: self.depth,
+            clone_key: self.clone_key,
+            captured: Mutex::new(self.captured().clone()),
+        }
+    }
+}
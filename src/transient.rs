@@ -0,0 +1,188 @@
+use crate::{imutable::Layer, LockedChainMap};
+use std::{
+    borrow::Borrow,
+    collections::HashMap,
+    hash::{BuildHasher, Hash},
+};
+
+/// A [`LockedChainMap`] opened for bulk mutation, see
+/// [`LockedChainMap::transient`].
+///
+/// The first write to a layer copies it into a regular hash map, so
+/// further writes to it don't pay for structural sharing. Layers that are
+/// never written stay shared with the original chain.
+pub struct TransientChainMap<K, V, S> {
+    maps: Vec<TransientLayer<K, V, S>>,
+}
+
+enum TransientLayer<K, V, S> {
+    Shared(Layer<K, V, S>),
+    /// An empty layer carrying the original's hasher, and the entries to
+    /// fill it with
+    Owned(Layer<K, V, S>, HashMap<K, V>),
+}
+
+impl<K, V, S> LockedChainMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    /// Opens the chain for a batch of inserts and removals, which are
+    /// applied to a new chain by [`TransientChainMap::persist`].
+    pub fn transient(&self) -> TransientChainMap<K, V, S> {
+        TransientChainMap {
+            maps: self
+                .maps
+                .iter()
+                .cloned()
+                .map(TransientLayer::Shared)
+                .collect(),
+        }
+    }
+}
+
+impl<K, V, S> TransientLayer<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self {
+            TransientLayer::Shared(map) => map.get(key),
+            TransientLayer::Owned(_, map) => map.get(key),
+        }
+    }
+
+    fn owned(&mut self) -> &mut HashMap<K, V> {
+        if let TransientLayer::Shared(map) = self {
+            let entries = map.iter().map(|(k, v)| (k.clone(), v.clone())).collect();
+            let mut empty = map.clone();
+            empty.clear();
+            *self = TransientLayer::Owned(empty, entries);
+        }
+        match self {
+            TransientLayer::Owned(_, map) => map,
+            TransientLayer::Shared(_) => unreachable!(),
+        }
+    }
+
+    fn persist(self) -> Layer<K, V, S> {
+        match self {
+            TransientLayer::Shared(map) => map,
+            TransientLayer::Owned(mut empty, entries) => {
+                empty.extend(entries);
+                empty
+            }
+        }
+    }
+}
+
+impl<K, V, S> TransientChainMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Clone,
+{
+    /// Inserts a key-value pair into the current scope.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        self.maps.last_mut()?.owned().insert(key, value)
+    }
+
+    pub fn insert_at(&mut self, idx: usize, key: K, value: V) -> Result<Option<V>, crate::Error> {
+        match self.maps.get_mut(idx) {
+            Some(layer) => Ok(layer.owned().insert(key, value)),
+            None => Err(crate::Error::IndexOutOfRange),
+        }
+    }
+
+    /// Removes the key from the current scope, returning its value.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.last_mut()?.owned().remove(key)
+    }
+
+    pub fn remove_at<Q>(&mut self, idx: usize, key: &Q) -> Result<Option<V>, crate::Error>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        match self.maps.get_mut(idx) {
+            Some(layer) => Ok(layer.owned().remove(key)),
+            None => Err(crate::Error::IndexOutOfRange),
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.maps.iter().rev().find_map(|layer| layer.get(key))
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get(key).is_some()
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.maps.len()
+    }
+
+    /// Builds the persistent chain holding every change made so far.
+    pub fn persist(self) -> LockedChainMap<K, V, S> {
+        LockedChainMap {
+            maps: self.maps.into_iter().map(TransientLayer::persist).collect(),
+        }
+    }
+}
+
+impl<K, V, S> TransientChainMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    V: Clone,
+    S: BuildHasher + Default + Clone,
+{
+    pub fn new_child(&mut self) {
+        self.maps
+            .push(TransientLayer::Owned(Layer::default(), HashMap::new()));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bulk_load() {
+        let mut chain_map: LockedChainMap<_, _> = LockedChainMap::default();
+        chain_map.insert(0, 0);
+        let original = chain_map.clone();
+        let mut transient = chain_map.transient();
+        transient.new_child();
+        for i in 0..1000 {
+            transient.insert(i, i);
+        }
+        assert_eq!(transient.remove(&5), Some(5));
+        assert_eq!(transient.insert_at(0, 1, 1).unwrap(), None);
+        assert!(!transient.contains_key(&5));
+        let chain_map = transient.persist();
+        assert_eq!(chain_map.child_len(), 2);
+        assert_eq!(chain_map.get(&999), Some(&999));
+        assert_eq!(chain_map.get(&5), None);
+        assert_eq!(chain_map.get_at(0, &1), Some(&1));
+        assert_eq!(original.get_at(0, &1), None);
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    borrow::Borrow,
+    collections::{hash_map::RandomState, HashMap},
+    hash::{BuildHasher, Hash},
+    ops::Index,
+};
+
+/// How to revert a single insert into an [`UndoChainMap`].
+pub(crate) enum Undo<K, V> {
+    /// The key was unbound before the insert
+    Remove(K),
+    /// The key was bound to this value before the insert
+    Restore(K, V),
+}
+
+impl<K: Hash + Eq, V> Undo<K, V> {
+    pub(crate) fn revert<S: BuildHasher>(self, table: &mut HashMap<K, V, S>) {
+        match self {
+            Undo::Remove(key) => {
+                table.remove(&key);
+            }
+            Undo::Restore(key, value) => {
+                table.insert(key, value);
+            }
+        }
+    }
+}
+
+/// A chain that keeps every visible binding in a single table and records
+/// each insert in an undo log instead of giving each scope its own map.
+///
+/// Pushing a scope only remembers the length of the log and popping one
+/// replays the log back to that point, so neither allocates a map. The
+/// trade-off is that shadowed bindings are not reachable until the scope
+/// shadowing them is removed.
+pub struct UndoChainMap<K, V, S = RandomState> {
+    table: HashMap<K, V, S>,
+    log: Vec<Undo<K, V>>,
+    marks: Vec<usize>,
+}
+
+impl<K, V, S> UndoChainMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    S: BuildHasher,
+{
+    pub fn with_hasher(hash_builder: S) -> Self {
+        Self {
+            table: HashMap::with_hasher(hash_builder),
+            log: Vec::new(),
+            marks: Vec::new(),
+        }
+    }
+
+    /// Inserts a key-value pair into the current scope, returning `true`
+    /// if the key was already bound.
+    ///
+    /// Unlike [`ChainMap::insert`](crate::ChainMap::insert) the previous
+    /// value is not returned, since it is kept in the undo log to be
+    /// restored when the scope is removed.
+    pub fn insert(&mut self, key: K, value: V) -> bool {
+        if self.marks.is_empty() {
+            return self.table.insert(key, value).is_some();
+        }
+        let undo = match self.table.insert(key.clone(), value) {
+            Some(old) => Undo::Restore(key, old),
+            None => Undo::Remove(key),
+        };
+        let replaced = matches!(undo, Undo::Restore(..));
+        self.log.push(undo);
+        replaced
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.table.get(key)
+    }
+
+    /// Returns a mutable reference to the visible binding of the key.
+    /// Changes made to a binding from an outer scope outlive the current
+    /// scope, as they would with [`ChainMap::get_mut`](crate::ChainMap::get_mut).
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.table.get_mut(key)
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.table.contains_key(key)
+    }
+
+    pub fn new_child(&mut self) {
+        self.marks.push(self.log.len());
+    }
+
+    /// Reverts every insert made since the matching
+    /// [`new_child`](Self::new_child). As with
+    /// [`ChainMap::remove_child`](crate::ChainMap::remove_child), the
+    /// outermost scope is cleared rather than removed, in which case this
+    /// returns `false`.
+    ///
+    /// Inserts into the outermost scope are never logged, since nothing
+    /// outlives it to restore.
+    pub fn remove_child(&mut self) -> bool {
+        let mark = match self.marks.pop() {
+            Some(mark) => mark,
+            None => {
+                self.table.clear();
+                return false;
+            }
+        };
+        for undo in self.log.drain(mark..).rev() {
+            undo.revert(&mut self.table);
+        }
+        true
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.marks.len() + 1
+    }
+
+    /// The number of visible keys.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+}
+
+impl<K, V, S> UndoChainMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    S: BuildHasher + Default,
+{
+    pub fn new() -> Self {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<K, V, S> Default for UndoChainMap<K, V, S>
+where
+    K: Hash + Eq + Clone,
+    S: BuildHasher + Default,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, Q: ?Sized, V, S> Index<&Q> for UndoChainMap<K, V, S>
+where
+    K: Eq + Hash + Clone + Borrow<Q>,
+    Q: Eq + Hash,
+    S: BuildHasher,
+{
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `UndoChainMap`.
+    #[inline]
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<K, V, S> core::fmt::Debug for UndoChainMap<K, V, S>
+where
+    K: Eq + Hash + core::fmt::Debug,
+    V: core::fmt::Debug,
+    S: BuildHasher,
+{
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("UndoChainMap")
+            .field("table", &self.table)
+            .field("depth", &(self.marks.len() + 1))
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::hash_map::RandomState;
+
+    #[test]
+    fn undo_scopes() {
+        let mut chain_map: UndoChainMap<_, _, RandomState> = UndoChainMap::new();
+        chain_map.insert("x", 0);
+        assert!(chain_map.log.is_empty());
+        chain_map.new_child();
+        chain_map.insert("x", 1);
+        chain_map.insert("x", 2);
+        chain_map.insert("y", 3);
+        *chain_map.get_mut("x").unwrap() += 1;
+        assert_eq!(chain_map["x"], 3);
+        assert_eq!(chain_map.child_len(), 2);
+        assert!(chain_map.remove_child());
+        assert_eq!(chain_map["x"], 0);
+        assert!(!chain_map.contains_key("y"));
+        assert!(!chain_map.remove_child());
+        assert!(chain_map.is_empty());
+    }
+
+    #[test]
+    fn undo_outer_mutation_persists() {
+        let mut chain_map: UndoChainMap<_, _, RandomState> = UndoChainMap::default();
+        chain_map.insert("x", 0);
+        chain_map.new_child();
+        *chain_map.get_mut("x").unwrap() = 5;
+        chain_map.remove_child();
+        assert_eq!(chain_map["x"], 5);
+    }
+}
@@ -0,0 +1,6 @@
+use std::{error::Error, sync::Arc};
+
+/// A check run against every key-value pair before a checked insert, see
+/// [`ChainMap::set_validator`](crate::ChainMap::set_validator).
+pub(crate) type Validator<K, V> =
+    Arc<dyn Fn(&K, &V) -> Result<(), Box<dyn Error + Send + Sync>> + Send + Sync>;
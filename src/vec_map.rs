@@ -0,0 +1,162 @@
+use std::{mem::take, ops::Index};
+
+/// A chain keyed by small integers, such as interned symbol ids, where each
+/// layer is a vector indexed directly by key instead of a hash map.
+///
+/// Each layer grows to fit the largest key inserted into it, so keys should
+/// be dense.
+pub struct ChainVecMap<V> {
+    maps: Vec<Vec<Option<V>>>,
+}
+
+impl<V> ChainVecMap<V> {
+    pub fn new() -> Self {
+        Self {
+            maps: vec![Vec::new()],
+        }
+    }
+
+    /// Inserts a key-value pair into the current scope.
+    /// If the current scope did not have this key present, None is returned.
+    pub fn insert(&mut self, key: usize, value: V) -> Option<V> {
+        let idx = self.maps.len().checked_sub(1)?;
+        Self::insert_into(&mut self.maps[idx], key, value)
+    }
+
+    pub fn insert_at(
+        &mut self,
+        idx: usize,
+        key: usize,
+        value: V,
+    ) -> Result<Option<V>, crate::Error> {
+        let map = self
+            .maps
+            .get_mut(idx)
+            .ok_or(crate::Error::IndexOutOfRange)?;
+        Ok(Self::insert_into(map, key, value))
+    }
+
+    fn insert_into(map: &mut Vec<Option<V>>, key: usize, value: V) -> Option<V> {
+        if map.len() <= key {
+            map.resize_with(key + 1, || None);
+        }
+        map[key].replace(value)
+    }
+
+    pub fn get(&self, key: usize) -> Option<&V> {
+        self.maps
+            .iter()
+            .rev()
+            .find_map(|map| map.get(key)?.as_ref())
+    }
+
+    pub fn get_mut(&mut self, key: usize) -> Option<&mut V> {
+        self.maps
+            .iter_mut()
+            .rev()
+            .find_map(|map| map.get_mut(key)?.as_mut())
+    }
+
+    pub fn contains_key(&self, key: usize) -> bool {
+        self.get(key).is_some()
+    }
+
+    pub fn last_has(&self, key: usize) -> bool {
+        self.maps
+            .last()
+            .and_then(|map| map.get(key))
+            .is_some_and(Option::is_some)
+    }
+
+    pub fn get_last_index(&self, key: usize) -> Option<usize> {
+        self.maps
+            .iter()
+            .rposition(|map| map.get(key).is_some_and(Option::is_some))
+    }
+
+    pub fn new_child(&mut self) {
+        self.maps.push(Vec::new());
+    }
+
+    pub fn new_child_with(&mut self, map: Vec<Option<V>>) {
+        self.maps.push(map);
+    }
+
+    pub fn remove_child(&mut self) -> Option<Vec<Option<V>>> {
+        if self.maps.len() == 1 {
+            Some(take(&mut self.maps[0]))
+        } else {
+            self.maps.pop()
+        }
+    }
+
+    pub fn child_len(&self) -> usize {
+        self.maps.len()
+    }
+}
+
+impl<V> Default for ChainVecMap<V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<V> Index<usize> for ChainVecMap<V> {
+    type Output = V;
+
+    /// Returns a reference to the value corresponding to the supplied key.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is not present in the `ChainVecMap`.
+    #[inline]
+    fn index(&self, key: usize) -> &V {
+        self.get(key).expect("no entry found for key")
+    }
+}
+
+impl<V: PartialEq> PartialEq for ChainVecMap<V> {
+    fn eq(&self, other: &ChainVecMap<V>) -> bool {
+        self.maps == other.maps
+    }
+}
+
+impl<V: Eq> Eq for ChainVecMap<V> {}
+
+impl<V: core::fmt::Debug> core::fmt::Debug for ChainVecMap<V> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("ChainVecMap")
+            .field("maps", &self.maps)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn vec_map_scopes() {
+        let mut chain_map = ChainVecMap::new();
+        chain_map.insert(2, "outer");
+        chain_map.new_child();
+        assert_eq!(chain_map.insert(2, "inner"), None);
+        assert_eq!(chain_map[2], "inner");
+        assert_eq!(chain_map.get(5), None);
+        assert!(chain_map.last_has(2));
+        *chain_map.get_mut(2).unwrap() = "changed";
+        let removed = chain_map.remove_child().unwrap();
+        assert_eq!(removed[2], Some("changed"));
+        assert_eq!(chain_map[2], "outer");
+    }
+
+    #[test]
+    fn vec_map_insert_at() {
+        let mut chain_map = ChainVecMap::default();
+        chain_map.new_child();
+        chain_map.insert_at(0, 7, 1).unwrap();
+        assert!(chain_map.insert_at(2, 7, 1).is_err());
+        assert_eq!(chain_map.get_last_index(7), Some(0));
+        assert!(!chain_map.last_has(7));
+    }
+}
@@ -0,0 +1,359 @@
+use crate::{lazy::LazyLayer, ChainMap, ChainSet};
+use std::{
+    borrow::Borrow,
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hash},
+    ops::Deref,
+    sync::Arc,
+};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+impl<K, V, S> ChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    V: Zeroize,
+    S: BuildHasher + Default,
+{
+    /// Scrubs every value in the layer at `idx` and empties it. The keys
+    /// are dropped without being scrubbed.
+    ///
+    /// Fails with `Error::SharedLayer` if a [`CapturedEnv`](crate::CapturedEnv)
+    /// still holds the layer, since scrubbing a private copy would leave
+    /// the captured values behind.
+    pub fn zeroize_layer(&mut self, idx: usize) -> Result<(), crate::Error> {
+        if idx >= self.maps.len() {
+            return Err(crate::Error::IndexOutOfRange);
+        }
+        let shared = self
+            .lazy
+            .iter()
+            .filter(|l| l.idx == idx)
+            .find_map(LazyLayer::as_shared);
+        if shared.is_some_and(|map| Arc::strong_count(map) > 1) {
+            return Err(crate::Error::SharedLayer);
+        }
+        let layer = self.layer_mut(idx);
+        layer.values_mut().for_each(Zeroize::zeroize);
+        layer.clear();
+        Ok(())
+    }
+
+    /// Scrubs the values of the current scope before removing it, see
+    /// [`zeroize_layer`](Self::zeroize_layer). As with
+    /// [`remove_child`](Self::remove_child), the last scope is emptied
+    /// rather than removed. A scope still shared with a captured
+    /// environment is removed without being scrubbed.
+    pub fn remove_child_zeroized(&mut self) {
+        if let Some(idx) = self.maps.len().checked_sub(1) {
+            let _ = self.zeroize_layer(idx);
+            self.remove_child();
+        }
+    }
+}
+
+/// Scrubs the values of every scope, leaving a single empty scope.
+///
+/// A plain chain neither scrubs the scopes [`remove_child`] pops nor its
+/// scopes when it is dropped; use a [`ZeroizingChainMap`] for that.
+///
+/// [`remove_child`]: ChainMap::remove_child
+impl<K, V, S> Zeroize for ChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    V: Zeroize,
+    S: BuildHasher + Default,
+{
+    fn zeroize(&mut self) {
+        while self.maps.len() > 1 {
+            self.remove_child_zeroized();
+        }
+        self.remove_child_zeroized();
+    }
+}
+
+impl<T> ChainSet<T>
+where
+    T: Hash + Eq + Zeroize,
+{
+    /// Scrubs every element of the layer at `idx` and empties it.
+    ///
+    /// A `HashSet` gives no mutable access to its elements, so each one is
+    /// moved out of the set before it is scrubbed. Memory an element owns,
+    /// like the buffer of a `Vec` or `String`, is scrubbed in place, but
+    /// the bytes of the element itself are left behind in the set's table.
+    /// Elements that keep their secret inline, like `[u8; 32]`, are not
+    /// reliably scrubbed.
+    pub fn zeroize_layer(&mut self, idx: usize) -> Result<(), crate::Error> {
+        let set = self
+            .sets
+            .get_mut(idx)
+            .ok_or(crate::Error::IndexOutOfRange)?;
+        set.drain().for_each(|mut v| v.zeroize());
+        Ok(())
+    }
+}
+
+/// Scrubs the elements of every scope, leaving a single empty scope.
+impl<T> Zeroize for ChainSet<T>
+where
+    T: Hash + Eq + Zeroize,
+{
+    fn zeroize(&mut self) {
+        for idx in 0..self.sets.len() {
+            let _ = self.zeroize_layer(idx);
+        }
+        self.sets.truncate(1);
+    }
+}
+
+/// A [`ChainMap`] of secrets that scrubs every value it lets go of: the
+/// values of a scope when it is removed, a value when it is replaced, and
+/// every scope when the chain is dropped.
+///
+/// Dereferences to the underlying chain for lookups. Changes go through
+/// the wrapper, so no scope can be popped without being scrubbed.
+pub struct ZeroizingChainMap<K, V, S = RandomState>
+where
+    K: Hash + Eq,
+    V: Zeroize,
+    S: BuildHasher + Default,
+{
+    chain: ChainMap<K, V, S>,
+}
+
+impl<K, V, S> ZeroizingChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    V: Zeroize,
+    S: BuildHasher + Default,
+{
+    pub fn new(chain: ChainMap<K, V, S>) -> Self {
+        Self { chain }
+    }
+
+    /// Binds the key in the current scope, scrubbing the value it
+    /// replaces.
+    pub fn insert(&mut self, key: K, value: V) {
+        if let Some(mut old) = self.chain.insert(key, value) {
+            old.zeroize();
+        }
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.chain.get_mut(key)
+    }
+
+    pub fn new_child(&mut self) {
+        self.chain.new_child();
+    }
+
+    /// Scrubs and removes the current scope. As with
+    /// [`ChainMap::remove_child`], the last scope is emptied rather than
+    /// removed.
+    pub fn remove_child(&mut self) {
+        self.chain.remove_child_zeroized();
+    }
+
+    /// See [`ChainMap::zeroize_layer`].
+    pub fn zeroize_layer(&mut self, idx: usize) -> Result<(), crate::Error> {
+        self.chain.zeroize_layer(idx)
+    }
+}
+
+impl<K, V, S> Default for ZeroizingChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    V: Zeroize,
+    S: BuildHasher + Default,
+{
+    fn default() -> Self {
+        Self::new(ChainMap::default())
+    }
+}
+
+impl<K, V, S> Deref for ZeroizingChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    V: Zeroize,
+    S: BuildHasher + Default,
+{
+    type Target = ChainMap<K, V, S>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.chain
+    }
+}
+
+impl<K, V, S> Zeroize for ZeroizingChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    V: Zeroize,
+    S: BuildHasher + Default,
+{
+    fn zeroize(&mut self) {
+        self.chain.zeroize();
+    }
+}
+
+impl<K, V, S> Drop for ZeroizingChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    V: Zeroize,
+    S: BuildHasher + Default,
+{
+    fn drop(&mut self) {
+        self.chain.zeroize();
+    }
+}
+
+impl<K, V, S> ZeroizeOnDrop for ZeroizingChainMap<K, V, S>
+where
+    K: Hash + Eq,
+    V: Zeroize,
+    S: BuildHasher + Default,
+{
+}
+
+/// A [`ChainSet`] that scrubs the elements of a scope when it is removed
+/// and of every scope when the set is dropped, with the limits described
+/// on [`ChainSet::zeroize_layer`].
+///
+/// Dereferences to the underlying set for lookups.
+pub struct ZeroizingChainSet<T>
+where
+    T: Hash + Eq + Zeroize,
+{
+    set: ChainSet<T>,
+}
+
+impl<T> ZeroizingChainSet<T>
+where
+    T: Hash + Eq + Zeroize,
+{
+    pub fn new(set: ChainSet<T>) -> Self {
+        Self { set }
+    }
+
+    pub fn insert(&mut self, value: T) -> bool {
+        self.set.insert(value)
+    }
+
+    pub fn new_child(&mut self) {
+        self.set.new_child();
+    }
+
+    /// Scrubs and removes the current scope. The last scope is emptied
+    /// rather than removed.
+    pub fn remove_child(&mut self) {
+        if let Some(idx) = self.set.sets.len().checked_sub(1) {
+            let _ = self.set.zeroize_layer(idx);
+            self.set.remove_child();
+        }
+    }
+}
+
+impl<T> Default for ZeroizingChainSet<T>
+where
+    T: Hash + Eq + Zeroize,
+{
+    fn default() -> Self {
+        Self::new(ChainSet::default())
+    }
+}
+
+impl<T> Deref for ZeroizingChainSet<T>
+where
+    T: Hash + Eq + Zeroize,
+{
+    type Target = ChainSet<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.set
+    }
+}
+
+impl<T> Drop for ZeroizingChainSet<T>
+where
+    T: Hash + Eq + Zeroize,
+{
+    fn drop(&mut self) {
+        self.set.zeroize();
+    }
+}
+
+impl<T> ZeroizeOnDrop for ZeroizingChainSet<T> where T: Hash + Eq + Zeroize {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn zeroize_scopes() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("user", "admin".to_string());
+        chain_map.new_child();
+        chain_map.insert("password", "hunter2".to_string());
+        chain_map.remove_child_zeroized();
+        assert_eq!(chain_map.child_len(), 1);
+        assert_eq!(chain_map.get("password"), None);
+        assert!(chain_map.zeroize_layer(1).is_err());
+        chain_map.new_child();
+        chain_map.zeroize();
+        assert!(chain_map.is_empty());
+        assert_eq!(chain_map.child_len(), 1);
+    }
+
+    #[test]
+    fn zeroize_captured_layer() {
+        let mut chain_map: ChainMap<_, _> = ChainMap::default();
+        chain_map.insert("password", "hunter2".to_string());
+        let env = chain_map.capture();
+        assert!(matches!(
+            chain_map.zeroize_layer(0),
+            Err(crate::Error::SharedLayer)
+        ));
+        assert_eq!(env.get("password").unwrap(), "hunter2");
+        drop(env);
+        chain_map.zeroize_layer(0).unwrap();
+        assert!(chain_map.is_empty());
+    }
+
+    #[test]
+    fn zeroizing_wrappers() {
+        fn on_drop<T: ZeroizeOnDrop>(_: &T) {}
+        let mut chain_map: ZeroizingChainMap<_, _> = ZeroizingChainMap::default();
+        chain_map.insert("user", "admin".to_string());
+        chain_map.new_child();
+        chain_map.insert("password", "hunter2".to_string());
+        chain_map.insert("password", "hunter3".to_string());
+        assert_eq!(chain_map.get("password").unwrap(), "hunter3");
+        chain_map.remove_child();
+        assert_eq!(chain_map.get("password"), None);
+        assert_eq!(chain_map.child_len(), 1);
+        on_drop(&chain_map);
+
+        let mut chain_set = ZeroizingChainSet::default();
+        chain_set.insert(vec![1u8]);
+        chain_set.new_child();
+        chain_set.insert(vec![2u8]);
+        chain_set.remove_child();
+        assert_eq!(chain_set.get(&vec![2u8]), None);
+        assert!(chain_set.get(&vec![1u8]).is_some());
+        on_drop(&chain_set);
+    }
+
+    #[test]
+    fn zeroize_set() {
+        let mut chain_set = ChainSet::default();
+        chain_set.insert(vec![1u8]);
+        chain_set.new_child();
+        chain_set.insert(vec![2u8]);
+        chain_set.zeroize();
+        assert_eq!(chain_set.sets.len(), 1);
+        assert_eq!(chain_set.get(&vec![1u8]), None);
+    }
+}